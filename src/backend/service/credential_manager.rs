@@ -1,40 +1,121 @@
-use std::fs::File;
-use std::io::Write;
+use super::utility::logger;
+use super::security::{
+    audit::{AuditEvent, AuditLog, Auditor, Outcome},
+    database::{Database, CredentialManager, CredentialRecord},
+    secret::SecretString,
+    vault::Vault,
+};
 
 pub trait Manager {
-    fn new() -> Credential;
-    fn new_credential(&mut self, app: String, username: String, password: String);
+    fn new(database: Database) -> Self;
+    fn new_credential(&mut self, vault: &dyn Vault, app: String, username: String, password: &SecretString) -> bool;
+    fn update_credential(&mut self, vault: &dyn Vault, id: i64, app: String, username: String, password: &SecretString) -> bool;
+    fn delete_credential(&mut self, vault: &dyn Vault, id: i64) -> bool;
+    fn get_credentials(&self, vault: &dyn Vault) -> Vec<CredentialRecord>;
+    fn reveal_password(&self, vault: &dyn Vault, record: &CredentialRecord) -> Option<SecretString>;
     fn set_app(&mut self, app: String);
     fn set_username(&mut self, username: String);
-    fn set_password(&mut self, password: String);
+    fn set_password(&mut self, password: SecretString);
     fn get_app(&self) -> String;
     fn get_username(&self) -> String;
-    fn get_password(&self) -> String;
+    fn get_password(&self) -> &SecretString;
 }
 
+/// Credential struct
+/// - database : database the credentials are stored in
 pub struct Credential {
     app: String,
     username: String,
-    password: String,
+    password: SecretString,
+    database: Database,
 }
 
 impl Manager for Credential {
-    fn new() -> Credential {
+    fn new(database: Database) -> Credential {
         Credential {
             app: String::new(),
             username: String::new(),
-            password: String::new(),
+            password: SecretString::default(),
+            database,
         }
     }
 
-    fn new_credential(&mut self, app: String, username: String, password: String) {
+    /// Store a new credential in the vault of the logged in account
+    /// - the password is encrypted with the vault key before it is written
+    /// - returns false if the vault is locked or the entry could not be stored
+    fn new_credential(&mut self, vault: &dyn Vault, app: String, username: String, password: &SecretString) -> bool {
         self.app = app;
         self.username = username;
-        self.password = password;
-        let path = "users.txt";
-        let mut output = File::create(path).unwrap();
-        let creds = format!("[username]: {} - [password]: {}", self.username, self.password);
-        write!(output, "{}", creds);
+        self.password.clear();
+
+        let vault_key = match vault.get_vault_key() {
+            Some(vault_key) => vault_key,
+            None => return false,
+        };
+
+        let account = vault.get_account_id();
+        let associated_data = entry_associated_data(account.as_str(), self.app.as_str(), self.username.as_str());
+        let sealed = vault_key.seal(password.expose().as_bytes(), &associated_data);
+
+        let mut database = self.database.clone();
+        let saved = database.create_credential(
+            account.as_str(), self.app.as_str(), self.username.as_str(), &sealed).is_ok();
+
+        self.audit(account.as_str(), AuditEvent::VaultWrite, saved, format!("add {}", self.app));
+        saved
+    }
+
+    /// Replace an existing credential in the vault of the logged in account
+    /// - the password is encrypted again with a fresh nonce
+    fn update_credential(&mut self, vault: &dyn Vault, id: i64, app: String, username: String, password: &SecretString) -> bool {
+        self.app = app;
+        self.username = username;
+        self.password.clear();
+
+        let vault_key = match vault.get_vault_key() {
+            Some(vault_key) => vault_key,
+            None => return false,
+        };
+
+        let account = vault.get_account_id();
+        let associated_data = entry_associated_data(account.as_str(), self.app.as_str(), self.username.as_str());
+        let sealed = vault_key.seal(password.expose().as_bytes(), &associated_data);
+
+        let mut database = self.database.clone();
+        let updated = database.update_credential(
+            account.as_str(), id, self.app.as_str(), self.username.as_str(), &sealed).unwrap_or(false);
+
+        self.audit(account.as_str(), AuditEvent::VaultWrite, updated, format!("update {}", self.app));
+        updated
+    }
+
+    /// Delete a credential from the vault of the logged in account
+    fn delete_credential(&mut self, vault: &dyn Vault, id: i64) -> bool {
+        let mut database = self.database.clone();
+        let account = vault.get_account_id();
+        let deleted = database.delete_credential(account.as_str(), id).unwrap_or(false);
+
+        self.audit(account.as_str(), AuditEvent::VaultWrite, deleted, format!("delete #{}", id));
+        deleted
+    }
+
+    /// Get every credential of the logged in account, passwords stay encrypted
+    fn get_credentials(&self, vault: &dyn Vault) -> Vec<CredentialRecord> {
+        let mut database = self.database.clone();
+        database.get_all_credentials(vault.get_account_id().as_str()).unwrap_or_default()
+    }
+
+    /// Decrypt the password of a credential, only done when the credential is viewed.
+    /// A password moved to another entry, or an entry renamed outside of the vault, does not decrypt
+    fn reveal_password(&self, vault: &dyn Vault, record: &CredentialRecord) -> Option<SecretString> {
+        let account = vault.get_account_id();
+        let associated_data = entry_associated_data(account.as_str(), record.app.as_str(), record.username.as_str());
+        let password = vault.get_vault_key()
+            .and_then(|vault_key| vault_key.open(&record.secret, &associated_data))
+            .and_then(|password| std::str::from_utf8(&password).ok().map(SecretString::from));
+
+        self.audit(account.as_str(), AuditEvent::VaultRead, password.is_some(), format!("reveal {}", record.app));
+        password
     }
 
     fn set_app(&mut self, app: String) {
@@ -45,7 +126,7 @@ impl Manager for Credential {
         self.username = username;
     }
 
-    fn set_password(&mut self, password: String) {
+    fn set_password(&mut self, password: SecretString) {
         self.password = password;
     }
 
@@ -57,7 +138,31 @@ impl Manager for Credential {
         self.username.clone()
     }
 
-    fn get_password(&self) -> String {
-        self.password.clone()
+    fn get_password(&self) -> &SecretString {
+        &self.password
     }
 }
+
+impl Credential {
+
+    /// Write a vault read or write of the logged in account to the audit log
+    fn audit(&self, account: &str, event: AuditEvent, succeeded: bool, detail: String) {
+        let outcome = match succeeded {
+            true => Outcome::Success,
+            false => Outcome::Failure,
+        };
+
+        AuditLog::new(self.database.clone()).record(Some(account), "", event, outcome, detail.as_str());
+
+        match succeeded {
+            true => logger::debug("vault", event.as_str(), &[("account", &account), ("detail", &detail)]),
+            false => logger::warn("vault", event.as_str(), &[("account", &account), ("detail", &detail)]),
+        }
+    }
+}
+
+/// Associated data of a credential, binding its password to the account, app and username of
+/// the entry, lengths keep the fields apart
+fn entry_associated_data(account: &str, app: &str, username: &str) -> Vec<u8> {
+    format!("{}:entry:{}:{}:{}", account, app.len(), app, username).into_bytes()
+}
@@ -1,2 +1,17 @@
+pub mod audit;
 pub mod authenticator;
-mod database;
+pub mod blocklists;
+pub mod breach;
+pub mod canonical;
+pub mod database;
+pub mod hasher;
+pub mod migration;
+pub mod pepper;
+pub mod policy;
+pub mod rate_limiter;
+pub mod recovery;
+pub mod secret;
+pub mod session;
+pub mod strength;
+pub mod totp;
+pub mod vault;
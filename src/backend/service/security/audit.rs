@@ -0,0 +1,274 @@
+use chrono::Utc;
+use data_encoding::HEXLOWER;
+use hmac::Mac;
+
+use super::database::{Database, AuditManager, AuditRecord};
+use super::pepper::Pepper;
+use super::super::utility::error::AuthError;
+use super::super::utility::logger;
+
+/// Hash the first event of the log is chained to
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// What happened to an account
+/// - SignUp : an account was created
+/// - Login : a password was checked at login
+/// - SecondFactor : a one time code or recovery code was checked at login
+/// - Unlock : a password was checked to unlock an idle session
+/// - PasswordChange : the password of an account was changed
+/// - VaultRead : a credential password was decrypted
+/// - VaultWrite : a credential was stored, replaced or deleted
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AuditEvent {
+    SignUp,
+    Login,
+    SecondFactor,
+    Unlock,
+    PasswordChange,
+    VaultRead,
+    VaultWrite,
+}
+
+impl AuditEvent {
+
+    /// Name of the event as stored in the log
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEvent::SignUp => "sign up",
+            AuditEvent::Login => "login",
+            AuditEvent::SecondFactor => "second factor",
+            AuditEvent::Unlock => "unlock",
+            AuditEvent::PasswordChange => "password change",
+            AuditEvent::VaultRead => "vault read",
+            AuditEvent::VaultWrite => "vault write",
+        }
+    }
+}
+
+/// Result of an audited event
+/// - Success : the event completed
+/// - Pending : the password was accepted, the login waits for a second factor
+/// - Failure : the event was refused or failed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Success,
+    Pending,
+    Failure,
+}
+
+impl Outcome {
+
+    /// Name of the outcome as stored in the log
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::Pending => "pending",
+            Outcome::Failure => "failure",
+        }
+    }
+
+    /// Outcome and detail of the result of an authenticator call
+    pub fn of(result: &Result<(), AuthError>) -> (Outcome, String) {
+        match result {
+            Ok(()) => (Outcome::Success, String::new()),
+            Err(AuthError::CodeRequired) => (Outcome::Pending, "code required".to_string()),
+            Err(AuthError::UnknownUser) => (Outcome::Failure, "unknown user".to_string()),
+            Err(AuthError::InvalidCredentials) => (Outcome::Failure, "invalid credentials".to_string()),
+            Err(AuthError::LockedOut(_)) => (Outcome::Failure, "locked out".to_string()),
+            Err(AuthError::SessionExpired) => (Outcome::Failure, "session expired".to_string()),
+            Err(AuthError::PolicyViolation(_)) => (Outcome::Failure, "policy violation".to_string()),
+            Err(AuthError::UsernameTaken) => (Outcome::Failure, "username taken".to_string()),
+            Err(AuthError::Storage(_)) => (Outcome::Failure, "storage error".to_string()),
+        }
+    }
+}
+
+/// State of the hash chain of the log
+/// - Intact : every event links to the one before it
+/// - Broken : the event with this id was edited, or events before it were deleted
+/// - Truncated : the latest events were deleted
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ChainStatus {
+    Intact,
+    Broken(i64),
+    Truncated,
+}
+
+/// Traits of the audit log
+pub trait Auditor {
+    fn record(&mut self, account: Option<&str>, username: &str, event: AuditEvent, outcome: Outcome, detail: &str);
+    fn get_events(&self, account: &str) -> Result<Vec<AuditRecord>, AuthError>;
+    fn verify(&self) -> Result<ChainStatus, AuthError>;
+}
+
+/// Append only log of account events, each event holds the hash of the one before it
+/// so edits and deletions can be detected
+/// - database : database the events are stored in
+pub struct AuditLog {
+    database: Database,
+}
+
+impl AuditLog {
+
+    /// Create the audit log of a database
+    pub fn new(database: Database) -> AuditLog {
+        AuditLog { database }
+    }
+}
+
+impl Auditor for AuditLog {
+
+    /// Append an event to the log
+    /// - a failure to write the log never fails the event being audited
+    fn record(&mut self, account: Option<&str>, username: &str, event: AuditEvent, outcome: Outcome, detail: &str) {
+        let record = AuditRecord {
+            id: 0,
+            account: account.filter(|account| !account.is_empty()).map(str::to_string),
+            username: username.to_string(),
+            event: event.as_str().to_string(),
+            outcome: outcome.as_str().to_string(),
+            detail: detail.to_string(),
+            created_at: Utc::now().timestamp(),
+            prev_hash: String::new(),
+            hash: String::new(),
+            key_id: Pepper::global().active_id(),
+        };
+
+        if let Err(error) = self.database.append_audit_event(record) {
+            logger::error("audit", "unable to write audit event",
+                &[("event", &event.as_str()), ("username", &username), ("error", &error.to_string())]);
+        }
+    }
+
+    /// Get the events of an account, latest first
+    fn get_events(&self, account: &str) -> Result<Vec<AuditRecord>, AuthError> {
+        let mut database = self.database.clone();
+        Ok(database.get_audit_events(account)?)
+    }
+
+    /// Walk the whole log and check every link of the hash chain
+    fn verify(&self) -> Result<ChainStatus, AuthError> {
+        let mut database = self.database.clone();
+
+        let mut prev_hash = GENESIS_HASH.to_string();
+        let mut last_id = 0;
+        for record in database.get_audit_chain()? {
+            if record.prev_hash != prev_hash
+                || chain_hash(prev_hash.as_str(), &record).as_deref() != Some(record.hash.as_str()) {
+                return Ok(ChainStatus::Broken(record.id));
+            }
+
+            prev_hash = record.hash;
+            last_id = record.id;
+        }
+
+        // Ids are never reused, a higher sequence means the latest events are gone
+        match database.get_audit_sequence()? > last_id {
+            true => Ok(ChainStatus::Truncated),
+            false => Ok(ChainStatus::Intact),
+        }
+    }
+}
+
+/// Hash of an event chained to the hash of the event before it, an HMAC-SHA256 keyed with the
+/// pepper key the event was recorded with, so the chain cannot be rebuilt without the key
+/// - fields, the event id included, are separated by a control character so they cannot be shifted into each other
+/// - returns None if the event has no key id or its pepper key is no longer available
+pub fn chain_hash(prev_hash: &str, record: &AuditRecord) -> Option<String> {
+    if record.key_id.is_empty() {
+        return None;
+    }
+
+    let id = record.id.to_string();
+    let created_at = record.created_at.to_string();
+    let fields = [
+        prev_hash,
+        record.account.as_deref().unwrap_or(""),
+        record.username.as_str(),
+        record.event.as_str(),
+        record.outcome.as_str(),
+        record.detail.as_str(),
+        created_at.as_str(),
+    ];
+
+    let mut mac = Pepper::global().audit_mac(record.key_id.as_str())?;
+    for field in std::iter::once(id.as_str()).chain(fields) {
+        mac.update(field.as_bytes());
+        mac.update(&[0x1f]);
+    }
+
+    Some(HEXLOWER.encode(&mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::database::{DatabaseManager, DatabasePath, MigrationManager};
+
+    fn log() -> (Database, AuditLog) {
+        let mut database = Database::open(&DatabasePath::Memory).unwrap();
+        database.migrate(false).unwrap();
+        (database.clone(), AuditLog::new(database))
+    }
+
+    /// Append an event chained to the latest one, its hash copied from the latest one
+    /// as there is no key to compute it with
+    fn append_forged(database: &mut Database, key_id: &str) {
+        let latest = database.get_audit_chain().unwrap().pop().unwrap();
+        database.connection().execute(
+            "INSERT INTO audit_events (username, event, outcome, detail, created_at, prev_hash, hash, key_id)
+                VALUES ('mallory', 'login', 'success', '', 1, ?1, ?1, ?2)",
+            (&latest.hash, key_id),
+        ).unwrap();
+    }
+
+    #[test]
+    fn keyed_chain_is_intact() {
+        let (mut database, mut log) = log();
+        log.record(Some("a1"), "alice", AuditEvent::Login, Outcome::Success, "");
+        log.record(None, "mallory", AuditEvent::Login, Outcome::Failure, "unknown user");
+
+        let chain = database.get_audit_chain().unwrap();
+        assert!(chain.iter().all(|record| record.key_id == Pepper::global().active_id()));
+        assert_eq!(log.verify().unwrap(), ChainStatus::Intact);
+    }
+
+    #[test]
+    fn hash_covers_event_id() {
+        let (mut database, mut log) = log();
+        log.record(Some("a1"), "alice", AuditEvent::Login, Outcome::Success, "");
+
+        let mut record = database.get_audit_chain().unwrap().remove(0);
+        assert_eq!(chain_hash(GENESIS_HASH, &record).as_deref(), Some(record.hash.as_str()));
+        record.id += 1;
+        assert_ne!(chain_hash(GENESIS_HASH, &record).as_deref(), Some(record.hash.as_str()));
+    }
+
+    #[test]
+    fn edited_event_breaks_chain() {
+        let (database, mut log) = log();
+        log.record(Some("a1"), "alice", AuditEvent::Login, Outcome::Failure, "invalid credentials");
+        log.record(Some("a1"), "alice", AuditEvent::Login, Outcome::Success, "");
+
+        database.connection().execute_batch(
+            "DROP TRIGGER audit_events_no_update;
+            UPDATE audit_events SET outcome = 'success', detail = '' WHERE id = 1;").unwrap();
+        assert_eq!(log.verify().unwrap(), ChainStatus::Broken(1));
+    }
+
+    #[test]
+    fn event_without_key_breaks_chain() {
+        let (mut database, mut log) = log();
+        log.record(Some("a1"), "alice", AuditEvent::Login, Outcome::Success, "");
+        append_forged(&mut database, "");
+        assert_eq!(log.verify().unwrap(), ChainStatus::Broken(2));
+    }
+
+    #[test]
+    fn event_with_unknown_key_breaks_chain() {
+        let (mut database, mut log) = log();
+        log.record(Some("a1"), "alice", AuditEvent::Login, Outcome::Success, "");
+        append_forged(&mut database, "retired");
+        assert_eq!(log.verify().unwrap(), ChainStatus::Broken(2));
+    }
+}
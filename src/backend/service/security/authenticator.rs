@@ -1,20 +1,36 @@
-use std::{fs::File, io::{BufReader, BufRead}, collections::HashSet};
 
-use bcrypt::{hash_with_salt, verify, DEFAULT_COST};
-use rustrict::CensorStr;
 use regex::Regex;
 use unicode_normalization::UnicodeNormalization;
 use rand::Rng;
-use super::database::{Database, DatabaseManager, AccountManager, Record};
-use super::super::utility::response::Response;
+use super::blocklists::Blocklists;
+use super::audit::{AuditEvent, AuditLog, Auditor, Outcome};
+use super::database::{self, Database, AccountManager, VaultManager, TotpManager, RecoveryManager, Record, VaultRecord};
+use super::vault::{Sealed, Vault, VaultKey, SALT_LENGTH};
+use super::hasher::Hasher;
+use super::pepper::Pepper;
+use super::policy::{Policy, DEFAULT_ROLE};
+use super::rate_limiter::{Limiter, RateLimiter};
+use super::recovery::{self, Recovery};
+use super::secret::SecretString;
+use super::strength::{self, Estimate};
+use super::totp::{Totp, TotpEnrollment, TwoFactor};
+use super::super::utility::error::AuthError;
+use super::super::utility::logger::{self, Level};
+
+/// Free usernames suggested in place of a taken one
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Highest number appended to a taken username when looking for free ones
+const MAX_SUGGESTION_SUFFIX: u32 = 20;
 
 /// Traits of an account verifier
 pub trait Verifier {
-    fn read_file(&self, file: &str) -> Vec<String>;
-    fn read_file_hashset(&self, file: &str) -> HashSet<String>;
-    fn validate_account(&self, username: String, password: String) -> bool;
-    fn validate_username(&self, username: String) -> Response;
-    fn validate_password(&self, password: String) -> Response;
+    fn validate_account(&self, username: String, password: &SecretString) -> Result<(), AuthError>;
+    fn validate_username(&self, username: String) -> Result<(), AuthError>;
+    fn check_username(&self, username: &str) -> Result<(), AuthError>;
+    fn suggest_usernames(&self, username: &str) -> Vec<String>;
+    fn validate_password(&self, username: &str, password: &SecretString) -> Result<(), AuthError>;
+    fn estimate_password(&self, username: &str, password: &SecretString) -> Estimate;
     fn validate_swear_words_regex_pattern_match(&self, text: String) -> bool;
 }
 
@@ -25,45 +41,71 @@ pub trait Username {
     fn get_username(&self) -> String;
 }
 
-/// Traits of an account password
+/// Traits of an account password, passwords are only ever borrowed as secrets
 pub trait Password {
 
     fn generate_salt(&self) -> [u8; 16];
-    fn generate_password(&self, password: String) -> String;
-    fn hash_password(&self, password: String, salt: [u8; 16]) -> String;
-    fn compare_password(&self, password: String, hash: String) -> bool;
-    fn set_password(&mut self, password: String);
+    fn generate_password(&self, password: &SecretString, pepper_id: Option<&str>) -> Option<SecretString>;
+    fn hash_password(&self, password: &SecretString, salt: [u8; 16]) -> SecretString;
+    fn compare_password(&self, password: &SecretString, hash: &str) -> bool;
+    fn needs_rehash(&self, hash: &str) -> bool;
 }
 
 
 /// These traits together are what creates our credential
 pub trait Credential: Verifier + Password + Username {
 
-    fn new() -> Self;
-    fn login(&mut self, username: String, password: String) -> Response;
-    fn create_account(&mut self, username: String, password: String) -> bool;
-    fn change_password(&self, password: String, new_password: String) -> Response;
+    fn new(database: Database) -> Self;
+    fn login(&mut self, username: String, password: &SecretString) -> Result<(), AuthError>;
+    fn create_account(&mut self, username: String, password: &SecretString) -> Result<(), AuthError>;
+    fn change_password(&mut self, password: &SecretString, new_password: &SecretString) -> Result<(), AuthError>;
+    fn unlock(&mut self, password: &SecretString) -> Result<(), AuthError>;
 }
 
-/// Account struct 
+/// Account struct, the password is never kept once it has been hashed or verified
+/// - id : account id, known once logged in
+/// - role : role of the account, selecting the rules of the password policy
+/// - password_changed_at : unix time the password was last changed, known once logged in
+/// - vault : vault key, unlocked at login
+/// - database : database the account is stored in
+/// - pending_totp : totp secret generated for an enrollment not confirmed yet
+/// - awaiting_code : the password was accepted, the login waits for a totp code
 pub struct Account {
+    id: String,
     username: String,
-    password: String,
+    role: String,
+    password_changed_at: Option<i64>,
+    vault: Option<VaultKey>,
+    database: Database,
+    pending_totp: Option<Totp>,
+    awaiting_code: bool,
 }
 
 /// A password behviour for an account
 impl Verifier for Account {
 
-    /// Validate the account against the username and password 
-    fn validate_account(&self, username: String, password: String) -> bool {
+    /// Validate the account against the username and password, reasons of both are returned
+    fn validate_account(&self, username: String, password: &SecretString) -> Result<(), AuthError> {
         // TODO: Pull record from database, and compare password
         // proper validation should be done here
-        
-        self.validate_username(username).validity && self.validate_password(password).validity
+
+        let reasons: Vec<String> = [self.validate_username(username.to_owned()), self.validate_password(username.as_str(), password)]
+            .into_iter()
+            .filter_map(|result| match result {
+                Err(AuthError::PolicyViolation(reasons)) => Some(reasons),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+
+        match reasons.is_empty() {
+            true => Ok(()),
+            false => Err(AuthError::PolicyViolation(reasons)),
+        }
     }
 
     /// Validate the username being passed through
-    fn validate_username(&self, username: String) -> Response {
+    fn validate_username(&self, username: String) -> Result<(), AuthError> {
 
         // Implement regex filter from requirements
         let regex_pattern = r"^[a-zA-Z0-9_]+$";
@@ -76,128 +118,73 @@ impl Verifier for Account {
         // requirements are not met
         if re.is_match(&normalization) {
 
-            // Speed of (o(n^2)) - not ideal using regex expressions
-            // return self.validate_swear_words_regex_pattern_match(normalization)
+            // Reserved names are refused even when allowlisted
+            if Blocklists::global().is_reserved(normalization.as_str()) {
+                return Err(policy_violation("Username is reserved, not allowed"))
+            }
 
-            // Speed of o(n) - better choice integration with std library
-            match normalization.is_inappropriate() {
+            // Patterns are compiled once into a RegexSet, and like the profanity check of rustrict
+            // every leetspeak reading is checked so numbers cannot stand in for letters (rule A.4)
+            match !self.validate_swear_words_regex_pattern_match(normalization.to_owned())
+                || Blocklists::global().is_profane(normalization.as_str()) {
                 true => {
-                    return Response {
-                        validity: false,
-                        message: "Inappropriate username, not allowed".to_string(),
-                    }
+                    return Err(policy_violation("Inappropriate username, not allowed"))
                 },
                 false => {
-                    return Response {
-                        validity: true,
-                        message: "Username is valid".to_string(),
-                    }
+                    return Ok(())
                 },
             }
         }
-        Response {
-            validity: false,
-            message: "Invalid username, characters not usable".to_string(),
-        }
+        Err(policy_violation("Invalid username, characters not usable"))
     }
 
-    /// Validate the password being passed through before being stored
-    fn validate_password(&self, password: String) -> Response {
-
-        // 9 rules to follow
-        // 1 - Password length must be between 8 - 64 characters long
-        // 2 - Check passwords against a list of known weak passwords / blacklist
-        // 3 - Make special characters optional
-        // 4 - Provide the user feedback on password attempts if they fail and why
-        // 5 - Do not provide password hints
-        // 6 - Implement rate limiting mechanisms to prevent brute force attacks
-        // 7 - Use password managers safely, or create one yourself :)
-        // 8 - Change password only when neccessary
-        // 9 - Store passwords in offline - attack - resistant forms.
-        // ===============================================================================
-        // Complexity really is about length rather than mix of characters 
-        // Password composition allow for printable characters as well as spaces, unicode
-        // characters etc. Avoid using "spaces" and tabs in the password.
-        // Use weak password text to verify against
-        // Do not provide password hints to the user 
-        // Implement rate limiting mechanisms to prevent brute force attacks (max 3)
-        // Password expiration, store using bcrypt, script, argon2
-
-        // 1 - Check password length between 8 - 64 characters long
-        let check_password_length = password.len() >= 8 && password.len() <= 64;
-
-        // 2 - Check passwords against a list of known weak passwords / blacklist
-        let weak_passwords = self.read_file_hashset("weakpasswords.txt");
-        let breached_passwords = self.read_file_hashset("breachedpasswords.txt");
-
-        // if 1st rule is met, check for second rule and return true if both are met
-        if check_password_length {
-            if breached_passwords.contains(&password) {
-
-                // Password found in blacklist
-                return Response {
-                    validity: false,
-                    message: "Password compromised, found online".to_string(),
-                }
-            } else if weak_passwords.contains(&password) {
-
-                // Password not found in blacklist and meets length requirements
-                return Response {
-                    validity: false,
-                    message: "Password is weak, use another".to_string(),
-                }
-            } else {
-
-                return Response {
-                    validity: true,
-                    message: "Password is secure".to_string(),
-                }
-            }
-        } else {
+    /// Check a username can be signed up with: valid, and neither taken nor looking like
+    /// an existing username, whatever its case
+    fn check_username(&self, username: &str) -> Result<(), AuthError> {
+        self.validate_username(username.to_owned())?;
 
-            // Password does not meet length requirements
-            return Response {
-                validity: false,
-                message: "Password length is not acceptable".to_string(),
-            }
+        let username: String = username.nfkc().collect();
+        match self.database.clone().get_confusable_account(username.as_str())? {
+            Some(_) => Err(AuthError::UsernameTaken),
+            None => Ok(()),
         }
+    }
 
-        // 6 - Implement rate limiting checks
-        // TODO
+    /// Suggest free usernames in place of a taken one, such as `alice_2` or `alice2` for `alice`,
+    /// every suggestion passes check_username
+    fn suggest_usernames(&self, username: &str) -> Vec<String> {
+        (1..=MAX_SUGGESTION_SUFFIX)
+            .flat_map(|number| [format!("{}_{}", username, number), format!("{}{}", username, number)])
+            .filter(|candidate| self.check_username(candidate.as_str()).is_ok())
+            .take(MAX_SUGGESTIONS)
+            .collect()
     }
 
-    /// Read a file and return a vector of strings
-    fn read_file(&self, file_name: &str) -> Vec<String> {
+    /// Validate the password being passed through before being stored, against the
+    /// password policy of the role of the account. Every failed rule is returned
+    /// - username : username of the account, refused within the password
+    fn validate_password(&self, username: &str, password: &SecretString) -> Result<(), AuthError> {
 
-        let mut list = Vec::new();
-        let file = File::open(file_name).unwrap();
-        let reader = BufReader::new(file);
-        for line in reader.lines() {
-            list.push(line.unwrap());
+        // Length, blocklists, context words, repeated and sequential characters are
+        // declared by the policy, special characters stay optional and no hints are given
+        let reasons = Policy::global().check(self.role.as_str(), username, password);
+
+        match reasons.is_empty() {
+            true => Ok(()),
+            false => Err(AuthError::PolicyViolation(reasons)),
         }
-        list
     }
 
-    /// Read a file and return a hashset of strings
-    fn read_file_hashset(&self, file_name: &str) -> HashSet<String> {
-
-        let file = File::open(file_name).unwrap();
-        let reader = BufReader::new(file);
-        let list: HashSet<String> = reader.lines().map(|line| line.unwrap()).collect();
-
-        return list
+    /// Estimate the strength of a password being typed, with the blocklists and context
+    /// words of the policy of the role of the account
+    fn estimate_password(&self, username: &str, password: &SecretString) -> Estimate {
+        strength::estimate(Policy::global().rules(self.role.as_str()), username, password.expose())
     }
 
-    /// Validate the usernames against a list of swear words using regex
+    /// Validate the usernames against a list of swear words using regex, the patterns
+    /// are compiled once when the blocklists are loaded or reloaded
     fn validate_swear_words_regex_pattern_match(&self, text: String) -> bool {
-        let filter_patterns = self.read_file("regex.txt");
-        for pattern in filter_patterns {
-            let re = Regex::new(pattern.as_str()).unwrap();
-            if re.is_match(text.as_str()) {
-                return false;
-            }
-        }
-        true
+        !Blocklists::global().matches_pattern(text.as_str())
     }
 }
 
@@ -216,43 +203,43 @@ impl Password for Account {
         salt
     }
 
-    fn generate_password(&self, password: String) -> String {
-
-        // No longer needed
-        const PEPPER: &str = "PkCt&farjdWL2&WTaoddA2u7S4hfxDkbtNFxxU92";
-
-        // let x = password.extend(PEPPER.as_bytes().iter().map(|x| x.to_string()));
-        // Clone the password into bytes to be concatenated with the pepper
-        let extended_password: String = password + PEPPER;
-        extended_password
+    /// Pepper the password with the pepper key the hash was made with
+    /// - pepper_id : None for hashes made before the pepper was externalised
+    /// - returns None if the pepper key is no longer available
+    fn generate_password(&self, password: &SecretString, pepper_id: Option<&str>) -> Option<SecretString> {
+        Pepper::global()
+            .apply(pepper_id, password.expose())
+            .map(SecretString::from)
     }
 
-    /// Hash function for our password
-    fn hash_password(&self, password: String, salt: [u8; 16]) -> String {
+    /// Hash function for our password, peppered with the active pepper key
+    fn hash_password(&self, password: &SecretString, salt: [u8; 16]) -> SecretString {
 
         // Get newly generated password
-        let generated_password = self.generate_password(password);
+        let pepper_id = Pepper::global().active_id();
+        let generated_password = self.generate_password(password, Some(pepper_id.as_str()))
+            .expect("active pepper key");
 
-        // Convert extended password to bytes and begin hashing with salt
-        let hash = hash_with_salt(generated_password.clone(), DEFAULT_COST, self.generate_salt()).unwrap();
+        // Convert extended password to bytes and begin hashing with salt,
+        // the result is a self-describing PHC string
+        let hash = Hasher::default().hash(generated_password.expose().as_bytes(), &salt);
 
         // Confirm hash
-        let result = self.compare_password(generated_password, hash.to_string());
-        println!("\nHash result one : {}", result);
-
-        // hash.to_string().clone()
+        let result = Hasher::default().verify(generated_password.expose().as_bytes(), &hash);
+        logger::debug("authenticator", "password hashed", &[("verified", &result), ("pepper_id", &pepper_id)]);
 
-        hash.to_string()
+        SecretString::new(hash)
     }
 
     /// compare the password with the hash to verify if they match
-    fn compare_password(&self, password: String, hash: String) -> bool {
-        verify(password, &hash).unwrap()
+    /// - the algorithm is read from the hash, so argon2id, scrypt and bcrypt hashes all verify
+    fn compare_password(&self, password: &SecretString, hash: &str) -> bool {
+        Hasher::default().verify(password.expose().as_bytes(), hash)
     }
 
-    /// Set | Change an existing password 
-    fn set_password(&mut self, password: String) {
-        self.password = password
+    /// Check if a hash was made with an outdated algorithm or cost
+    fn needs_rehash(&self, hash: &str) -> bool {
+        Hasher::default().needs_rehash(hash)
     }
 }
 
@@ -272,74 +259,295 @@ impl Username for Account {
     }
 }
 
-/// Connect Database 
-struct Db {
-    database: Database,
-}
-
 /// Implement the credential behavior of our account
 impl Credential for Account {
 
-    /// Create a new account
-    fn new() -> Account {
+    /// Create a new account stored in the given database
+    fn new(database: Database) -> Account {
         Account {
+            id: String::new(),
             username: String::new(),
-            password: String::new(),
+            role: DEFAULT_ROLE.to_string(),
+            password_changed_at: None,
+            vault: None,
+            database,
+            pending_totp: None,
+            awaiting_code: false,
+        }
+    }
+
+    /// Login to an existing account, every attempt is written to the audit log
+    fn login(&mut self, username: String, password: &SecretString) -> Result<(), AuthError> {
+        let result = self.attempt_login(username.to_owned(), password);
+        self.audit(AuditEvent::Login, username.as_str(), &result);
+        result
+    }
+
+    /// Create an account, every attempt is written to the audit log
+    fn create_account(&mut self, username: String, password: &SecretString) -> Result<(), AuthError> {
+        let result = self.attempt_create_account(username.to_owned(), password);
+        self.audit(AuditEvent::SignUp, username.as_str(), &result);
+        result
+    }
+
+    /// Change the password of an existing account, every attempt is written to the audit log
+    fn change_password(&mut self, password: &SecretString, new_password: &SecretString) -> Result<(), AuthError> {
+        let result = self.attempt_change_password(password, new_password);
+        self.audit(AuditEvent::PasswordChange, self.username.as_str(), &result);
+        result
+    }
+
+    /// Unlock a locked session of the logged in account, every attempt is written to the audit log
+    fn unlock(&mut self, password: &SecretString) -> Result<(), AuthError> {
+        let result = self.attempt_unlock(password);
+        self.audit(AuditEvent::Unlock, self.username.as_str(), &result);
+        result
+    }
+}
+
+/// Implement the vault behavior of our account
+/// - The vault key is random and wrapped by a key derived from the master password
+/// - The account id is used as associated data so vaults cannot be swapped between accounts
+impl Vault for Account {
+
+    /// Unlock the vault of a logged in account, creating the vault on first use
+    fn unlock_vault(&mut self, password: &str) -> Result<(), rusqlite::Error> {
+
+        let mut database = self.database.clone();
+
+        match database.get_vault(self.id.as_str())? {
+            Some(vault) => {
+                let derived_key = VaultKey::derive(password, &vault.salt);
+                self.vault = derived_key.unwrap(&vault.key, self.id.as_bytes());
+            },
+            None => {
+                let salt = VaultKey::generate_salt();
+                let derived_key = VaultKey::derive(password, &salt);
+                let vault_key = VaultKey::generate();
+
+                database.create_vault(self.id.as_str(), &salt, &derived_key.wrap(&vault_key, self.id.as_bytes()))?;
+                self.vault = Some(vault_key);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wrap the unlocked vault key under a new master password, None while the vault is locked
+    fn rewrap_vault(&self, password: &str) -> Option<([u8; SALT_LENGTH], Sealed)> {
+        let vault_key = self.vault.as_ref()?;
+
+        let salt = VaultKey::generate_salt();
+        let derived_key = VaultKey::derive(password, &salt);
+        Some((salt, derived_key.wrap(vault_key, self.id.as_bytes())))
+    }
+
+    /// Forget the vault key, the master password is needed to unlock it again
+    fn lock_vault(&mut self) {
+        self.vault = None;
+    }
+
+    /// Get the vault key of the unlocked vault
+    fn get_vault_key(&self) -> Option<&VaultKey> {
+        self.vault.as_ref()
+    }
+
+    /// Get the id of the account owning the vault
+    fn get_account_id(&self) -> String {
+        self.id.clone()
+    }
+}
+
+/// Implement the second factor behavior of our account
+/// - The totp secret is sealed with the vault key, so it can only be read once the password is accepted
+impl TwoFactor for Account {
+
+    /// Check if the account is enrolled in totp
+    fn has_totp(&self) -> Result<bool, AuthError> {
+        let mut database = self.database.clone();
+        Ok(database.get_totp(self.id.as_str())?.is_some())
+    }
+
+    /// Generate a new totp secret for the logged in account, kept until it is confirmed
+    fn enroll_totp(&mut self) -> Result<TotpEnrollment, AuthError> {
+        if self.vault.is_none() {
+            return Err(AuthError::Storage("credential vault is locked".to_string()));
         }
+
+        let totp = Totp::generate();
+        let enrollment = totp.enrollment(self.username.as_str());
+        self.pending_totp = Some(totp);
+
+        Ok(enrollment)
+    }
+
+    /// Confirm the pending totp secret with a code from the authenticator app, then store it
+    fn confirm_totp(&mut self, code: &str) -> Result<(), AuthError> {
+        let (totp, vault_key) = match (&self.pending_totp, &self.vault) {
+            (Some(totp), Some(vault_key)) => (totp, vault_key),
+            _ => return Err(AuthError::InvalidCredentials),
+        };
+
+        let step = totp.verify(code, 0).ok_or(AuthError::InvalidCredentials)?;
+        let sealed = vault_key.seal(totp.secret(), totp_associated_data(self.id.as_str()).as_bytes());
+
+        let mut database = self.database.clone();
+        database.save_totp(self.id.as_str(), &sealed, step)?;
+        self.pending_totp = None;
+
+        Ok(())
+    }
+
+    /// Finish a login waiting for a code, every attempt is written to the audit log
+    fn verify_totp(&mut self, code: &str) -> Result<(), AuthError> {
+        let result = self.attempt_totp(code);
+        self.audit(AuditEvent::SecondFactor, self.username.as_str(), &result);
+        result
+    }
+
+    /// Abandon a pending enrollment, or a login waiting for a code
+    fn cancel_totp(&mut self) {
+        self.pending_totp = None;
+
+        if self.awaiting_code {
+            self.awaiting_code = false;
+            self.vault = None;
+        }
+    }
+}
+
+/// Implement the recovery code behavior of our account
+/// - Codes are hashed and peppered like passwords, they cannot be shown again once generated
+impl Recovery for Account {
+
+    /// Replace the recovery codes of the logged in account
+    fn generate_recovery_codes(&mut self) -> Result<Vec<SecretString>, AuthError> {
+        if self.vault.is_none() {
+            return Err(AuthError::Storage("credential vault is locked".to_string()));
+        }
+
+        let codes = recovery::generate_codes();
+        let hashes: Vec<SecretString> = codes
+            .iter()
+            .map(|code| self.hash_password(&recovery::normalize(code.expose()), self.generate_salt()))
+            .collect();
+
+        let mut database = self.database.clone();
+        database.replace_recovery_codes(self.id.as_str(), &hashes, Pepper::global().active_id().as_str())?;
+
+        Ok(codes)
+    }
+
+    /// Finish a login waiting for a code with a recovery code, every attempt is written to the audit log
+    fn verify_recovery_code(&mut self, code: &str) -> Result<(), AuthError> {
+        let result = self.attempt_recovery_code(code);
+        self.audit(AuditEvent::SecondFactor, self.username.as_str(), &result);
+        result
+    }
+
+    /// Count the recovery codes of the account not used yet
+    fn count_recovery_codes(&self) -> Result<usize, AuthError> {
+        let mut database = self.database.clone();
+        Ok(database.get_recovery_codes(self.id.as_str())?.len())
+    }
+}
+
+/// Checks behind the credential, second factor and recovery behavior of our account,
+/// the trait methods write their result to the audit log
+impl Account {
+
+    /// Role of the account, the default role until logged in
+    pub fn get_role(&self) -> &str {
+        self.role.as_str()
+    }
+
+    /// Unix time the password was last changed, None until logged in
+    pub fn get_password_changed_at(&self) -> Option<i64> {
+        self.password_changed_at
     }
 
     /// Login to an existing account
-    fn login(&mut self, username: String, password: String) -> Response {
-        // TODO
+    /// - unknown usernames are hashed and counted as failures too, so they cannot be told
+    ///   apart from wrong passwords by timing or throttling
+    /// - accounts enrolled in totp return CodeRequired, the login is finished by verify_totp
+    fn attempt_login(&mut self, username: String, password: &SecretString) -> Result<(), AuthError> {
         // Get password hash && username from the database,
         // - verify the password hash by providing the password with the hash and using verify
         // function
         // Get password from the user and verify that with the hash from the database.
         // Compare the two, if it is true then allow the user to login
 
-        let mut db = Db {
-            database: Database::new()
-        };
+        let mut database = self.database.clone();
 
-        self.set_username(username.to_owned());
-        self.set_password(password.to_owned());
+        // Refuse the attempt while the username or every username is throttled
+        let mut limiter = RateLimiter::new(self.database.clone());
+        if let Some(wait) = limiter.get_wait(username.as_str())? {
+            return Err(AuthError::LockedOut(wait));
+        }
 
-        // Get password
-        let generated_password = self.generate_password(password.clone());
+        self.set_username(username.to_owned());
 
         // Get the hash from the database
-        let result = db.database.get_account(username.clone().as_str().clone()).unwrap();
+        let result = match database.get_account(username.as_str()) {
+            Ok(result) => result,
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                let _ = Hasher::default().hash(password.expose().as_bytes(), &self.generate_salt());
+                limiter.record_failure(username.as_str())?;
+                return Err(AuthError::UnknownUser);
+            },
+            Err(error) => return Err(error.into()),
+        };
         let db_password_hash = result.password;
-        let db_username = result.username;
-        let db_logged_in = result.logged_in;
 
-        let verified = verify(generated_password, &db_password_hash);
+        // Get password, peppered with the key the hash was made with, wiped once verified
+        let verified = match self.generate_password(password, result.pepper_id.as_deref()) {
+            Some(generated_password) => self.compare_password(&generated_password, db_password_hash.as_str()),
+            None => return Err(AuthError::Storage("pepper key of this account is not available".to_string())),
+        };
 
         match verified {
-            Ok(true) => {
+            true => {
 
-                // println!("Password verified");
-                return Response {
-                    validity: true,
-                    message: "Success".to_string(),
+                // Upgrade hashes made with an outdated algorithm, cost or pepper key
+                let pepper_id = Pepper::global().active_id();
+                if self.needs_rehash(db_password_hash.as_str()) || result.pepper_id.as_deref() != Some(pepper_id.as_str()) {
+                    let salt: [u8; 16] = self.generate_salt();
+                    let password_hash: SecretString = self.hash_password(password, salt);
+                    // The old hash still verifies, a failed upgrade is retried at the next login
+                    match database.update_account_password(
+                        result.id.as_str(), password_hash.expose(), salt, pepper_id.as_str()) {
+                        Ok(_) => logger::info("authenticator", "password rehashed", &[("username", &username), ("pepper_id", &pepper_id)]),
+                        Err(e) => logger::error("authenticator", "password rehash failed", &[("username", &username), ("error", &e.to_string())]),
+                    }
                 }
-            },
-            Ok(false) => {
 
-                // println!("Invalid credentials, try again");
-                return Response {
-                    validity: false,
-                    message: "Invalid credentials, try again".to_string(),
+                // Unlock the credential vault with the master password, the account keeps
+                // the username as it was signed up with, whatever case it was typed in
+                self.id = result.id;
+                self.role = result.role;
+                self.password_changed_at = result.password_changed_at;
+                self.set_username(result.username);
+                self.unlock_vault(password.expose())?;
+                if self.vault.is_none() {
+                    return Err(AuthError::Storage("unable to unlock credential vault".to_string()));
                 }
+
+                // Second factor, failures are only forgotten once the code is accepted
+                if self.has_totp()? {
+                    self.awaiting_code = true;
+                    return Err(AuthError::CodeRequired);
+                }
+
+                // Forget previous failed attempts
+                limiter.record_success(username.as_str())?;
+
+                Ok(())
             },
-            Err(_) => {
-                println!("Invalid credentials");
-            }
-        }
+            false => {
 
-        return Response {
-            validity: false,
-            message: "Unable to login to account".to_string(),
+                limiter.record_failure(username.as_str())?;
+                Err(AuthError::InvalidCredentials)
+            },
         }
     }
 
@@ -349,111 +557,269 @@ impl Credential for Account {
     /// - Validate the Username
     /// - Validate the Password
     /// - Create the account
-    fn create_account(&mut self, username: String, password: String) -> bool {
+    fn attempt_create_account(&mut self, username: String, password: &SecretString) -> Result<(), AuthError> {
 
-        // let account_validity = self.validate_account(username.to_owned(), password.to_owned());
-        match self.validate_account(username.to_owned(), password.to_owned()) {
-            true => {
-                // create account using database query here
-                // or create account using the offline-status-store such 
-                // as keyring / TODO: Figure which one
-                let mut db = Db {
-                    database: Database::new()
-                };
+        self.validate_account(username.to_owned(), password)?;
+
+        // create account using database query here
+        // or create account using the offline-status-store such 
+        // as keyring / TODO: Figure which one
+        let mut database = self.database.clone();
 
-                // let result = db.database.get_account(username.clone().as_str().clone()).unwrap();
-                // let db_username = result.username;
+        // Compatibility characters are stored folded, `ａｌｉｃｅ` is stored as `alice`, and
+        // usernames looking like an existing one are refused, whatever their case
+        let username: String = username.nfkc().collect();
+        if database.get_confusable_account(username.as_str())?.is_some() {
+            return Err(AuthError::UsernameTaken);
+        }
 
-                self.set_username(username.to_owned());
-                self.set_password(password.to_owned());
+        self.set_username(username.to_owned());
 
-                // - Generate salt,
-                let salt: [u8; 16] = self.generate_salt();
+        // - Generate salt,
+        let salt: [u8; 16] = self.generate_salt();
 
-                let generated_password = self.generate_password(password.clone());
+        let pepper_id = Pepper::global().active_id();
 
-                // - Generate password hash 
-                let password_hash: String = self.hash_password(password.clone(), salt);
+        // - Generate password hash 
+        let password_hash: SecretString = self.hash_password(password, salt);
 
-                // Confirm hash
-                let result = self.compare_password(generated_password, password_hash.clone());
-                println!("\nHash result two : {}", result);
+        // Confirm hash
+        let generated_password = self.generate_password(password, Some(pepper_id.as_str()))
+            .expect("active pepper key");
+        let result = self.compare_password(&generated_password, password_hash.expose());
+        drop(generated_password);
+        logger::debug("authenticator", "account password confirmed", &[("username", &username), ("verified", &result)]);
+
+        // - Store salt, password hash and pepper key id in Database, the unique index on the
+        // canonical username still refuses an account signed up since the check above
+        match database.create_account(username.as_str(), password_hash.expose(), salt, pepper_id.as_str()) {
+            Ok(()) => Ok(()),
+            Err(error) if database::is_constraint_violation(&error) => Err(AuthError::UsernameTaken),
+            Err(error) => Err(error.into()),
+        }
+    }
 
-                // - Store salt and password hash in Database
-                let result = db.database.create_account(username.as_str(), password_hash.as_str(), salt).unwrap();
+    /// Change the password of an existing account, the new password follows the rules of signup
+    /// and the current password and the last ones of the password history are refused
+    /// - wrong current passwords count towards the rate limit like at login
+    fn attempt_change_password(&mut self, password: &SecretString, new_password: &SecretString) -> Result<(), AuthError> {
 
-                return result
-            },
-            false => false
+        // The vault key is wrapped again under the new password, it cannot be while locked
+        if self.vault.is_none() {
+            return Err(AuthError::Storage("credential vault is locked".to_string()));
         }
 
-    }
+        let mut database = self.database.clone();
+
+        let mut limiter = RateLimiter::new(self.database.clone());
+        if let Some(wait) = limiter.get_wait(self.username.as_str())? {
+            return Err(AuthError::LockedOut(wait));
+        }
 
-    /// Change the password of an existing account
-    fn change_password(&self, password: String, new_password: String) -> Response {
+        // Get the hash from the database
+        let result = match database.get_account(self.username.as_str()) {
+            Ok(result) => result,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Err(AuthError::UnknownUser),
+            Err(error) => return Err(error.into()),
+        };
+        let db_password_hash = result.password;
 
-        let mut db = Db {
-            database: Database::new()
+        // Get password, peppered with the key the hash was made with, wiped once verified
+        let verified = match self.generate_password(password, result.pepper_id.as_deref()) {
+            Some(generated_password) => self.compare_password(&generated_password, db_password_hash.as_str()),
+            None => return Err(AuthError::Storage("pepper key of this account is not available".to_string())),
         };
 
-        // Get password
-        let generated_password = self.generate_password(password.clone());
+        if !verified {
+            limiter.record_failure(self.username.as_str())?;
+            return Err(AuthError::InvalidCredentials);
+        }
+        limiter.record_success(self.username.as_str())?;
+
+        // The new password follows the same rules as at signup
+        self.validate_password(self.username.as_str(), new_password)?;
+
+        // The current password is always refused, the previous ones as long as the policy keeps them
+        let history = Policy::global().rules(self.role.as_str()).password_history;
+        if self.is_reused_password(new_password, result.pepper_id.as_deref(), db_password_hash.as_str()) {
+            return Err(policy_violation("Password must differ from the current password"));
+        }
+        let reused = database.get_password_history(result.id.as_str())?
+            .iter()
+            .take(history)
+            .any(|entry| self.is_reused_password(new_password, entry.pepper_id.as_deref(), entry.password.as_str()));
+        if reused {
+            return Err(AuthError::PolicyViolation(vec![
+                format!("Password must not be one of your last {} passwords", history)]));
+        }
+
+        // generate salt
+        let salt: [u8; 16] = self.generate_salt();
+
+        // generate new password hash
+        let password_hash: SecretString = self.hash_password(new_password, salt);
+
+        // Wrap the vault key under the new password, stored with it so neither is changed without the other
+        let (vault_salt, vault_key) = self.rewrap_vault(new_password.expose())
+            .ok_or_else(|| AuthError::Storage("credential vault is locked".to_string()))?;
+        let vault = VaultRecord { salt: vault_salt.to_vec(), key: vault_key };
+
+        let changed_at = database.change_account_password(
+            result.id.as_str(), password_hash.expose(), salt, Pepper::global().active_id().as_str(), history, &vault)?;
+        self.password_changed_at = Some(changed_at);
+
+        Ok(())
+    }
+
+    /// Check if a password verifies against a hash of the account, a hash made with a pepper
+    /// key that is no longer available cannot be checked
+    fn is_reused_password(&self, password: &SecretString, pepper_id: Option<&str>, hash: &str) -> bool {
+        match self.generate_password(password, pepper_id) {
+            Some(generated_password) => self.compare_password(&generated_password, hash),
+            None => false,
+        }
+    }
+
+    /// Unlock a locked session of the logged in account with its password
+    /// - attempts count towards the rate limit like logins
+    fn attempt_unlock(&mut self, password: &SecretString) -> Result<(), AuthError> {
+
+        let mut database = self.database.clone();
+
+        let mut limiter = RateLimiter::new(self.database.clone());
+        if let Some(wait) = limiter.get_wait(self.username.as_str())? {
+            return Err(AuthError::LockedOut(wait));
+        }
 
         // Get the hash from the database
-        let result = db.database.get_account(self.username.clone().as_str().clone()).unwrap();
-        let db_password_hash = result.password;
+        let result = match database.get_account(self.username.as_str()) {
+            Ok(result) => result,
+            Err(rusqlite::Error::QueryReturnedNoRows) => return Err(AuthError::UnknownUser),
+            Err(error) => return Err(error.into()),
+        };
 
-        let verified = verify(generated_password, &db_password_hash);
+        // Get password, peppered with the key the hash was made with, wiped once verified
+        let verified = match self.generate_password(password, result.pepper_id.as_deref()) {
+            Some(generated_password) => self.compare_password(&generated_password, result.password.as_str()),
+            None => return Err(AuthError::Storage("pepper key of this account is not available".to_string())),
+        };
 
-        match verified {
+        if !verified {
+            limiter.record_failure(self.username.as_str())?;
+            return Err(AuthError::InvalidCredentials);
+        }
 
-            Ok(true) => {
-
-                // generate salt
-                let salt: [u8; 16] = self.generate_salt();
-
-                // generate new password hash
-                let password_hash: String = self.hash_password(new_password.clone(), salt);
-
-                println!("[Authenticator] -- {} -- {}", self.username, password_hash);
-
-                let update_result = db.database.update_account_password(
-                    self.username.as_str(), password_hash.as_str(), salt);
-
-                match update_result {
-                    Ok(true) => {
-                        return Response {
-                            validity: true,
-                            message: "Success".to_string(),
-                        }
-                    },
-                    Ok(false) => {
-                        return Response {
-                            validity: false,
-                            message: "Error trying to update password".to_string(),
-                        }
-                    },
-                    Err(_) => {
-                        println!("Error trying to update password");
-                    }
-                }
-            },
-            Ok(false) => {
+        self.unlock_vault(password.expose())?;
+        if self.vault.is_none() {
+            return Err(AuthError::Storage("unable to unlock credential vault".to_string()));
+        }
 
-                return Response {
-                    validity: false,
-                    message: "Password does not match, try again".to_string(),
-                }
+        limiter.record_success(self.username.as_str())?;
 
-            },
-            Err(_) => {
-                println!("Invalid credentials");
-            }
+        Ok(())
+    }
+
+    /// Finish a login waiting for a code, codes count towards the rate limit like passwords
+    fn attempt_totp(&mut self, code: &str) -> Result<(), AuthError> {
+        if !self.awaiting_code {
+            return Err(AuthError::InvalidCredentials);
+        }
+
+        let mut limiter = RateLimiter::new(self.database.clone());
+        if let Some(wait) = limiter.get_wait(self.username.as_str())? {
+            return Err(AuthError::LockedOut(wait));
+        }
+
+        let mut database = self.database.clone();
+        let record = database.get_totp(self.id.as_str())?
+            .ok_or_else(|| AuthError::Storage("totp secret not found".to_string()))?;
+
+        let secret = self.vault.as_ref()
+            .and_then(|vault_key| vault_key.open(&record.secret, totp_associated_data(self.id.as_str()).as_bytes()))
+            .ok_or_else(|| AuthError::Storage("unable to read totp secret".to_string()))?;
+
+        // A code is accepted once, even within its validity window
+        let accepted = match Totp::from_secret(secret).verify(code, record.last_step) {
+            Some(step) => database.use_totp_step(self.id.as_str(), step)?,
+            None => false,
+        };
+
+        if !accepted {
+            limiter.record_failure(self.username.as_str())?;
+            return Err(AuthError::InvalidCredentials);
         }
 
-        return Response {
-            validity: false,
-            message: "Error trying to verify password".to_string(),
+        self.awaiting_code = false;
+        limiter.record_success(self.username.as_str())?;
+
+        Ok(())
+    }
+
+    /// Finish a login waiting for a code with a recovery code, which is then used up
+    fn attempt_recovery_code(&mut self, code: &str) -> Result<(), AuthError> {
+        if !self.awaiting_code {
+            return Err(AuthError::InvalidCredentials);
+        }
+
+        let mut limiter = RateLimiter::new(self.database.clone());
+        if let Some(wait) = limiter.get_wait(self.username.as_str())? {
+            return Err(AuthError::LockedOut(wait));
+        }
+
+        let mut database = self.database.clone();
+        let code = recovery::normalize(code);
+
+        // Codes whose pepper key is no longer available cannot match
+        let matched = database.get_recovery_codes(self.id.as_str())?
+            .into_iter()
+            .find(|record| self
+                .generate_password(&code, Some(record.pepper_id.as_str()))
+                .map(|generated_password| self.compare_password(&generated_password, record.password.as_str()))
+                .unwrap_or(false));
+
+        let accepted = match matched {
+            Some(record) => database.use_recovery_code(self.id.as_str(), record.id)?,
+            None => false,
+        };
+
+        if !accepted {
+            limiter.record_failure(self.username.as_str())?;
+            return Err(AuthError::InvalidCredentials);
         }
+
+        self.awaiting_code = false;
+        limiter.record_success(self.username.as_str())?;
+
+        Ok(())
     }
+
+    /// Write the result of an event to the audit log
+    /// - logins and sign ups are tied to the account the username belongs to, if any,
+    ///   other events to the logged in account
+    fn audit(&self, event: AuditEvent, username: &str, result: &Result<(), AuthError>) {
+        let account = match event {
+            AuditEvent::Login | AuditEvent::SignUp => self.database.clone().get_account(username).ok().map(|record| record.id),
+            _ => Some(self.id.clone()),
+        };
+
+        let (outcome, detail) = Outcome::of(result);
+        AuditLog::new(self.database.clone()).record(account.as_deref(), username, event, outcome, detail.as_str());
+
+        let level = match outcome {
+            Outcome::Failure => Level::Warn,
+            _ => Level::Info,
+        };
+        logger::log(level, "authenticator", event.as_str(),
+            &[("username", &username), ("outcome", &outcome.as_str()), ("detail", &detail)]);
+    }
+}
+
+/// Associated data binding a totp secret to its account, distinct from credential entries
+fn totp_associated_data(account: &str) -> String {
+    format!("{}:totp", account)
+}
+
+/// Policy violation with a single reason
+fn policy_violation(reason: &str) -> AuthError {
+    AuthError::PolicyViolation(vec![reason.to_string()])
 }
@@ -0,0 +1,482 @@
+use std::{collections::HashMap, fmt, fs, io, path::{Path, PathBuf}, sync::{Arc, OnceLock, RwLock}};
+
+use regex::{Regex, RegexSet};
+use rustrict::{CensorStr, Type};
+
+use super::canonical::{canonical, leetspeak_variants, skeleton};
+use super::super::utility::{logger, paths};
+
+/// Directory of the override files shared by every user of the machine
+const SYSTEM_DIR: &str = "/etc/termauth";
+
+/// Settings of the username filter, within the same directories as the override files
+const SETTINGS_FILE: &str = "usernames.conf";
+
+static BLOCKLISTS: OnceLock<RwLock<Arc<Blocklists>>> = OnceLock::new();
+
+/// Errors while loading the override files
+/// - Io : an override file exists but could not be read
+/// - Pattern : a line of a pattern file is not a valid regular expression
+/// - Syntax : a line of the settings file is invalid
+/// - Set : the username patterns could not be compiled together
+#[derive(Debug)]
+pub enum BlocklistError {
+    Io(String, io::Error),
+    Pattern { path: String, line: usize, message: String },
+    Syntax { path: String, line: usize, message: String },
+    Set(String),
+}
+
+impl fmt::Display for BlocklistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlocklistError::Io(path, error) => write!(f, "unable to read blocklist {}: {}", path, error),
+            BlocklistError::Pattern { path, line, message } => write!(f, "invalid pattern in {}, line {}: {}", path, line, message),
+            BlocklistError::Syntax { path, line, message } => write!(f, "invalid username settings {}, line {}: {}", path, line, message),
+            BlocklistError::Set(message) => write!(f, "unable to compile the username patterns: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for BlocklistError {}
+
+/// Lists bundled with the application, an override file of the same name adds to each
+/// - WeakPasswords : most common passwords, most common first
+/// - BreachedPasswords : passwords found in data breaches
+/// - UsernamePatterns : regular expressions of offensive usernames
+/// - ReservedNames : usernames kept for the system, such as root or admin
+/// - UsernameAllowlist : words wrongly taken as offensive, such as surnames
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListKind {
+    WeakPasswords,
+    BreachedPasswords,
+    UsernamePatterns,
+    ReservedNames,
+    UsernameAllowlist,
+}
+
+impl ListKind {
+
+    /// Every list, in the order they are reported
+    pub const ALL: [ListKind; 5] = [ListKind::WeakPasswords, ListKind::BreachedPasswords, ListKind::UsernamePatterns,
+        ListKind::ReservedNames, ListKind::UsernameAllowlist];
+
+    /// Name of the bundled list and of its override files
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ListKind::WeakPasswords => "weakpasswords.txt",
+            ListKind::BreachedPasswords => "breachedpasswords.txt",
+            ListKind::UsernamePatterns => "regex.txt",
+            ListKind::ReservedNames => "reserved.txt",
+            ListKind::UsernameAllowlist => "allowlist.txt",
+        }
+    }
+
+    /// Name of the list in plain words
+    pub fn title(&self) -> &'static str {
+        match self {
+            ListKind::WeakPasswords => "Weak passwords",
+            ListKind::BreachedPasswords => "Breached passwords",
+            ListKind::UsernamePatterns => "Username patterns",
+            ListKind::ReservedNames => "Reserved usernames",
+            ListKind::UsernameAllowlist => "Username allowlist",
+        }
+    }
+
+    /// Get the password list of a file name, None for other files
+    pub fn from_password_file(name: &str) -> Option<ListKind> {
+        [ListKind::WeakPasswords, ListKind::BreachedPasswords].into_iter().find(|kind| kind.file_name() == name)
+    }
+
+    /// Copy of the list compiled into the binary
+    fn bundled(&self) -> &'static str {
+        match self {
+            ListKind::WeakPasswords => include_str!("../../../../weakpasswords.txt"),
+            ListKind::BreachedPasswords => include_str!("../../../../breachedpasswords.txt"),
+            ListKind::UsernamePatterns => include_str!("regex.txt"),
+            ListKind::ReservedNames => include_str!("reserved.txt"),
+            ListKind::UsernameAllowlist => include_str!("allowlist.txt"),
+        }
+    }
+}
+
+/// Where entries of a list were read from
+/// - Bundled : compiled into the binary
+/// - System : `/etc/termauth`, for every user of the machine
+/// - User : the configuration directory of the user
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Origin {
+    Bundled,
+    System,
+    User,
+}
+
+impl Origin {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Origin::Bundled => "bundled",
+            Origin::System => "system",
+            Origin::User => "user",
+        }
+    }
+}
+
+/// Lowest level of profanity refused in usernames, see rustrict
+/// - Off : only the username patterns are checked
+/// - Severe : severely profane, offensive, sexual or mean words
+/// - Moderate : the default of rustrict, any profane, offensive or sexual word and severely mean ones
+/// - Mild : any profane, offensive, sexual or mean word, however mild
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strictness {
+    Off,
+    Severe,
+    Moderate,
+    Mild,
+}
+
+impl Strictness {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Strictness::Off => "off",
+            Strictness::Severe => "severe",
+            Strictness::Moderate => "moderate",
+            Strictness::Mild => "mild",
+        }
+    }
+
+    /// Parse a strictness from the settings file
+    pub fn parse(value: &str) -> Option<Strictness> {
+        [Strictness::Off, Strictness::Severe, Strictness::Moderate, Strictness::Mild]
+            .into_iter()
+            .find(|strictness| strictness.as_str() == value)
+    }
+
+    /// Analysis of rustrict a text is refused for
+    fn threshold(&self) -> Type {
+        let offensive = Type::PROFANE | Type::OFFENSIVE | Type::SEXUAL | Type::MEAN;
+        match self {
+            Strictness::Off => Type::NONE,
+            Strictness::Severe => offensive & Type::SEVERE,
+            Strictness::Moderate => Type::INAPPROPRIATE,
+            Strictness::Mild => offensive & Type::MILD_OR_HIGHER,
+        }
+    }
+}
+
+/// A source merged into a list
+/// - origin : where the source comes from
+/// - path : file the source was read from, None for the bundled copy
+/// - entries : non blank lines of the source
+pub struct Source {
+    pub origin: Origin,
+    pub path: Option<PathBuf>,
+    pub entries: usize,
+}
+
+/// A list merged from the bundled copy and its override files
+/// - kind : which list it is
+/// - entries : every entry with its rank, its best position within any source
+/// - sources : sources merged into the list, the bundled copy first
+pub struct Blocklist {
+    pub kind: ListKind,
+    entries: HashMap<String, usize>,
+    sources: Vec<Source>,
+}
+
+impl Blocklist {
+
+    /// Start a list from its bundled copy
+    fn bundled(kind: ListKind) -> Result<Blocklist, BlocklistError> {
+        let mut list = Blocklist { kind, entries: HashMap::new(), sources: Vec::new() };
+        list.merge(Origin::Bundled, None, kind.bundled())?;
+        Ok(list)
+    }
+
+    /// Add the lines of a source, an entry already listed keeps its best rank
+    fn merge(&mut self, origin: Origin, path: Option<PathBuf>, contents: &str) -> Result<(), BlocklistError> {
+        let mut entries = 0;
+
+        for (index, line) in contents.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let rank = entries + 1;
+            entries += 1;
+
+            // Passwords are kept as they are, spaces included, usernames in their canonical form
+            let entry = match self.kind {
+                ListKind::UsernamePatterns => line.trim().to_string(),
+                ListKind::ReservedNames | ListKind::UsernameAllowlist => canonical(line.trim()),
+                _ => line.to_string(),
+            };
+            if let Some(best) = self.entries.get_mut(&entry) {
+                *best = (*best).min(rank);
+                continue;
+            }
+
+            // Patterns are checked one by one so the faulty line can be reported
+            if self.kind == ListKind::UsernamePatterns {
+                Regex::new(entry.as_str()).map_err(|error| BlocklistError::Pattern {
+                    path: path.as_ref().map(|path| path.display().to_string()).unwrap_or_else(|| "bundled list".to_string()),
+                    line: index + 1,
+                    message: error.to_string(),
+                })?;
+            }
+            self.entries.insert(entry, rank);
+        }
+
+        self.sources.push(Source { origin, path, entries });
+        Ok(())
+    }
+
+    /// Entries of the list with their rank, 1 being the first entry of a source
+    pub fn entries(&self) -> &HashMap<String, usize> {
+        &self.entries
+    }
+
+    /// Distinct entries of the list, once merged
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the list has no entry
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Sources merged into the list
+    pub fn sources(&self) -> &[Source] {
+        &self.sources
+    }
+}
+
+/// Password and username blocklists, built once at startup.
+///
+/// Each list starts from the copy compiled into the binary, then the file of the same name in
+/// `/etc/termauth` and in the configuration directory are added to it when they exist. Username
+/// patterns and reserved names are compiled together into a RegexSet as they are loaded, so
+/// validation never reads a file nor compiles a pattern. The username lists and settings can be
+/// reloaded while the application runs, see reload.
+/// - usernames : username patterns followed by the reserved names, anchored
+/// - patterns : username patterns at the start of the set
+/// - strictness : lowest level of profanity refused in usernames
+/// - settings : settings files read, `usernames.conf` of the system then of the user
+pub struct Blocklists {
+    lists: Vec<Blocklist>,
+    usernames: RegexSet,
+    patterns: usize,
+    strictness: Strictness,
+    settings: Vec<PathBuf>,
+}
+
+impl Default for Blocklists {
+    fn default() -> Blocklists {
+        let lists = ListKind::ALL
+            .into_iter()
+            .map(|kind| Blocklist::bundled(kind).expect("bundled username patterns compile"))
+            .collect();
+        Blocklists::compile(lists, Strictness::Moderate, Vec::new()).expect("bundled username patterns compile")
+    }
+}
+
+impl Blocklists {
+
+    /// Load the blocklists once for the whole process, fails if an override file is invalid
+    pub fn init() -> Result<Arc<Blocklists>, BlocklistError> {
+        if let Some(blocklists) = BLOCKLISTS.get() {
+            return Ok(blocklists.read().expect("blocklists lock").clone());
+        }
+
+        let blocklists = Arc::new(Blocklists::load()?);
+        BLOCKLISTS.get_or_init(|| RwLock::new(blocklists.clone()));
+        Ok(blocklists)
+    }
+
+    /// Get the blocklists of the process, loaded on first use
+    pub fn global() -> Arc<Blocklists> {
+        BLOCKLISTS
+            .get_or_init(|| RwLock::new(Arc::new(Blocklists::load().unwrap_or_default())))
+            .read()
+            .expect("blocklists lock")
+            .clone()
+    }
+
+    /// Read the override and settings files again and replace the blocklists of the process,
+    /// the current blocklists are kept if a file is invalid
+    /// - password rules keep the password lists they were built with until a restart
+    pub fn reload() -> Result<Arc<Blocklists>, BlocklistError> {
+        let blocklists = Arc::new(Blocklists::load()?);
+        let lock = BLOCKLISTS.get_or_init(|| RwLock::new(blocklists.clone()));
+        *lock.write().expect("blocklists lock") = blocklists.clone();
+
+        logger::info("blocklists", "blocklists reloaded", &[("strictness", &blocklists.strictness.as_str()),
+            ("usernames", &blocklists.usernames.len())]);
+        Ok(blocklists)
+    }
+
+    /// Load the bundled lists with the overrides of the system and of the configuration directory
+    pub fn load() -> Result<Blocklists, BlocklistError> {
+        Blocklists::load_from(Path::new(SYSTEM_DIR), &paths::config_dir())
+    }
+
+    /// Load the bundled lists with the override and settings files found in the given directories
+    pub fn load_from(system_dir: &Path, user_dir: &Path) -> Result<Blocklists, BlocklistError> {
+        let mut lists = Vec::new();
+
+        for kind in ListKind::ALL {
+            let mut list = Blocklist::bundled(kind)?;
+            for (origin, directory) in [(Origin::System, system_dir), (Origin::User, user_dir)] {
+                let path = directory.join(kind.file_name());
+                match fs::read_to_string(&path) {
+                    Ok(contents) => list.merge(origin, Some(path), contents.as_str())?,
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => {},
+                    Err(error) => return Err(BlocklistError::Io(path.display().to_string(), error)),
+                }
+            }
+            logger::info("blocklists", "list loaded", &[("list", &kind.file_name()), ("entries", &list.len()),
+                ("sources", &list.sources().len())]);
+            lists.push(list);
+        }
+
+        // Settings of the user override the ones of the system
+        let mut strictness = Strictness::Moderate;
+        let mut settings = Vec::new();
+        for directory in [system_dir, user_dir] {
+            let path = directory.join(SETTINGS_FILE);
+            match fs::read_to_string(&path) {
+                Ok(contents) => {
+                    strictness = parse_settings(contents.as_str(), path.display().to_string().as_str(), strictness)?;
+                    settings.push(path);
+                },
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {},
+                Err(error) => return Err(BlocklistError::Io(path.display().to_string(), error)),
+            }
+        }
+
+        Blocklists::compile(lists, strictness, settings)
+    }
+
+    /// Compile the username patterns and the reserved names into a single set, a reserved
+    /// name matches the whole username or a username with the same skeleton
+    fn compile(lists: Vec<Blocklist>, strictness: Strictness, settings: Vec<PathBuf>) -> Result<Blocklists, BlocklistError> {
+        let find = |kind: ListKind| lists.iter().find(|list| list.kind == kind).expect("every list is loaded");
+
+        let mut expressions: Vec<String> = find(ListKind::UsernamePatterns).entries().keys().cloned().collect();
+        let patterns = expressions.len();
+        for name in find(ListKind::ReservedNames).entries().keys() {
+            expressions.push(format!("^{}$", regex::escape(name)));
+            expressions.push(format!("^{}$", regex::escape(skeleton(name).as_str())));
+        }
+
+        let usernames = RegexSet::new(&expressions).map_err(|error| BlocklistError::Set(error.to_string()))?;
+        Ok(Blocklists { lists, usernames, patterns, strictness, settings })
+    }
+
+    /// Get a list
+    pub fn list(&self, kind: ListKind) -> &Blocklist {
+        self.lists.iter().find(|list| list.kind == kind).expect("every list is loaded")
+    }
+
+    /// Every list, in the order of ListKind::ALL
+    pub fn lists(&self) -> &[Blocklist] {
+        &self.lists
+    }
+
+    /// Lowest level of profanity refused in usernames
+    pub fn strictness(&self) -> Strictness {
+        self.strictness
+    }
+
+    /// Settings files the strictness was read from, none for the default
+    pub fn settings(&self) -> &[PathBuf] {
+        &self.settings
+    }
+
+    /// Check if a username is reserved, in any leetspeak reading or look alike
+    /// - `r00t` and `adrnin` are reserved as root and admin are
+    pub fn is_reserved(&self, username: &str) -> bool {
+        leetspeak_variants(username)
+            .into_iter()
+            .flat_map(|variant| [skeleton(variant.as_str()), variant])
+            .any(|candidate| self.usernames.matches(candidate.as_str()).iter().any(|index| index >= self.patterns))
+    }
+
+    /// Check if any leetspeak reading of a username matches a username pattern, once its
+    /// words found in the allowlist are taken out
+    pub fn matches_pattern(&self, username: &str) -> bool {
+        self.screened(username)
+            .iter()
+            .any(|variant| self.usernames.matches(variant.as_str()).iter().any(|index| index < self.patterns))
+    }
+
+    /// Check if any leetspeak reading of a username is profane at the configured strictness,
+    /// once its words found in the allowlist are taken out
+    pub fn is_profane(&self, username: &str) -> bool {
+        let threshold = self.strictness.threshold();
+        threshold != Type::NONE && self.screened(username).iter().any(|variant| variant.as_str().is(threshold))
+    }
+
+    /// Leetspeak readings of a username without its words found in the allowlist. A word is a part
+    /// of the username between underscores and is only taken out when it is exactly an entry, so a
+    /// swear word joined to an allowed surname is still screened, none are left if every word is allowed
+    fn screened(&self, username: &str) -> Vec<String> {
+        let allowed = self.list(ListKind::UsernameAllowlist).entries();
+        let words: Vec<&str> = username
+            .split('_')
+            .filter(|word| !allowed.contains_key(&canonical(word)))
+            .collect();
+
+        match words.iter().all(|word| word.is_empty()) {
+            true => Vec::new(),
+            false => leetspeak_variants(words.join("_").as_str()),
+        }
+    }
+}
+
+/// Parse a settings file of the username filter, hand written like the policy file
+/// - strictness : off, severe, moderate or mild
+fn parse_settings(contents: &str, path: &str, mut strictness: Strictness) -> Result<Strictness, BlocklistError> {
+    for (index, line) in contents.lines().enumerate() {
+        let syntax = |message: String| BlocklistError::Syntax { path: path.to_string(), line: index + 1, message };
+        let line = line.split('#').next().unwrap_or_default().trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let (key, value) = line.split_once('=')
+            .ok_or_else(|| syntax(format!("expected `setting = value`, found `{}`", line)))?;
+        match key.trim() {
+            "strictness" => strictness = Strictness::parse(value.trim())
+                .ok_or_else(|| syntax(format!("strictness expects off, severe, moderate or mild, found `{}`", value.trim())))?,
+            key => return Err(syntax(format!("unknown setting `{}`", key))),
+        }
+    }
+
+    Ok(strictness)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundled() -> Blocklists {
+        let missing = Path::new("/nonexistent/termauth");
+        Blocklists::load_from(missing, missing).unwrap()
+    }
+
+    fn refused(blocklists: &Blocklists, username: &str) -> bool {
+        blocklists.matches_pattern(username) || blocklists.is_profane(username)
+    }
+
+    #[test]
+    fn allowlist_takes_out_whole_words_only() {
+        let blocklists = bundled();
+
+        assert!(!refused(&blocklists, "Hancock"));
+        assert!(!refused(&blocklists, "john_hancock"));
+        assert!(!refused(&blocklists, "hancock_hitchcock"));
+
+        assert!(refused(&blocklists, "shitchcock"));
+        assert!(refused(&blocklists, "hancockshit"));
+        assert!(refused(&blocklists, "shit_hancock"));
+        assert!(refused(&blocklists, "hancock_cock"));
+    }
+}
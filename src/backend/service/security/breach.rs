@@ -0,0 +1,481 @@
+use std::{fmt, fs::{self, File, OpenOptions}, io::{self, BufRead, BufReader, BufWriter, Seek, SeekFrom, Write}, path::{Path, PathBuf}};
+
+use data_encoding::HEXUPPER_PERMISSIVE;
+use md4::Md4;
+use memmap2::Mmap;
+use sha1::{Digest, Sha1};
+
+use super::super::utility::logger;
+
+/// First bytes of an index file, and of a Bloom filter file
+const INDEX_MAGIC: &[u8; 8] = b"TABRIDX1";
+const BLOOM_MAGIC: &[u8; 8] = b"TABLOOM1";
+
+/// Bytes before the prefix table of an index: magic, kind and padding, count of hashes
+const INDEX_HEADER: usize = 24;
+
+/// Bytes before the bits of a Bloom filter: magic, count of bits, count of hashes and padding
+const BLOOM_HEADER: usize = 24;
+
+/// Entries of the prefix table, one per value of the first two bytes of a hash plus the end
+const PREFIXES: usize = 65536 + 1;
+
+/// Bits of the Bloom filter per hash and bits set per hash, about 1% false positives
+const BLOOM_BITS_PER_HASH: u64 = 10;
+const BLOOM_HASHES: u32 = 7;
+
+/// Lines read between two progress reports of an import
+const PROGRESS_LINES: u64 = 10_000_000;
+
+/// Hash of the passwords of a breach corpus, as downloaded from Have I Been Pwned
+/// - Sha1 : SHA-1 of the password, 40 hex characters
+/// - Ntlm : MD4 of the password in UTF-16, 32 hex characters
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HashKind {
+    Sha1,
+    Ntlm,
+}
+
+impl HashKind {
+
+    /// Bytes of a hash
+    pub fn size(&self) -> usize {
+        match self {
+            HashKind::Sha1 => 20,
+            HashKind::Ntlm => 16,
+        }
+    }
+
+    /// Name of the hash
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HashKind::Sha1 => "SHA-1",
+            HashKind::Ntlm => "NTLM",
+        }
+    }
+
+    /// Hash of a password
+    pub fn digest(&self, password: &str) -> Vec<u8> {
+        match self {
+            HashKind::Sha1 => Sha1::digest(password.as_bytes()).to_vec(),
+            HashKind::Ntlm => {
+                let utf16: Vec<u8> = password.encode_utf16().flat_map(u16::to_le_bytes).collect();
+                Md4::digest(utf16).to_vec()
+            },
+        }
+    }
+
+    /// Kind of a hash from its count of hex characters
+    fn from_hex_length(length: usize) -> Option<HashKind> {
+        match length {
+            40 => Some(HashKind::Sha1),
+            32 => Some(HashKind::Ntlm),
+            _ => None,
+        }
+    }
+
+    fn code(&self) -> u8 {
+        match self {
+            HashKind::Sha1 => 1,
+            HashKind::Ntlm => 2,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<HashKind> {
+        match code {
+            1 => Some(HashKind::Sha1),
+            2 => Some(HashKind::Ntlm),
+            _ => None,
+        }
+    }
+}
+
+/// Errors of the breach index
+/// - Io : a file could not be read or written
+/// - Format : a line of the corpus is not a hash, optionally followed by `:count`
+/// - Unsorted : a line of the corpus is not after the line before it
+/// - Empty : the corpus holds no hash
+/// - Invalid : a file is not an index or a Bloom filter, or is corrupted
+#[derive(Debug)]
+pub enum BreachError {
+    Io(String, io::Error),
+    Format { line: u64, message: String },
+    Unsorted(u64),
+    Empty(String),
+    Invalid(String),
+}
+
+impl fmt::Display for BreachError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BreachError::Io(path, error) => write!(f, "unable to access {}: {}", path, error),
+            BreachError::Format { line, message } => write!(f, "invalid hash on line {}: {}", line, message),
+            BreachError::Unsorted(line) => write!(f, "line {} is not sorted by hash, sort the file by hash first", line),
+            BreachError::Empty(path) => write!(f, "{} holds no hash", path),
+            BreachError::Invalid(path) => write!(f, "{} is not a valid breach index", path),
+        }
+    }
+}
+
+impl std::error::Error for BreachError {}
+
+/// Result of an import
+/// - kind : hash of the corpus
+/// - count : hashes written to the index
+/// - bloom : Bloom filter written next to the index, if one was asked for
+pub struct ImportSummary {
+    pub kind: HashKind,
+    pub count: u64,
+    pub bloom: Option<PathBuf>,
+}
+
+/// Index of the hashes of breached passwords, memory mapped and searched in place so
+/// corpora of hundreds of millions of passwords are never read into memory.
+///
+/// The index holds the sorted hashes as raw bytes, after a table of where the hashes
+/// starting with each two byte prefix begin. A lookup is a binary search within a prefix,
+/// after an optional Bloom filter ruling out most passwords that were never breached.
+/// - kind : hash of the indexed passwords
+/// - count : hashes in the index
+/// - index : mapped index file
+/// - bloom : mapped Bloom filter, with its count of bits and hashes
+pub struct BreachIndex {
+    kind: HashKind,
+    count: u64,
+    index: Mmap,
+    bloom: Option<(Mmap, u64, u32)>,
+}
+
+impl BreachIndex {
+
+    /// Open an index, with the Bloom filter next to it if there is one
+    pub fn open(path: &Path) -> Result<BreachIndex, BreachError> {
+        let display = path.display().to_string();
+        let index = map(path)?;
+
+        if index.len() < INDEX_HEADER + PREFIXES * 8 || &index[..8] != INDEX_MAGIC {
+            return Err(BreachError::Invalid(display));
+        }
+        let kind = HashKind::from_code(index[8]).ok_or_else(|| BreachError::Invalid(display.clone()))?;
+        let count = read_u64(&index, 16);
+        if (index.len() - INDEX_HEADER - PREFIXES * 8) as u64 != count * kind.size() as u64 {
+            return Err(BreachError::Invalid(display));
+        }
+
+        // Lookups read the hashes between two entries of the prefix table, which must never
+        // decrease and must end at the count of hashes to stay within the index
+        let mut previous = 0;
+        for prefix in 0..PREFIXES {
+            let start = read_u64(&index, INDEX_HEADER + prefix * 8);
+            if start < previous || (prefix == 0 && start != 0) {
+                return Err(BreachError::Invalid(display));
+            }
+            previous = start;
+        }
+        if previous != count {
+            return Err(BreachError::Invalid(display));
+        }
+
+        // The Bloom filter is only an accelerator, the index is used alone if it is unusable
+        let bloom_path = bloom_path(path);
+        let bloom = match bloom_path.exists() {
+            true => match open_bloom(&bloom_path) {
+                Ok(bloom) => Some(bloom),
+                Err(error) => {
+                    logger::warn("breach", "bloom filter not loaded", &[("error", &error.to_string())]);
+                    None
+                },
+            },
+            false => None,
+        };
+
+        Ok(BreachIndex { kind, count, index, bloom })
+    }
+
+    /// Hash of the indexed passwords
+    pub fn kind(&self) -> HashKind {
+        self.kind
+    }
+
+    /// Hashes in the index
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Check if a password is in the index
+    pub fn contains(&self, password: &str) -> bool {
+        let hash = self.kind.digest(password);
+
+        if let Some((bits, size, hashes)) = &self.bloom {
+            if !bloom_positions(&hash, *size, *hashes).all(|bit| bits[BLOOM_HEADER + (bit / 8) as usize] & (1 << (bit % 8)) != 0) {
+                return false;
+            }
+        }
+
+        let prefix = u16::from_be_bytes([hash[0], hash[1]]) as usize;
+        let mut low = read_u64(&self.index, INDEX_HEADER + prefix * 8) as usize;
+        let mut high = read_u64(&self.index, INDEX_HEADER + (prefix + 1) * 8) as usize;
+
+        let size = self.kind.size();
+        let records = &self.index[INDEX_HEADER + PREFIXES * 8..];
+        while low < high {
+            let middle = low + (high - low) / 2;
+            match records[middle * size..(middle + 1) * size].cmp(hash.as_slice()) {
+                std::cmp::Ordering::Equal => return true,
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+            }
+        }
+
+        false
+    }
+}
+
+/// Build an index from a corpus in the Have I Been Pwned downloadable format, one hash per
+/// line sorted by hash, each optionally followed by `:count`. The corpus is streamed, the
+/// index is written next to its destination then moved in place once complete.
+/// - source : corpus of SHA-1 or NTLM hashes
+/// - destination : index file, replaced if it exists
+/// - bloom : also build a Bloom filter, the destination with a `.bloom` extension
+pub fn import(source: &Path, destination: &Path, bloom: bool) -> Result<ImportSummary, BreachError> {
+    let partial = partial_path(destination);
+    let (kind, count) = match write_index(source, &partial) {
+        Ok(written) => written,
+        Err(error) => {
+            let _ = fs::remove_file(&partial);
+            return Err(error);
+        },
+    };
+
+    fs::rename(&partial, destination).map_err(|error| BreachError::Io(destination.display().to_string(), error))?;
+    logger::info("breach", "index imported", &[("kind", &kind.as_str()), ("hashes", &count)]);
+
+    let bloom = match bloom {
+        true => Some(build_bloom(destination, kind, count)?),
+        false => {
+            // A filter of a previous corpus would hide the passwords of this one
+            let _ = fs::remove_file(bloom_path(destination));
+            None
+        },
+    };
+
+    Ok(ImportSummary { kind, count, bloom })
+}
+
+/// Write the index of a corpus, with the kind and count of its hashes
+fn write_index(source: &Path, partial: &Path) -> Result<(HashKind, u64), BreachError> {
+    let io_error = |path: &Path| { let path = path.display().to_string(); move |error| BreachError::Io(path, error) };
+
+    let reader = BufReader::new(File::open(source).map_err(io_error(source))?);
+    let file = OpenOptions::new().create(true).write(true).truncate(true).open(partial).map_err(io_error(partial))?;
+    let mut writer = BufWriter::new(file);
+
+    // Header and prefix table are written once every hash is counted
+    writer.write_all(&vec![0; INDEX_HEADER + PREFIXES * 8]).map_err(io_error(partial))?;
+
+    let mut kind: Option<HashKind> = None;
+    let mut prefixes = vec![0u64; PREFIXES];
+    let mut previous: Vec<u8> = Vec::new();
+    let mut count: u64 = 0;
+
+    for (number, line) in reader.lines().enumerate() {
+        let number = number as u64 + 1;
+        let line = line.map_err(io_error(source))?;
+        let hex = line.split(':').next().unwrap_or_default().trim();
+        if hex.is_empty() {
+            continue;
+        }
+
+        let line_kind = HashKind::from_hex_length(hex.len()).ok_or_else(|| BreachError::Format { line: number,
+            message: format!("expected 40 (SHA-1) or 32 (NTLM) hex characters, found {}", hex.len()) })?;
+        match kind {
+            Some(kind) if kind != line_kind => return Err(BreachError::Format { line: number,
+                message: format!("{} hash in a {} corpus", line_kind.as_str(), kind.as_str()) }),
+            _ => kind = Some(line_kind),
+        }
+
+        let hash = HEXUPPER_PERMISSIVE.decode(hex.as_bytes())
+            .map_err(|error| BreachError::Format { line: number, message: error.to_string() })?;
+        match hash.as_slice().cmp(previous.as_slice()) {
+            std::cmp::Ordering::Greater => {},
+            std::cmp::Ordering::Equal => continue,
+            std::cmp::Ordering::Less => return Err(BreachError::Unsorted(number)),
+        }
+
+        writer.write_all(&hash).map_err(io_error(partial))?;
+        prefixes[u16::from_be_bytes([hash[0], hash[1]]) as usize + 1] += 1;
+        previous = hash;
+        count += 1;
+
+        if number.is_multiple_of(PROGRESS_LINES) {
+            logger::info("breach", "import progress", &[("lines", &number), ("hashes", &count)]);
+        }
+    }
+
+    let kind = kind.ok_or_else(|| BreachError::Empty(source.display().to_string()))?;
+
+    // Prefix table, where the hashes of each prefix start
+    for prefix in 1..PREFIXES {
+        prefixes[prefix] += prefixes[prefix - 1];
+    }
+    let mut header = Vec::with_capacity(INDEX_HEADER + PREFIXES * 8);
+    header.extend_from_slice(INDEX_MAGIC);
+    header.extend_from_slice(&[kind.code(), 0, 0, 0, 0, 0, 0, 0]);
+    header.extend_from_slice(&count.to_le_bytes());
+    prefixes.iter().for_each(|start| header.extend_from_slice(&start.to_le_bytes()));
+
+    let mut file = writer.into_inner().map_err(|error| BreachError::Io(partial.display().to_string(), error.into_error()))?;
+    file.seek(SeekFrom::Start(0)).map_err(io_error(partial))?;
+    file.write_all(&header).map_err(io_error(partial))?;
+    file.sync_all().map_err(io_error(partial))?;
+
+    Ok((kind, count))
+}
+
+/// Build the Bloom filter of an index from its hashes
+fn build_bloom(index_path: &Path, kind: HashKind, count: u64) -> Result<PathBuf, BreachError> {
+    let path = bloom_path(index_path);
+    let partial = partial_path(&path);
+    let io_error = |error| BreachError::Io(partial.display().to_string(), error);
+
+    let index = map(index_path)?;
+    let size = (count * BLOOM_BITS_PER_HASH).max(64);
+    let mut bits = vec![0u8; size.div_ceil(8) as usize];
+
+    for hash in index[INDEX_HEADER + PREFIXES * 8..].chunks_exact(kind.size()) {
+        for bit in bloom_positions(hash, size, BLOOM_HASHES) {
+            bits[(bit / 8) as usize] |= 1 << (bit % 8);
+        }
+    }
+
+    let mut file = File::create(&partial).map_err(io_error)?;
+    file.write_all(BLOOM_MAGIC).map_err(io_error)?;
+    file.write_all(&size.to_le_bytes()).map_err(io_error)?;
+    file.write_all(&BLOOM_HASHES.to_le_bytes()).map_err(io_error)?;
+    file.write_all(&[0; 4]).map_err(io_error)?;
+    file.write_all(&bits).map_err(io_error)?;
+    file.sync_all().map_err(io_error)?;
+    fs::rename(&partial, &path).map_err(|error| BreachError::Io(path.display().to_string(), error))?;
+
+    Ok(path)
+}
+
+/// Open a Bloom filter, with its count of bits and hashes
+fn open_bloom(path: &Path) -> Result<(Mmap, u64, u32), BreachError> {
+    let bloom = map(path)?;
+    if bloom.len() < BLOOM_HEADER || &bloom[..8] != BLOOM_MAGIC {
+        return Err(BreachError::Invalid(path.display().to_string()));
+    }
+
+    let size = read_u64(&bloom, 8);
+    let hashes = u32::from_le_bytes(bloom[16..20].try_into().expect("4 bytes"));
+    if size == 0 || (bloom.len() - BLOOM_HEADER) as u64 != size.div_ceil(8) {
+        return Err(BreachError::Invalid(path.display().to_string()));
+    }
+
+    Ok((bloom, size, hashes))
+}
+
+/// Bits of the Bloom filter for a hash, by double hashing: the hash is already uniform, so
+/// two of its words are enough to derive every position
+fn bloom_positions(hash: &[u8], size: u64, hashes: u32) -> impl Iterator<Item = u64> {
+    let first = read_u64(hash, 0);
+    let second = read_u64(hash, 8) | 1;
+    (0..hashes as u64).map(move |index| first.wrapping_add(index.wrapping_mul(second)) % size)
+}
+
+/// Map a file in memory, read only
+fn map(path: &Path) -> Result<Mmap, BreachError> {
+    let file = File::open(path).map_err(|error| BreachError::Io(path.display().to_string(), error))?;
+
+    // SAFETY: index files are only ever replaced by renaming a new file over them, never
+    // written in place, so the mapped contents cannot change while they are read
+    unsafe { Mmap::map(&file) }.map_err(|error| BreachError::Io(path.display().to_string(), error))
+}
+
+/// Bloom filter of an index, next to it
+pub fn bloom_path(index_path: &Path) -> PathBuf {
+    index_path.with_extension("bloom")
+}
+
+/// File written until it is complete, then renamed over its destination
+fn partial_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.partial", path.display()))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().expect("8 bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BREACHED: [&str; 4] = ["password", "123456", "hunter2", "letmein"];
+
+    /// Scratch directory of a test, removed when dropped
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Scratch {
+            let path = std::env::temp_dir().join(format!("termauth-breach-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Scratch(path)
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Write a sorted SHA-1 corpus of the breached passwords, then import it
+    fn import_corpus(scratch: &Scratch, bloom: bool) -> PathBuf {
+        let mut hashes: Vec<String> = BREACHED.iter()
+            .map(|password| HEXUPPER_PERMISSIVE.encode(&HashKind::Sha1.digest(password)))
+            .collect();
+        hashes.sort();
+
+        let corpus = scratch.0.join("corpus.txt");
+        let lines: Vec<String> = hashes.iter().map(|hash| format!("{}:42", hash)).collect();
+        fs::write(&corpus, lines.join("\n")).unwrap();
+
+        let index = scratch.0.join("breach.idx");
+        let summary = import(&corpus, &index, bloom).unwrap();
+        assert_eq!(summary.kind, HashKind::Sha1);
+        assert_eq!(summary.count, BREACHED.len() as u64);
+        assert_eq!(summary.bloom.is_some(), bloom);
+        index
+    }
+
+    #[test]
+    fn imported_index_finds_breached_passwords() {
+        for bloom in [false, true] {
+            let scratch = Scratch::new(&format!("lookup-{}", bloom));
+            let index = BreachIndex::open(&import_corpus(&scratch, bloom)).unwrap();
+
+            assert_eq!(index.count(), BREACHED.len() as u64);
+            assert!(BREACHED.iter().all(|password| index.contains(password)));
+            assert!(!index.contains("correct horse battery staple"));
+        }
+    }
+
+    #[test]
+    fn refuses_corrupted_prefix_table() {
+        let scratch = Scratch::new("corrupted");
+        let path = import_corpus(&scratch, false);
+        let original = fs::read(&path).unwrap();
+
+        // A prefix starting after the next one, then a table ending past the hashes
+        for (prefix, start) in [(100, 3u64), (PREFIXES - 1, 5)] {
+            let mut corrupted = original.clone();
+            let offset = INDEX_HEADER + prefix * 8;
+            corrupted[offset..offset + 8].copy_from_slice(&start.to_le_bytes());
+            fs::write(&path, corrupted).unwrap();
+
+            assert!(matches!(BreachIndex::open(&path), Err(BreachError::Invalid(_))));
+        }
+    }
+}
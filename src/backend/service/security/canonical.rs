@@ -0,0 +1,166 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Confusable characters with their prototype, in the format of UTS #39 confusables.txt
+const CONFUSABLES: &str = include_str!("confusables.txt");
+
+/// Most leetspeak readings checked for a username, the first reading of a character is kept beyond it
+const MAX_VARIANTS: usize = 64;
+
+static PROTOTYPES: OnceLock<HashMap<char, String>> = OnceLock::new();
+
+/// Canonical form of a username, unique among accounts: compatibility characters such as
+/// fullwidth or mathematical letters are folded (NFKC), then the case
+/// - `Alice`, `ALICE` and `ａｌｉｃｅ` are the same username
+pub fn canonical(username: &str) -> String {
+    username.nfkc().collect::<String>().to_lowercase()
+}
+
+/// Skeleton of a username, usernames that look alike have the same skeleton.
+///
+/// The skeleton of UTS #39 maps every character to the prototype it is confusable with, such
+/// as `0` to `O` or the Cyrillic `а` to `a`. It is taken from the canonical username, as
+/// usernames are not case sensitive and `I` is confusable with `l` where `i` is not, then again
+/// once lowercased, so `b0b`, `Bob` and `bоb` all have the skeleton `bob`.
+pub fn skeleton(username: &str) -> String {
+    let skeleton = prototype(canonical(username).as_str());
+    prototype(skeleton.to_lowercase().as_str())
+}
+
+/// Readings of a username with leetspeak substitutions undone, such as `sh1t` read as
+/// `shit` and `shlt`, underscores between letters are dropped
+/// - the username itself is the first variant
+pub fn leetspeak_variants(username: &str) -> Vec<String> {
+    let mut variants = vec![String::new()];
+
+    for character in username.to_lowercase().chars() {
+        let readings: &[&str] = match character {
+            '0' => &["o"],
+            '1' => &["i", "l"],
+            '2' => &["z"],
+            '3' => &["e"],
+            '4' | '@' => &["a"],
+            '5' | '$' => &["s"],
+            '6' => &["g", "b"],
+            '7' | '+' => &["t"],
+            '8' => &["b"],
+            '9' => &["g"],
+            '!' => &["i"],
+            '|' => &["l"],
+            '_' | '-' | '.' => &[""],
+            _ => {
+                variants.iter_mut().for_each(|variant| variant.push(character));
+                continue;
+            },
+        };
+
+        // Ambiguous characters branch until there are too many variants
+        let readings = match variants.len() * readings.len() > MAX_VARIANTS {
+            true => &readings[..1],
+            false => readings,
+        };
+        variants = variants
+            .iter()
+            .flat_map(|variant| readings.iter().map(move |reading| format!("{}{}", variant, reading)))
+            .collect();
+    }
+
+    let username = username.to_lowercase();
+    variants.retain(|variant| *variant != username);
+    variants.insert(0, username);
+    variants
+}
+
+/// Map every character to its prototype, decomposed before and after (NFD)
+fn prototype(text: &str) -> String {
+    let prototypes = PROTOTYPES.get_or_init(|| parse_confusables(CONFUSABLES));
+    let mapped: String = text
+        .nfd()
+        .map(|character| match prototypes.get(&character) {
+            Some(prototype) => prototype.clone(),
+            None => character.to_string(),
+        })
+        .collect();
+    mapped.nfd().collect()
+}
+
+/// Parse `source ; prototype ; type` lines of code points in hexadecimal, comments start with #
+fn parse_confusables(contents: &str) -> HashMap<char, String> {
+    let code_points = |field: &str| -> Option<String> {
+        field.split_whitespace()
+            .map(|code| u32::from_str_radix(code, 16).ok().and_then(char::from_u32))
+            .collect()
+    };
+
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .filter_map(|line| {
+            let mut fields = line.split(';');
+            let source = code_points(fields.next()?)?.chars().next()?;
+            let prototype = code_points(fields.next()?)?;
+            Some((source, prototype))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use super::super::blocklists::Blocklists;
+
+    #[test]
+    fn folds_compatibility_characters_and_case() {
+        assert_eq!(canonical("ＡＬＩＣＥ"), "alice");
+        assert_eq!(canonical("Alice"), canonical("ａｌｉｃｅ"));
+        assert_eq!(skeleton("ＡＬＩＣＥ"), skeleton("alice"));
+        assert_eq!(skeleton("ALICE"), "alice");
+    }
+
+    #[test]
+    fn look_alikes_share_a_skeleton() {
+        let cyrillic = "b\u{043e}b";
+
+        assert_ne!(canonical(cyrillic), canonical("bob"));
+        assert_ne!(canonical("b0b"), canonical("bob"));
+        assert_eq!(skeleton(cyrillic), skeleton("bob"));
+        assert_eq!(skeleton("b0b"), skeleton("bob"));
+        assert_eq!(skeleton("BOB"), skeleton("bob"));
+        assert_ne!(skeleton("bib"), skeleton("bob"));
+    }
+
+    #[test]
+    fn look_alikes_of_reserved_names_are_reserved() {
+        assert_eq!(skeleton("adrnin"), skeleton("admin"));
+
+        let missing = Path::new("/nonexistent/termauth");
+        let blocklists = Blocklists::load_from(missing, missing).unwrap();
+        assert!(blocklists.is_reserved("adrnin"));
+        assert!(blocklists.is_reserved("r00t"));
+        assert!(blocklists.is_reserved("Adm1n"));
+        assert!(!blocklists.is_reserved("admiral"));
+    }
+
+    #[test]
+    fn reads_leetspeak() {
+        assert_eq!(leetspeak_variants("Sh1t"), vec!["sh1t", "shit", "shlt"]);
+        assert_eq!(leetspeak_variants("r00t_"), vec!["r00t_", "root"]);
+        assert_eq!(leetspeak_variants("alice"), vec!["alice"]);
+    }
+
+    #[test]
+    fn caps_leetspeak_variants() {
+        let username = "1".repeat(12);
+        let variants = leetspeak_variants(username.as_str());
+
+        // The username, then every reading of the first characters until the cap, the others read as `i`
+        assert_eq!(variants[0], username);
+        assert_eq!(variants.len(), MAX_VARIANTS + 1);
+        assert!(variants.contains(&"iiiiiiiiiiii".to_string()));
+        assert!(variants.contains(&"lllllliiiiii".to_string()));
+        assert!(!variants.contains(&"llllllllllll".to_string()));
+    }
+}
@@ -1,109 +1,201 @@
-use std::{io::{Read, Write}, path::Path, fs::File};
+use std::{collections::HashMap, path::PathBuf, rc::Rc};
 
+use chrono::Utc;
 use rand::Rng;
-use rusqlite::{Connection, ToSql, Statement, Rows, Result, named_params};
+use rusqlite::{Connection, ToSql, Statement, Rows, Result, named_params, OptionalExtension, Transaction, TransactionBehavior};
+
+use super::audit::{self, GENESIS_HASH};
+use super::canonical::{canonical, skeleton};
+use super::secret::SecretString;
+use super::vault::Sealed;
+use super::super::utility::logger;
+use super::migration::{Migration, MigrationError, MIGRATIONS, latest_version};
 
 /// Database Manager with the following methods and behavior
 pub trait DatabaseManager {
 
-    fn new() -> Self;
-    fn build_schema(&mut self, name: &str) -> Result<(), rusqlite::Error>;
+    fn open(path: &DatabasePath) -> Result<Self, rusqlite::Error> where Self: Sized;
+
+}
 
+/// Migration Manager with the following methods and behavior
+pub trait MigrationManager {
+    fn get_schema_version(&self) -> Result<u32, rusqlite::Error>;
+    fn migrate(&mut self, dry_run: bool) -> Result<Vec<&'static Migration>, MigrationError>;
 }
 
 /// Account Manager with the following methods and behavior
 pub trait AccountManager {
-    fn create_account(&mut self, username: &str, password: &str, salt: [u8; 16]) -> Result<bool, rusqlite::Error>;
-    fn update_account_password(&mut self, username: &str, password: &str, salt: [u8; 16]) -> Result<bool, rusqlite::Error>;
+    fn create_account(&mut self, username: &str, password: &str, salt: [u8; 16], pepper_id: &str) -> Result<(), rusqlite::Error>;
+    fn update_account_password(&mut self, account: &str, password: &str, salt: [u8; 16], pepper_id: &str) -> Result<bool, rusqlite::Error>;
     fn get_account(&mut self, username: &str) -> Result<Record, rusqlite::Error>;
+    fn get_username_conflicts(&mut self) -> Result<Vec<(String, String)>, rusqlite::Error>;
+    fn get_confusable_account(&mut self, username: &str) -> Result<Option<String>, rusqlite::Error>;
+    fn change_account_password(&mut self, account: &str, password: &str, salt: [u8; 16], pepper_id: &str, history: usize, vault: &VaultRecord) -> Result<i64, rusqlite::Error>;
+    fn get_password_history(&mut self, account: &str) -> Result<Vec<PasswordHistoryRecord>, rusqlite::Error>;
+}
+
+/// Vault Manager with the following methods and behavior
+pub trait VaultManager {
+    fn create_vault(&mut self, account: &str, salt: &[u8], key: &Sealed) -> Result<(), rusqlite::Error>;
+    fn get_vault(&mut self, account: &str) -> Result<Option<VaultRecord>, rusqlite::Error>;
+}
+
+/// Attempt Manager with the following methods and behavior
+pub trait AttemptManager {
+    fn get_attempts(&mut self, scope: &str) -> Result<Option<AttemptRecord>, rusqlite::Error>;
+    fn save_attempts(&mut self, attempts: &AttemptRecord) -> Result<(), rusqlite::Error>;
+    fn clear_attempts(&mut self, scope: &str) -> Result<(), rusqlite::Error>;
+}
+
+/// Totp Manager with the following methods and behavior
+pub trait TotpManager {
+    fn save_totp(&mut self, account: &str, secret: &Sealed, last_step: i64) -> Result<(), rusqlite::Error>;
+    fn get_totp(&mut self, account: &str) -> Result<Option<TotpRecord>, rusqlite::Error>;
+    fn use_totp_step(&mut self, account: &str, step: i64) -> Result<bool, rusqlite::Error>;
+}
+
+/// Recovery Manager with the following methods and behavior
+pub trait RecoveryManager {
+    fn replace_recovery_codes(&mut self, account: &str, codes: &[SecretString], pepper_id: &str) -> Result<(), rusqlite::Error>;
+    fn get_recovery_codes(&mut self, account: &str) -> Result<Vec<RecoveryRecord>, rusqlite::Error>;
+    fn use_recovery_code(&mut self, account: &str, id: i64) -> Result<bool, rusqlite::Error>;
+}
+
+/// Session Manager with the following methods and behavior
+pub trait SessionManager {
+    fn create_session(&mut self, session: &SessionRecord) -> Result<(), rusqlite::Error>;
+    fn touch_session(&mut self, id: &str, last_seen: i64) -> Result<(), rusqlite::Error>;
+    fn end_session(&mut self, id: &str, ended_at: i64) -> Result<(), rusqlite::Error>;
+}
+
+/// Audit Manager with the following methods and behavior
+pub trait AuditManager {
+    fn append_audit_event(&mut self, record: AuditRecord) -> Result<(), rusqlite::Error>;
+    fn get_audit_events(&mut self, account: &str) -> Result<Vec<AuditRecord>, rusqlite::Error>;
+    fn get_audit_chain(&mut self) -> Result<Vec<AuditRecord>, rusqlite::Error>;
+    fn get_audit_sequence(&mut self) -> Result<i64, rusqlite::Error>;
 }
 
 /// Credential Manager with the following methods and behavior
-trait CredentialManager {
-    fn create_credential(&mut self, username: &str, password: &str, role: &str) -> Result<(), rusqlite::Error>;
-    fn update_credential(&mut self, username: &str, password: &str, role: &str) -> Result<(), rusqlite::Error>;
-    fn delete_credential(&mut self, username: &str) -> Result<(), rusqlite::Error>;
-    fn get_credential(&mut self, username: &str) -> Result<(), rusqlite::Error>;
-    fn get_all_credentials(&mut self, account: &str) -> Result<(), rusqlite::Error>;
+pub trait CredentialManager {
+    fn create_credential(&mut self, account: &str, app: &str, username: &str, password: &Sealed) -> Result<i64, rusqlite::Error>;
+    fn update_credential(&mut self, account: &str, id: i64, app: &str, username: &str, password: &Sealed) -> Result<bool, rusqlite::Error>;
+    fn delete_credential(&mut self, account: &str, id: i64) -> Result<bool, rusqlite::Error>;
+    fn get_all_credentials(&mut self, account: &str) -> Result<Vec<CredentialRecord>, rusqlite::Error>;
+}
+
+/// Location of the database
+/// - File : database file at the given path
+/// - Memory : database kept in memory, discarded on exit
+pub enum DatabasePath {
+    File(PathBuf),
+    Memory,
+}
+
+impl DatabasePath {
+
+    /// Location of the database from a setting, `:memory:` for an in-memory database
+    pub fn from_setting(setting: &str) -> DatabasePath {
+        match setting {
+            ":memory:" => DatabasePath::Memory,
+            path => DatabasePath::File(PathBuf::from(path)),
+        }
+    }
 }
 
-/// Database struct implementing the Connection trait from crate
+/// Database struct implementing the Connection trait from crate.
+///
+/// A single connection is opened at startup and shared by every service, cloning
+/// the database only clones the handle to that connection.
+#[derive(Clone)]
 pub struct Database {
-    connection: Connection,
+    connection: Rc<Connection>,
 }
 
 /// DatabaseManager trait implementation for Database struct
 impl DatabaseManager for Database {
 
-    /// Create a new Database struct
-    fn new() -> Database {
-        let connection = Connection::open("database.db").unwrap();
-        // let connection = Connection::open_in_memory().unwrap();
-        Database { connection }
-    }
-
-    /// Build the database schema
-    fn build_schema(&mut self, name: &str) -> Result<(), rusqlite::Error> {
-
-        const _DATABASE: &str = 
-            "CREATE DATABASE IF NOT EXISTS ?1";
-
-        const TABLE_ACCOUNT: &str = 
-            "CREATE TABLE IF NOT EXISTS account (
-                id              CHAR(36)                PRIMARY KEY,
-                username        VARCHAR(40)             NOT NULL UNIQUE,
-                password        VARCHAR(150)            NOT NULL,
-                salt            BINARY(150)             NOT NULL,
-                logged_in       BOOLEAN                 NOT NULL DEFAULT 0,
-                signed_in       BOOLEAN                 NOT NULL DEFAULT 0,
-                created_at      DATETIME                NOT NULL DEFAULT CURRENT_TIMESTAMP,
-                updated_at      DATETIME                NOT NULL DEFAULT CURRENT_TIMESTAMP
-            )";
-
-        const TABLE_CREDENTIALS: &str = 
-            "CREATE TABLE IF NOT EXISTS password_manager (
-                id              INTEGER PRIMARY KEY AUTOINCREMENT,
-                account         CHAR(36) NOT NULL,
-                username        VARCHAR(36) NOT NULL,
-                password        BINARY(150) NOT NULL,
-                salt            VARCHAR(150) NOT NULL,
-                FOREIGN KEY (account) REFERENCES account(id)
-            )";
-
-        const _VIEW_ACCOUNT: &str = 
-            "CREATE OR REPLACE
-                ALGORITHM = UNDEFINED
-                SQL SECURITY DEFINER
-            VIEW `account_view` AS 
-                SELECT 
-                    `account`.`id` AS `id`,
-                    `account`.`username` AS `username`,
-                    `account`.`password` AS `password`,
-                    `account`.`salt` AS `salt`,
-                    `account`.`logged_in` AS `logged_in`,
-                    `account`.`signed_in` AS `signed_in`,
-                    `account`.`created_at` AS `created_at`,
-                    `account`.`updated_at` AS `updated_at`
-            ";
-
-        const _VIEW_CREDENTIALS: &str = 
-            "CREATE OR REPLACE
-                ALGORITHM = UNDEFINED
-                SQL SECURITY DEFINER
-            VIEW `password_manager_view` AS 
-                SELECT 
-                    `password_manager`.`id` AS `id`,
-                    `password_manager`.`account` AS `account`,
-                    `password_manager`.`username` AS `username`,
-                    `password_manager`.`password` AS `password`,
-                    `password_manager`.`salt` AS `salt`
-            ";
-
-        // self.connection.execute(DATABASE, [name])?;
-        self.connection.execute(TABLE_ACCOUNT, [])?;
-        self.connection.execute(TABLE_CREDENTIALS, [])?;
-        // self.connection.execute(VIEW_ACCOUNT, [])?;
-        // self.connection.execute(VIEW_CREDENTIALS, [])?;
+    /// Open the database at the given location, with foreign keys enforced
+    fn open(path: &DatabasePath) -> Result<Database, rusqlite::Error> {
+        let connection = match path {
+            DatabasePath::File(path) => Connection::open(path)?,
+            DatabasePath::Memory => Connection::open_in_memory()?,
+        };
+
+        connection.pragma_update(None, "foreign_keys", true)?;
+        Ok(Database { connection: Rc::new(connection) })
+    }
+
+}
+
+/// MigrationManager trait implementation for Database struct
+impl MigrationManager for Database {
+
+    /// Get the schema version recorded in the database, 0 for databases created before migrations
+    fn get_schema_version(&self) -> Result<u32> {
+        self.connection.pragma_query_value(None, "user_version", |row| row.get(0))
+    }
+
+    /// Apply every pending migration in order, each within its own transaction,
+    /// returns the migrations applied
+    /// - dry_run : apply the pending migrations within a single transaction then roll it back
+    fn migrate(&mut self, dry_run: bool) -> Result<Vec<&'static Migration>, MigrationError> {
+
+        let version = self.get_schema_version()?;
+        let latest = latest_version();
+        if version > latest {
+            return Err(MigrationError::Newer { found: version, latest });
+        }
+
+        let pending: Vec<&'static Migration> = MIGRATIONS
+            .iter()
+            .filter(|migration| migration.version > version)
+            .collect();
+
+        if pending.is_empty() {
+            return Ok(pending);
+        }
+
+        // Tables are rebuilt while migrating, which foreign key enforcement would refuse
+        self.connection.pragma_update(None, "foreign_keys", false)?;
+        let result = self.apply_migrations(&pending, dry_run);
+        self.connection.pragma_update(None, "foreign_keys", true)?;
+
+        result.map(|_| pending)
+    }
+}
+
+impl Database {
+
+    /// Connection of the database, for tests writing rows the services never would
+    #[cfg(test)]
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Apply migrations, recording the version of each one along with its changes
+    fn apply_migrations(&mut self, migrations: &[&'static Migration], dry_run: bool) -> Result<(), MigrationError> {
+
+        if dry_run {
+            let transaction = self.connection.unchecked_transaction()?;
+            for migration in migrations {
+                migration.apply(&transaction)?;
+            }
+            transaction.rollback()?;
+
+            return Ok(());
+        }
+
+        for migration in migrations {
+            let transaction = self.connection.unchecked_transaction()?;
+            migration.apply(&transaction)?;
+            transaction.pragma_update(None, "user_version", migration.version)?;
+            transaction.commit()?;
+
+            logger::info("database", "migration applied",
+                &[("version", &migration.version), ("description", &migration.description)]);
+        }
 
         Ok(())
     }
@@ -111,11 +203,12 @@ impl DatabaseManager for Database {
 
 #[derive(Debug)]
 pub struct Record {
-    id: String,
+    pub id: String,
     pub username: String,
     pub password: String,
-    salt: String,
-    pub logged_in: bool,
+    pub pepper_id: Option<String>,
+    pub role: String,
+    pub password_changed_at: Option<i64>,
 }
 
 /// AccountManager trait implementation for Database struct
@@ -128,70 +221,53 @@ impl AccountManager for Database {
     /// - salt is also stored in the database 
     /// - secure_password (to be removed)
     /// - claim as a JWT token (to be removed)
-    /// - logged_in and signed_in are booleans (to be removed - probably)
     /// - created_at and updated_at are timestamps
-    fn create_account(&mut self, username: &str, password: &str, salt: [u8; 16]) -> Result<bool, rusqlite::Error> {
-
-        // Convert salt into readable string type
-        // let salt_string = match std::str::from_utf8(&salt) {
-        //     Ok(v) => v,
-        //     Err(e) => panic!("Invalid UTF-8 sequence: {}", e),
-        // };
-
-        // let test = format!("{:?}", salt);
+    /// - pepper_id is the id of the pepper key the password was hashed with
+    /// - canonical and skeleton of the username are stored for lookups, see canonical
+    ///
+    /// A username taken by another account breaks the unique index on canonical, the
+    /// constraint violation is returned as is, see is_constraint_violation
+    fn create_account(&mut self, username: &str, password: &str, salt: [u8; 16], pepper_id: &str) -> Result<(), rusqlite::Error> {
 
-        self.build_schema("database.db")?;
-
-        // let salt_string = format!("{:?}", salt);
-        let salt_string = String::from_utf8_lossy(&salt).to_string();
-
-        // let salt_string = salt
+        let salt_bytes: &[u8] = &salt;
 
         // Generate random UUID for user account
         let random_bytes = rand::thread_rng().gen::<[u8; 16]>();
         let id = uuid::Builder::from_bytes(random_bytes).into_uuid().to_string();
 
-        // Write contents to a file (test)
-        let path = Path::new("users.txt");
-        let display = path.display();
-        let mut file = match File::create(&path) {
-            Err(why) => panic!("couldn't create {}: {}", display, why),
-            Ok(file) => file,
-        };
-
-        let account_credential = format!("Database Service :: \nid: {} \nusername:{}\npassword:{}\nsalt:{}:", id, username, password, salt_string);
-        match file.write_all(account_credential.as_bytes()) {
-            Err(why) => panic!("couldn't write to {}: {}", display, why),
-            Ok(_) => println!("successfully wrote to {}", display),
-        }
+        // Insert query with parameters, the password is the first entry of the password history
+        let now = Utc::now().timestamp();
+        let transaction = self.connection.unchecked_transaction()?;
+        transaction.execute(
+            "INSERT INTO `account` (`id`, `username`, `canonical`, `skeleton`, `password`, `salt`, `pepper_id`, `password_changed_at`)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            (&id, username, canonical(username), skeleton(username), password, salt_bytes, pepper_id, now),
+        )?;
+        transaction.execute(
+            "INSERT INTO `password_history` (`account`, `password`, `pepper_id`, `created_at`) VALUES (?1, ?2, ?3, ?4)",
+            (&id, password, pepper_id, now),
+        )?;
+        transaction.commit()?;
 
-        // Insert query with parameters
-        match self.connection.execute(
-            "INSERT INTO `account` (`id`, `username`, `password`, `salt`) VALUES (?1, ?2, ?3, ?4)",
-            &[&id, username, &password, salt_string.as_str()],
-        ) 
-            {
-                Ok(_) => {
-                    Ok(true)
-                },
-                Err(_) => Ok(false),
-            }
+        logger::info("database", "account created", &[("id", &id), ("username", &username)]);
+        Ok(())
     }
 
-    fn update_account_password(&mut self, username: &str, password: &str, salt: [u8; 16]) -> Result<bool, rusqlite::Error> {
+    /// Replace the password of an account, false when no account has this id
+    fn update_account_password(&mut self, account: &str, password: &str, salt: [u8; 16], pepper_id: &str) -> Result<bool, rusqlite::Error> {
 
-        match self.connection.execute(
-            "UPDATE account SET password = ?1 WHERE username = ?2",
-            &[&password, &username],
-        )
-            {
-                Ok(_) => {
-                    Ok(true)
-                },
-                Err(_) => Ok(false),
-            }
+        let salt_bytes: &[u8] = &salt;
+        let updated = self.connection.execute(
+            "UPDATE account SET password = ?1, salt = ?2, pepper_id = ?3, updated_at = CURRENT_TIMESTAMP WHERE id = ?4",
+            (password, salt_bytes, pepper_id, account),
+        )?;
+
+        Ok(updated > 0)
     }
 
+    /// Get an account by username, whatever its case, the stored username is returned
+    /// - an account differing only by case from an older one when usernames stopped being case
+    ///   sensitive is only found by its exact username, see migration 12
     fn get_account(&mut self, username: &str) -> Result<Record, rusqlite::Error> {
 
         // Return multiple records
@@ -210,64 +286,699 @@ impl AccountManager for Database {
         // let result = stmt.execute(&[&username]);
         // println!("{:?}", result.);
 
-        let mut stmt = self.connection.prepare("SELECT * FROM `account` WHERE `username` = ?1").unwrap();
-        let record = stmt.query_row([username], |row| {
+        let mut stmt = self.connection.prepare(
+            "SELECT `id`, `username`, `password`, `pepper_id`, `role`, `password_changed_at` FROM `account`
+                WHERE `username` = ?1 OR `canonical` = ?2 ORDER BY `username` = ?1 DESC LIMIT 1")?;
+        let record = stmt.query_row((username, canonical(username)), |row| {
             Ok(Record {
                 id: row.get(0)?,
                 username: row.get(1)?,
                 password: row.get(2)?,
-                salt: row.get(3)?,
-                logged_in: row.get(4)?,
+                pepper_id: row.get(3)?,
+                role: row.get(4)?,
+                password_changed_at: row.get(5)?,
             })
         });
         record
 
     }
 
+    /// Get the accounts whose username only differs by case from the username of an older account,
+    /// along with that older username, they have to be renamed
+    fn get_username_conflicts(&mut self) -> Result<Vec<(String, String)>, rusqlite::Error> {
+        let tables: i64 = self.connection.query_row(
+            "SELECT COUNT(*) FROM `sqlite_master` WHERE `type` = 'table' AND `name` = 'account'", [], |row| row.get(0))?;
+        if tables == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut stmt = self.connection.prepare("SELECT `username` FROM `account` ORDER BY `created_at`, `rowid`")?;
+        let usernames = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut seen: HashMap<String, String> = HashMap::new();
+        let mut conflicts = Vec::new();
+        for username in usernames {
+            match seen.get(&canonical(username.as_str())) {
+                Some(existing) => conflicts.push((username, existing.clone())),
+                None => { seen.insert(canonical(username.as_str()), username); },
+            }
+        }
+
+        Ok(conflicts)
+    }
+
+    /// Get the username of an account looking like the given username, such as `b0b` for `Bob`
+    fn get_confusable_account(&mut self, username: &str) -> Result<Option<String>, rusqlite::Error> {
+        self.connection.query_row(
+            "SELECT `username` FROM `account` WHERE `skeleton` = ?1 OR `canonical` = ?2 LIMIT 1",
+            (skeleton(username), canonical(username)),
+            |row| row.get(0),
+        ).optional()
+    }
+
+    /// Change the password of an account, the new password is added to its password history
+    /// and only the last entries are kept. The vault key wrapped under the new password is stored
+    /// in the same transaction, nothing is changed if any of it fails.
+    /// - account : account id
+    /// - history : passwords kept in the history, the new password included
+    /// - vault : vault key wrapped under the new password, with its kdf salt
+    /// - returns the unix time the password was changed
+    fn change_account_password(&mut self, account: &str, password: &str, salt: [u8; 16], pepper_id: &str, history: usize, vault: &VaultRecord) -> Result<i64, rusqlite::Error> {
+
+        let salt_bytes: &[u8] = &salt;
+        let now = Utc::now().timestamp();
+        let transaction = self.connection.unchecked_transaction()?;
+
+        transaction.execute(
+            "UPDATE `account` SET `password` = ?1, `salt` = ?2, `pepper_id` = ?3, `password_changed_at` = ?4,
+                `updated_at` = CURRENT_TIMESTAMP WHERE `id` = ?5",
+            (password, salt_bytes, pepper_id, now, account),
+        )?;
+        transaction.execute(
+            "INSERT INTO `password_history` (`account`, `password`, `pepper_id`, `created_at`) VALUES (?1, ?2, ?3, ?4)",
+            (account, password, pepper_id, now),
+        )?;
+        transaction.execute(
+            "DELETE FROM `password_history` WHERE `account` = ?1 AND `id` NOT IN
+                (SELECT `id` FROM `password_history` WHERE `account` = ?1 ORDER BY `id` DESC LIMIT ?2)",
+            (account, history as i64),
+        )?;
+
+        // Without its vault the account could not read its credentials with the new password
+        let rewrapped = transaction.execute(
+            "UPDATE `vault` SET `salt` = ?1, `wrapped_key` = ?2, `nonce` = ?3 WHERE `account` = ?4",
+            (&vault.salt, &vault.key.ciphertext, &vault.key.nonce, account),
+        )?;
+        if rewrapped != 1 {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+
+        transaction.commit()?;
+        Ok(now)
+    }
+
+    /// Get the password history of an account, latest first
+    fn get_password_history(&mut self, account: &str) -> Result<Vec<PasswordHistoryRecord>, rusqlite::Error> {
+        let mut stmt = self.connection.prepare(
+            "SELECT `password`, `pepper_id` FROM `password_history` WHERE `account` = ?1 ORDER BY `id` DESC")?;
+        let history = stmt.query_map([account], |row| {
+            Ok(PasswordHistoryRecord {
+                password: row.get(0)?,
+                pepper_id: row.get(1)?,
+            })
+        })?;
+
+        history.collect()
+    }
+
+}
+
+/// Previous password of an account
+/// - password : hash of the password
+/// - pepper_id : id of the pepper key the password was hashed with
+pub struct PasswordHistoryRecord {
+    pub password: String,
+    pub pepper_id: Option<String>,
 }
 
+/// Whether a write was refused by a constraint, such as the unique username of an account
+pub fn is_constraint_violation(error: &rusqlite::Error) -> bool {
+    matches!(error, rusqlite::Error::SqliteFailure(failure, _) if failure.code == rusqlite::ErrorCode::ConstraintViolation)
+}
 
-impl CredentialManager for Database {
-    fn create_credential(&mut self, username: &str, password: &str, role: &str) -> Result<()> {
+/// Vault record of an account
+/// - salt : salt for deriving the key from the master password
+/// - key : vault key wrapped under the derived key
+pub struct VaultRecord {
+    pub salt: Vec<u8>,
+    pub key: Sealed,
+}
+
+/// VaultManager trait implementation for Database struct
+impl VaultManager for Database {
+
+    /// Store the wrapped vault key of an account
+    fn create_vault(&mut self, account: &str, salt: &[u8], key: &Sealed) -> Result<()> {
         self.connection.execute(
-            "INSERT INTO credentials (username, password, role) VALUES (?1, ?2, ?3)",
-            &[&username, &password, &role],
+            "INSERT INTO `vault` (`account`, `salt`, `wrapped_key`, `nonce`) VALUES (?1, ?2, ?3, ?4)",
+            (account, salt, &key.ciphertext, &key.nonce),
         )?;
 
         Ok(())
     }
 
-    fn update_credential(&mut self, username: &str, password: &str, role: &str) -> Result<()> {
+    /// Get the wrapped vault key of an account, if the account has one yet
+    fn get_vault(&mut self, account: &str) -> Result<Option<VaultRecord>> {
+
+        let mut stmt = self.connection.prepare(
+            "SELECT `salt`, `wrapped_key`, `nonce` FROM `vault` WHERE `account` = ?1")?;
+        stmt.query_row([account], |row| {
+            Ok(VaultRecord {
+                salt: row.get(0)?,
+                key: Sealed {
+                    ciphertext: row.get(1)?,
+                    nonce: row.get(2)?,
+                },
+            })
+        }).optional()
+    }
+}
+
+/// Failed login attempts against a username, or against every username
+/// - scope : username, or * for every username
+/// - failures : failed attempts counted so far
+/// - last_failure : unix time of the last failed attempt
+/// - locked_until : unix time until which logins are refused
+pub struct AttemptRecord {
+    pub scope: String,
+    pub failures: i64,
+    pub last_failure: i64,
+    pub locked_until: i64,
+}
+
+/// AttemptManager trait implementation for Database struct
+impl AttemptManager for Database {
+
+    /// Get the failed attempts recorded for a scope
+    fn get_attempts(&mut self, scope: &str) -> Result<Option<AttemptRecord>> {
+
+        let mut stmt = self.connection.prepare(
+            "SELECT `scope`, `failures`, `last_failure`, `locked_until` FROM `login_attempts` WHERE `scope` = ?1")?;
+        stmt.query_row([scope], |row| {
+            Ok(AttemptRecord {
+                scope: row.get(0)?,
+                failures: row.get(1)?,
+                last_failure: row.get(2)?,
+                locked_until: row.get(3)?,
+            })
+        }).optional()
+    }
+
+    /// Insert or replace the failed attempts recorded for a scope
+    fn save_attempts(&mut self, attempts: &AttemptRecord) -> Result<()> {
         self.connection.execute(
-            "UPDATE credentials SET password = ?1, role = ?2 WHERE username = ?3",
-            &[&password, &role, &username],
+            "INSERT OR REPLACE INTO `login_attempts` (`scope`, `failures`, `last_failure`, `locked_until`) VALUES (?1, ?2, ?3, ?4)",
+            (&attempts.scope, attempts.failures, attempts.last_failure, attempts.locked_until),
         )?;
 
         Ok(())
     }
 
-    fn delete_credential(&mut self, username: &str) -> Result<()> {
+    /// Forget the failed attempts recorded for a scope
+    fn clear_attempts(&mut self, scope: &str) -> Result<()> {
+        self.connection.execute("DELETE FROM `login_attempts` WHERE `scope` = ?1", [scope])?;
+
+        Ok(())
+    }
+}
+
+/// Totp record of an account
+/// - secret : totp secret sealed with the vault key
+/// - last_step : last time step a code was accepted for
+pub struct TotpRecord {
+    pub secret: Sealed,
+    pub last_step: i64,
+}
+
+/// TotpManager trait implementation for Database struct
+impl TotpManager for Database {
+
+    /// Store the totp secret of an account, replacing any previous secret
+    fn save_totp(&mut self, account: &str, secret: &Sealed, last_step: i64) -> Result<()> {
         self.connection.execute(
-            "DELETE FROM credentials WHERE username = ?1",
-            &[&username],
+            "INSERT OR REPLACE INTO `totp` (`account`, `secret`, `nonce`, `last_step`) VALUES (?1, ?2, ?3, ?4)",
+            (account, &secret.ciphertext, &secret.nonce, last_step),
         )?;
 
         Ok(())
     }
 
-    fn get_credential(&mut self, username: &str) -> Result<()> {
-        // get just the credential from database based on username 
-        let mut stmt = self.connection.prepare("SELECT * FROM `credentials` WHERE `username` = ?1")?;
-        stmt.execute(&[&username])?;
+    /// Get the totp secret of an account, if the account is enrolled
+    fn get_totp(&mut self, account: &str) -> Result<Option<TotpRecord>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT `secret`, `nonce`, `last_step` FROM `totp` WHERE `account` = ?1")?;
+        stmt.query_row([account], |row| {
+            Ok(TotpRecord {
+                secret: Sealed {
+                    ciphertext: row.get(0)?,
+                    nonce: row.get(1)?,
+                },
+                last_step: row.get(2)?,
+            })
+        }).optional()
+    }
+
+    /// Record the step of an accepted code, returns false if a code of this
+    /// or a later step was already accepted
+    fn use_totp_step(&mut self, account: &str, step: i64) -> Result<bool> {
+        let updated = self.connection.execute(
+            "UPDATE `totp` SET `last_step` = ?1 WHERE `account` = ?2 AND `last_step` < ?1",
+            (step, account),
+        )?;
+
+        Ok(updated == 1)
+    }
+}
+
+/// Unused recovery code of an account
+/// - id : code id
+/// - password : hash of the code
+/// - pepper_id : id of the pepper key the code was hashed with
+pub struct RecoveryRecord {
+    pub id: i64,
+    pub password: String,
+    pub pepper_id: String,
+}
+
+/// RecoveryManager trait implementation for Database struct
+impl RecoveryManager for Database {
+
+    /// Replace every recovery code of an account with a new set of hashes
+    fn replace_recovery_codes(&mut self, account: &str, codes: &[SecretString], pepper_id: &str) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+
+        transaction.execute("DELETE FROM `recovery_codes` WHERE `account` = ?1", [account])?;
+        for code in codes {
+            transaction.execute(
+                "INSERT INTO `recovery_codes` (`account`, `password`, `pepper_id`) VALUES (?1, ?2, ?3)",
+                (account, code.expose(), pepper_id),
+            )?;
+        }
+
+        transaction.commit()
+    }
+
+    /// Get the recovery codes of an account that were not used yet
+    fn get_recovery_codes(&mut self, account: &str) -> Result<Vec<RecoveryRecord>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT `id`, `password`, `pepper_id` FROM `recovery_codes` WHERE `account` = ?1 AND `used_at` IS NULL ORDER BY `id`")?;
+        let codes = stmt.query_map([account], |row| {
+            Ok(RecoveryRecord {
+                id: row.get(0)?,
+                password: row.get(1)?,
+                pepper_id: row.get(2)?,
+            })
+        })?;
+
+        codes.collect()
+    }
+
+    /// Mark a recovery code as used, returns false if it was already used
+    fn use_recovery_code(&mut self, account: &str, id: i64) -> Result<bool> {
+        let updated = self.connection.execute(
+            "UPDATE `recovery_codes` SET `used_at` = CURRENT_TIMESTAMP WHERE `id` = ?1 AND `account` = ?2 AND `used_at` IS NULL",
+            (id, account),
+        )?;
+
+        Ok(updated == 1)
+    }
+}
+
+/// Session of an account
+/// - id : session id
+/// - account : id of the account the session belongs to
+/// - tty : terminal the session was started from, if any
+/// - created_at, last_seen and expires_at are unix times
+pub struct SessionRecord {
+    pub id: String,
+    pub account: String,
+    pub tty: Option<String>,
+    pub created_at: i64,
+    pub last_seen: i64,
+    pub expires_at: i64,
+}
+
+/// SessionManager trait implementation for Database struct
+impl SessionManager for Database {
+
+    /// Store a new session
+    fn create_session(&mut self, session: &SessionRecord) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO `sessions` (`id`, `account`, `tty`, `created_at`, `last_seen`, `expires_at`) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (&session.id, &session.account, &session.tty, session.created_at, session.last_seen, session.expires_at),
+        )?;
 
         Ok(())
     }
 
-    fn get_all_credentials(&mut self, account: &str) -> Result<()> {
-        // get all credentials from database
-        let mut stmt = self.connection.prepare("SELECT * FROM `credentials` WHERE `account` = ?1")?;
-        stmt.execute(&[&account])?;
+    /// Record the last time the session was used
+    fn touch_session(&mut self, id: &str, last_seen: i64) -> Result<()> {
+        self.connection.execute(
+            "UPDATE `sessions` SET `last_seen` = ?1 WHERE `id` = ?2 AND `ended_at` IS NULL",
+            (last_seen, id),
+        )?;
 
         Ok(())
     }
+
+    /// End a session, once ended it is kept for reference only
+    fn end_session(&mut self, id: &str, ended_at: i64) -> Result<()> {
+        self.connection.execute(
+            "UPDATE `sessions` SET `ended_at` = ?1, `last_seen` = MAX(`last_seen`, ?1) WHERE `id` = ?2 AND `ended_at` IS NULL",
+            (ended_at, id),
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Event of the audit log
+/// - id : event id, never reused
+/// - account : id of the account the event belongs to, if the account exists
+/// - username : username given for the event
+/// - event and outcome : what happened and how it ended, see audit::AuditEvent and audit::Outcome
+/// - detail : reason of a failure, empty otherwise
+/// - created_at : unix time
+/// - prev_hash and hash : hash of the event before, and of this event chained to it
+/// - key_id : id of the pepper key the hash is keyed with
+#[derive(Clone)]
+pub struct AuditRecord {
+    pub id: i64,
+    pub account: Option<String>,
+    pub username: String,
+    pub event: String,
+    pub outcome: String,
+    pub detail: String,
+    pub created_at: i64,
+    pub prev_hash: String,
+    pub hash: String,
+    pub key_id: String,
+}
+
+/// AuditManager trait implementation for Database struct
+impl AuditManager for Database {
+
+    /// Append an event chained to the latest one, the write lock is taken before the
+    /// latest hash is read so two processes cannot chain to the same event
+    fn append_audit_event(&mut self, mut record: AuditRecord) -> Result<()> {
+        let transaction = Transaction::new_unchecked(&self.connection, TransactionBehavior::Immediate)?;
+
+        record.prev_hash = transaction
+            .query_row("SELECT `hash` FROM `audit_events` ORDER BY `id` DESC LIMIT 1", [], |row| row.get(0))
+            .optional()?
+            .unwrap_or_else(|| GENESIS_HASH.to_string());
+
+        // The id is part of the hash, so it is taken before the event is written
+        record.id = transaction.query_row(
+            "SELECT MAX(COALESCE((SELECT `seq` FROM `sqlite_sequence` WHERE `name` = 'audit_events'), 0),
+                COALESCE((SELECT MAX(`id`) FROM `audit_events`), 0)) + 1", [], |row| row.get(0))?;
+        record.hash = match audit::chain_hash(record.prev_hash.as_str(), &record) {
+            Some(hash) => hash,
+            None => return Err(rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISUSE), Some(format!(
+                "pepper key {} of the audit chain is not available", record.key_id)))),
+        };
+
+        transaction.execute(
+            "INSERT INTO `audit_events` (`id`, `account`, `username`, `event`, `outcome`, `detail`, `created_at`, `prev_hash`, `hash`, `key_id`)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            (record.id, &record.account, &record.username, &record.event, &record.outcome, &record.detail,
+                record.created_at, &record.prev_hash, &record.hash, &record.key_id),
+        )?;
+
+        transaction.commit()
+    }
+
+    /// Get the events of an account, latest first
+    fn get_audit_events(&mut self, account: &str) -> Result<Vec<AuditRecord>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT `id`, `account`, `username`, `event`, `outcome`, `detail`, `created_at`, `prev_hash`, `hash`, `key_id`
+                FROM `audit_events` WHERE `account` = ?1 ORDER BY `id` DESC")?;
+        let events = stmt.query_map([account], audit_record)?;
+
+        events.collect()
+    }
+
+    /// Get every event in the order they were chained
+    fn get_audit_chain(&mut self) -> Result<Vec<AuditRecord>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT `id`, `account`, `username`, `event`, `outcome`, `detail`, `created_at`, `prev_hash`, `hash`, `key_id`
+                FROM `audit_events` ORDER BY `id`")?;
+        let events = stmt.query_map([], audit_record)?;
+
+        events.collect()
+    }
+
+    /// Get the highest event id ever handed out, deleted events included
+    fn get_audit_sequence(&mut self) -> Result<i64> {
+        let sequence: Option<i64> = self.connection
+            .query_row("SELECT `seq` FROM `sqlite_sequence` WHERE `name` = 'audit_events'", [], |row| row.get(0))
+            .optional()?;
+
+        Ok(sequence.unwrap_or(0))
+    }
+}
+
+/// Read an audit event from a row
+fn audit_record(row: &rusqlite::Row) -> Result<AuditRecord> {
+    Ok(AuditRecord {
+        id: row.get(0)?,
+        account: row.get(1)?,
+        username: row.get(2)?,
+        event: row.get(3)?,
+        outcome: row.get(4)?,
+        detail: row.get(5)?,
+        created_at: row.get(6)?,
+        prev_hash: row.get(7)?,
+        hash: row.get(8)?,
+        key_id: row.get(9)?,
+    })
+}
+
+/// Credential record of an account, the password stays encrypted until it is viewed
+/// - id : entry id
+/// - app : app the credential belongs to
+/// - username : username for the app
+/// - secret : encrypted password for the app
+/// - created_at and updated_at are timestamps
+#[derive(Clone)]
+pub struct CredentialRecord {
+    pub id: i64,
+    pub app: String,
+    pub username: String,
+    pub secret: Sealed,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// CredentialManager trait implementation for Database struct
+/// - every query is scoped by the id of the account owning the credential
+/// - the nonce of the sealed password is kept in the salt column
+impl CredentialManager for Database {
+
+    /// Store an encrypted credential for an account, returns the id of the new entry
+    fn create_credential(&mut self, account: &str, app: &str, username: &str, password: &Sealed) -> Result<i64> {
+        self.connection.execute(
+            "INSERT INTO `password_manager` (`account`, `app`, `username`, `password`, `salt`, `created_at`, `updated_at`)
+                VALUES (?1, ?2, ?3, ?4, ?5, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)",
+            (account, app, username, &password.ciphertext, &password.nonce),
+        )?;
+
+        Ok(self.connection.last_insert_rowid())
+    }
+
+    /// Update an existing credential of an account, returns false if no such entry exists
+    fn update_credential(&mut self, account: &str, id: i64, app: &str, username: &str, password: &Sealed) -> Result<bool> {
+        let updated = self.connection.execute(
+            "UPDATE `password_manager` 
+                SET `app` = ?1, `username` = ?2, `password` = ?3, `salt` = ?4, `updated_at` = CURRENT_TIMESTAMP
+                WHERE `id` = ?5 AND `account` = ?6",
+            (app, username, &password.ciphertext, &password.nonce, id, account),
+        )?;
+
+        Ok(updated == 1)
+    }
+
+    /// Delete a credential of an account, returns false if no such entry exists
+    fn delete_credential(&mut self, account: &str, id: i64) -> Result<bool> {
+        let deleted = self.connection.execute(
+            "DELETE FROM `password_manager` WHERE `id` = ?1 AND `account` = ?2",
+            (id, account),
+        )?;
+
+        Ok(deleted == 1)
+    }
+
+    /// Get every credential of an account ordered by app and username
+    fn get_all_credentials(&mut self, account: &str) -> Result<Vec<CredentialRecord>> {
+        let mut stmt = self.connection.prepare(
+            "SELECT `id`, `app`, `username`, `password`, `salt`, `created_at`, `updated_at`
+                FROM `password_manager` WHERE `account` = ?1
+                ORDER BY `app` COLLATE NOCASE, `username` COLLATE NOCASE")?;
+        let records = stmt.query_map([account], credential_record)?;
+        records.collect()
+    }
+}
+
+/// Map a password_manager row onto a credential record
+fn credential_record(row: &rusqlite::Row) -> Result<CredentialRecord> {
+    Ok(CredentialRecord {
+        id: row.get(0)?,
+        app: row.get(1)?,
+        username: row.get(2)?,
+        secret: Sealed {
+            ciphertext: row.get(3)?,
+            nonce: row.get(4)?,
+        },
+        created_at: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+        updated_at: row.get::<_, Option<String>>(6)?.unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Schema written by builds from before migrations existed, left at version 0
+    const UNVERSIONED_SCHEMA: &str =
+        "CREATE TABLE account (
+            id              CHAR(36)                PRIMARY KEY,
+            username        VARCHAR(40)             NOT NULL UNIQUE,
+            password        VARCHAR(150)            NOT NULL,
+            salt            BINARY(150)             NOT NULL,
+            logged_in       BOOLEAN                 NOT NULL DEFAULT 0,
+            signed_in       BOOLEAN                 NOT NULL DEFAULT 0,
+            created_at      DATETIME                NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at      DATETIME                NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE password_manager (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            account         CHAR(36) NOT NULL,
+            username        VARCHAR(36) NOT NULL,
+            password        BINARY(150) NOT NULL,
+            salt            VARCHAR(150) NOT NULL,
+            FOREIGN KEY (account) REFERENCES account(id)
+        );";
+
+    fn memory() -> Database {
+        Database::open(&DatabasePath::Memory).unwrap()
+    }
+
+    fn count(database: &Database, table: &str) -> i64 {
+        database.connection
+            .query_row(&format!("SELECT COUNT(*) FROM `{}`", table), [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn migrates_fresh_database() {
+        let mut database = memory();
+        assert_eq!(database.get_schema_version().unwrap(), 0);
+
+        let applied = database.migrate(false).unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+        assert_eq!(database.get_schema_version().unwrap(), latest_version());
+        assert!(database.migrate(false).unwrap().is_empty());
+
+        database.create_account("Alice", "hash", [7; 16], "pepper").unwrap();
+        let record = database.get_account("ALICE").unwrap();
+        assert_eq!(record.username, "Alice");
+        assert_eq!(record.role, "user");
+        assert_eq!(database.get_password_history(&record.id).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn dry_run_leaves_database_untouched() {
+        let mut database = memory();
+
+        let applied = database.migrate(true).unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+        assert_eq!(database.get_schema_version().unwrap(), 0);
+        assert_eq!(count(&database, "sqlite_master"), 0);
+    }
+
+    #[test]
+    fn upgrades_unversioned_database() {
+        let mut database = memory();
+        database.connection.execute_batch(UNVERSIONED_SCHEMA).unwrap();
+        database.connection.execute(
+            "INSERT INTO account (id, username, password, salt, updated_at) VALUES ('a1', 'Bob', 'hash', 'text salt', '2020-01-01 00:00:00')",
+            []).unwrap();
+        database.connection.execute(
+            "INSERT INTO password_manager (account, username, password, salt) VALUES ('a1', 'bob', x'00', x'01')",
+            []).unwrap();
+
+        database.migrate(false).unwrap();
+        assert_eq!(database.get_schema_version().unwrap(), latest_version());
+
+        let record = database.get_account("bob").unwrap();
+        assert_eq!(record.id, "a1");
+        assert_eq!(record.pepper_id, None);
+        assert_eq!(record.password_changed_at, Some(1577836800));
+
+        let salt: Vec<u8> = database.connection
+            .query_row("SELECT salt FROM account WHERE id = 'a1'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(salt, b"text salt");
+
+        let history = database.get_password_history("a1").unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].password, "hash");
+
+        let credentials = database.get_all_credentials("a1").unwrap();
+        assert_eq!(credentials.len(), 1);
+        assert_eq!(credentials[0].app, "");
+    }
+
+    #[test]
+    fn upgrades_accounts_differing_by_case() {
+        let mut database = memory();
+        database.connection.execute_batch(UNVERSIONED_SCHEMA).unwrap();
+        database.connection.execute_batch(
+            "INSERT INTO account (id, username, password, salt, created_at) VALUES ('a1', 'Bob', 'first', 'salt', '2020-01-01 00:00:00');
+            INSERT INTO account (id, username, password, salt, created_at) VALUES ('a2', 'BOB', 'second', 'salt', '2020-01-02 00:00:00');
+            INSERT INTO account (id, username, password, salt, created_at) VALUES ('a3', 'bob', 'third', 'salt', '2020-01-03 00:00:00');").unwrap();
+
+        assert_eq!(database.get_username_conflicts().unwrap(), vec![
+            ("BOB".to_string(), "Bob".to_string()),
+            ("bob".to_string(), "Bob".to_string()),
+        ]);
+
+        database.migrate(false).unwrap();
+        assert_eq!(database.get_schema_version().unwrap(), latest_version());
+
+        assert_eq!(database.get_account("Bob").unwrap().id, "a1");
+        assert_eq!(database.get_account("bOB").unwrap().id, "a1");
+        assert_eq!(database.get_account("BOB").unwrap().id, "a2");
+        assert_eq!(database.get_account("bob").unwrap().id, "a3");
+        assert_eq!(database.get_username_conflicts().unwrap().len(), 2);
+        assert!(database.create_account("boB", "hash", [7; 16], "pepper").is_err());
+
+        assert!(database.update_account_password("a2", "rehashed", [7; 16], "pepper").unwrap());
+        assert_eq!(database.get_account("Bob").unwrap().password, "first");
+        assert_eq!(database.get_account("BOB").unwrap().password, "rehashed");
+        assert!(!database.update_account_password("a4", "rehashed", [7; 16], "pepper").unwrap());
+    }
+
+    #[test]
+    fn changes_password_with_vault_or_not_at_all() {
+        let mut database = memory();
+        database.migrate(false).unwrap();
+        database.create_account("alice", "first", [7; 16], "pepper").unwrap();
+        let account = database.get_account("alice").unwrap().id;
+        let wrapped = VaultRecord { salt: vec![9; 16], key: Sealed { nonce: vec![1; 24], ciphertext: vec![2; 48] } };
+
+        // No vault to re-key, the password and its history are left as they were
+        assert!(database.change_account_password(account.as_str(), "second", [8; 16], "pepper", 5, &wrapped).is_err());
+        assert_eq!(database.get_account("alice").unwrap().password, "first");
+        assert_eq!(database.get_password_history(account.as_str()).unwrap().len(), 1);
+
+        database.create_vault(account.as_str(), &[3; 16], &Sealed { nonce: vec![4; 24], ciphertext: vec![5; 48] }).unwrap();
+        database.change_account_password(account.as_str(), "second", [8; 16], "pepper", 5, &wrapped).unwrap();
+        assert_eq!(database.get_account("alice").unwrap().password, "second");
+        assert_eq!(database.get_password_history(account.as_str()).unwrap().len(), 2);
+
+        let vault = database.get_vault(account.as_str()).unwrap().unwrap();
+        assert_eq!((vault.salt, vault.key.nonce, vault.key.ciphertext), (wrapped.salt, wrapped.key.nonce, wrapped.key.ciphertext));
+    }
+
+    #[test]
+    fn refuses_newer_database() {
+        let mut database = memory();
+        database.connection.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+
+        assert!(matches!(database.migrate(false), Err(MigrationError::Newer { .. })));
+    }
 }
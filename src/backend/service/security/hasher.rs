@@ -0,0 +1,204 @@
+use argon2::{
+    Argon2, Params as Argon2Params, Version,
+    password_hash::{PasswordHash, PasswordHasher as PhcHasher, PasswordVerifier, SaltString},
+};
+use scrypt::{Scrypt, Params as ScryptParams};
+
+/// Argon2id parameters for password hashing
+/// - memory : 19 MiB
+/// - iterations : 2
+/// - parallelism : 1
+const ARGON2_MEMORY_COST: u32 = 19456;
+const ARGON2_TIME_COST: u32 = 2;
+const ARGON2_PARALLELISM: u32 = 1;
+
+/// Scrypt parameters for password hashing
+/// - log_n : cpu / memory cost as a power of two
+/// - r : block size
+/// - p : parallelism
+const SCRYPT_LOG_N: u8 = 17;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+/// Bcrypt cost for password hashing
+const BCRYPT_COST: u32 = 12;
+
+/// Password hashing algorithms
+/// - Argon2id : memory hard, the default for new hashes
+/// - Scrypt : memory hard
+/// - Bcrypt : legacy, hashes created before argon2id became the default
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Algorithm {
+    Argon2id,
+    Scrypt,
+    Bcrypt,
+}
+
+/// Password hasher producing self-describing hash strings.
+///
+/// Argon2id and scrypt hashes are PHC strings (`$argon2id$v=19$m=..,t=..,p=..$salt$hash`),
+/// bcrypt hashes use the modular crypt format (`$2b$12$...`). Both carry the algorithm,
+/// cost and salt, so any stored hash can be verified and checked against the
+/// algorithm and cost currently in use.
+pub struct Hasher {
+    algorithm: Algorithm,
+}
+
+impl Default for Hasher {
+    fn default() -> Hasher {
+        Hasher::new(Algorithm::Argon2id)
+    }
+}
+
+impl Hasher {
+
+    /// Create a hasher producing hashes with the given algorithm
+    pub fn new(algorithm: Algorithm) -> Hasher {
+        Hasher { algorithm }
+    }
+
+    /// Hash a password with the given salt
+    pub fn hash(&self, password: &[u8], salt: &[u8; 16]) -> String {
+        match self.algorithm {
+            Algorithm::Argon2id => {
+                let salt = SaltString::encode_b64(salt).expect("valid salt length");
+                argon2().hash_password(password, &salt)
+                    .expect("argon2id hashing")
+                    .to_string()
+            }
+            Algorithm::Scrypt => {
+                let salt = SaltString::encode_b64(salt).expect("valid salt length");
+                Scrypt.hash_password_customized(password, None, None, scrypt_params(), &salt)
+                    .expect("scrypt hashing")
+                    .to_string()
+            }
+            Algorithm::Bcrypt => {
+                bcrypt::hash_with_salt(password, BCRYPT_COST, *salt)
+                    .expect("bcrypt hashing")
+                    .to_string()
+            }
+        }
+    }
+
+    /// Verify a password against a stored hash of any supported algorithm
+    pub fn verify(&self, password: &[u8], hash: &str) -> bool {
+        match identify(hash) {
+            Some(Algorithm::Argon2id) => PasswordHash::new(hash)
+                .map(|parsed| argon2().verify_password(password, &parsed).is_ok())
+                .unwrap_or(false),
+            Some(Algorithm::Scrypt) => PasswordHash::new(hash)
+                .map(|parsed| Scrypt.verify_password(password, &parsed).is_ok())
+                .unwrap_or(false),
+            Some(Algorithm::Bcrypt) => bcrypt::verify(password, hash).unwrap_or(false),
+            None => false,
+        }
+    }
+
+    /// Check if a stored hash was made with another algorithm or cost than the
+    /// ones currently in use, and should be replaced at the next login
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        if identify(hash) != Some(self.algorithm) {
+            return true;
+        }
+
+        match self.algorithm {
+            Algorithm::Argon2id => PasswordHash::new(hash)
+                .map(|parsed| {
+                    parsed.version != Some(Version::V0x13.into())
+                        || parsed.params.get_decimal("m") != Some(ARGON2_MEMORY_COST)
+                        || parsed.params.get_decimal("t") != Some(ARGON2_TIME_COST)
+                        || parsed.params.get_decimal("p") != Some(ARGON2_PARALLELISM)
+                })
+                .unwrap_or(true),
+            Algorithm::Scrypt => PasswordHash::new(hash)
+                .map(|parsed| {
+                    parsed.params.get_decimal("ln") != Some(SCRYPT_LOG_N as u32)
+                        || parsed.params.get_decimal("r") != Some(SCRYPT_R)
+                        || parsed.params.get_decimal("p") != Some(SCRYPT_P)
+                })
+                .unwrap_or(true),
+            Algorithm::Bcrypt => hash
+                .split('$')
+                .nth(2)
+                .and_then(|cost| cost.parse::<u32>().ok())
+                != Some(BCRYPT_COST),
+        }
+    }
+}
+
+/// Identify the algorithm of a stored hash from its prefix
+pub fn identify(hash: &str) -> Option<Algorithm> {
+    if hash.starts_with("$argon2id$") {
+        Some(Algorithm::Argon2id)
+    } else if hash.starts_with("$scrypt$") {
+        Some(Algorithm::Scrypt)
+    } else if ["$2a$", "$2b$", "$2x$", "$2y$"].iter().any(|prefix| hash.starts_with(prefix)) {
+        Some(Algorithm::Bcrypt)
+    } else {
+        None
+    }
+}
+
+/// Argon2id hasher with the current parameters
+fn argon2() -> Argon2<'static> {
+    let params = Argon2Params::new(ARGON2_MEMORY_COST, ARGON2_TIME_COST, ARGON2_PARALLELISM, None)
+        .expect("valid argon2 parameters");
+    Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params)
+}
+
+/// Scrypt parameters currently in use
+fn scrypt_params() -> ScryptParams {
+    ScryptParams::new(SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P, ScryptParams::RECOMMENDED_LEN)
+        .expect("valid scrypt parameters")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SALT: [u8; 16] = [42; 16];
+
+    #[test]
+    fn argon2id_verifies_and_is_current() {
+        let hasher = Hasher::default();
+        let hash = hasher.hash(b"correct horse", &SALT);
+
+        assert_eq!(identify(&hash), Some(Algorithm::Argon2id));
+        assert!(hasher.verify(b"correct horse", &hash));
+        assert!(!hasher.verify(b"wrong horse", &hash));
+        assert!(!hasher.needs_rehash(&hash));
+
+        let weaker = hash.replace(&format!("m={}", ARGON2_MEMORY_COST), "m=4096");
+        assert!(hasher.needs_rehash(&weaker));
+        assert!(Hasher::new(Algorithm::Scrypt).needs_rehash(&hash));
+    }
+
+    #[test]
+    fn legacy_hashes_verify_and_need_rehash() {
+        let bcrypt = bcrypt::hash_with_salt(b"correct horse", 4, SALT).unwrap().to_string();
+        let salt = SaltString::encode_b64(&SALT).unwrap();
+        let scrypt = Scrypt
+            .hash_password_customized(b"correct horse", None, None, ScryptParams::new(4, 8, 1, 32).unwrap(), &salt)
+            .unwrap()
+            .to_string();
+
+        let hasher = Hasher::default();
+        for hash in [&bcrypt, &scrypt] {
+            assert!(hasher.verify(b"correct horse", hash));
+            assert!(!hasher.verify(b"wrong horse", hash));
+            assert!(hasher.needs_rehash(hash));
+        }
+
+        assert!(Hasher::new(Algorithm::Bcrypt).needs_rehash(&bcrypt));
+        assert!(Hasher::new(Algorithm::Scrypt).needs_rehash(&scrypt));
+    }
+
+    #[test]
+    fn unknown_hashes_never_verify() {
+        let hasher = Hasher::default();
+
+        assert_eq!(identify("plaintext"), None);
+        assert!(!hasher.verify(b"plaintext", "plaintext"));
+        assert!(hasher.needs_rehash("plaintext"));
+    }
+}
@@ -0,0 +1,357 @@
+use std::{collections::HashMap, fmt};
+
+use rusqlite::Connection;
+
+use super::canonical::{canonical, skeleton};
+use super::super::utility::logger;
+
+/// A schema migration
+/// - version : schema version (`PRAGMA user_version`) once the migration is applied
+/// - description : what the migration changes, shown by `--migrate-only`
+/// - up : statements applied within the transaction of the migration
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    up: fn(&Connection) -> Result<(), rusqlite::Error>,
+}
+
+impl Migration {
+
+    /// Apply the migration on a connection, the caller owns the transaction
+    pub fn apply(&self, connection: &Connection) -> Result<(), rusqlite::Error> {
+        (self.up)(connection)
+    }
+}
+
+/// Every migration in the order they are applied.
+///
+/// Databases created before migrations existed are at version 0 and may already
+/// hold some of these tables and columns, so the first migrations only create what
+/// is missing. Never edit a migration once released, add a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration { version: 1, description: "create account and password_manager tables", up: create_accounts },
+    Migration { version: 2, description: "add app and timestamps to password_manager, create vault table", up: create_vault },
+    Migration { version: 3, description: "create login_attempts table", up: create_login_attempts },
+    Migration { version: 4, description: "add pepper_id to account", up: add_pepper_id },
+    Migration { version: 5, description: "store account salts as BLOB", up: blob_salt },
+    Migration { version: 6, description: "index credentials by account and app", up: index_credentials },
+    Migration { version: 7, description: "create totp table", up: create_totp },
+    Migration { version: 8, description: "create recovery_codes table", up: create_recovery_codes },
+    Migration { version: 9, description: "create sessions table, drop logged_in and signed_in from account", up: create_sessions },
+    Migration { version: 10, description: "create audit_events table", up: create_audit_events },
+    Migration { version: 11, description: "add role to account", up: add_role },
+    Migration { version: 12, description: "add canonical username and skeleton to account", up: add_canonical_username },
+    Migration { version: 13, description: "create password_history table, add password_changed_at to account", up: create_password_history },
+];
+
+/// Latest schema version known to this build
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|migration| migration.version).unwrap_or(0)
+}
+
+/// Errors while migrating the database
+/// - Sqlite : a migration failed, its transaction was rolled back
+/// - Newer : the database was migrated by a newer build
+#[derive(Debug)]
+pub enum MigrationError {
+    Sqlite(rusqlite::Error),
+    Newer { found: u32, latest: u32 },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MigrationError::Sqlite(error) => write!(f, "database migration failed: {}", error),
+            MigrationError::Newer { found, latest } => write!(f,
+                "database schema version {} is newer than the latest known version {}, upgrade termauth-rs", found, latest),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(error: rusqlite::Error) -> MigrationError {
+        MigrationError::Sqlite(error)
+    }
+}
+
+/// Add a column to a table created before the column existed
+fn add_column_if_missing(connection: &Connection, table: &str, column: &str, definition: &str) -> Result<(), rusqlite::Error> {
+    let mut stmt = connection.prepare(&format!("PRAGMA table_info(`{}`)", table))?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .any(|name| name.map(|name| name == column).unwrap_or(false));
+
+    if !exists {
+        connection.execute(&format!("ALTER TABLE `{}` ADD COLUMN `{}` {}", table, column, definition), [])?;
+    }
+
+    Ok(())
+}
+
+/// Version 1 - the original account and credential tables
+fn create_accounts(connection: &Connection) -> Result<(), rusqlite::Error> {
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS account (
+            id              CHAR(36)                PRIMARY KEY,
+            username        VARCHAR(40)             NOT NULL UNIQUE,
+            password        VARCHAR(150)            NOT NULL,
+            salt            BINARY(150)             NOT NULL,
+            logged_in       BOOLEAN                 NOT NULL DEFAULT 0,
+            signed_in       BOOLEAN                 NOT NULL DEFAULT 0,
+            created_at      DATETIME                NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at      DATETIME                NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
+        CREATE TABLE IF NOT EXISTS password_manager (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            account         CHAR(36) NOT NULL,
+            username        VARCHAR(36) NOT NULL,
+            password        BINARY(150) NOT NULL,
+            salt            VARCHAR(150) NOT NULL,
+            FOREIGN KEY (account) REFERENCES account(id)
+        );")
+}
+
+/// Version 2 - encrypted credential vault
+/// - password_manager.password : encrypted entry password
+/// - password_manager.salt : nonce the entry password was encrypted with
+/// - vault.salt : salt for deriving the key from the master password
+/// - vault.wrapped_key : vault key encrypted under the derived key
+/// - vault.nonce : nonce the vault key was encrypted with
+fn create_vault(connection: &Connection) -> Result<(), rusqlite::Error> {
+    add_column_if_missing(connection, "password_manager", "app", "VARCHAR(80) NOT NULL DEFAULT ''")?;
+    add_column_if_missing(connection, "password_manager", "created_at", "DATETIME")?;
+    add_column_if_missing(connection, "password_manager", "updated_at", "DATETIME")?;
+
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS vault (
+            account         CHAR(36)                PRIMARY KEY,
+            salt            BLOB                    NOT NULL,
+            wrapped_key     BLOB                    NOT NULL,
+            nonce           BLOB                    NOT NULL,
+            FOREIGN KEY (account) REFERENCES account(id)
+        );")
+}
+
+/// Version 3 - persistent login rate limiting
+/// - scope : username the attempts were made against, or * for every username
+/// - failures : failed attempts since the last successful login
+/// - last_failure : unix time of the last failed attempt
+/// - locked_until : unix time until which logins are refused
+fn create_login_attempts(connection: &Connection) -> Result<(), rusqlite::Error> {
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS login_attempts (
+            scope           VARCHAR(40)             PRIMARY KEY,
+            failures        INTEGER                 NOT NULL DEFAULT 0,
+            last_failure    INTEGER                 NOT NULL DEFAULT 0,
+            locked_until    INTEGER                 NOT NULL DEFAULT 0
+        );")
+}
+
+/// Version 4 - id of the pepper key a password was hashed with, NULL for legacy hashes
+fn add_pepper_id(connection: &Connection) -> Result<(), rusqlite::Error> {
+    add_column_if_missing(connection, "account", "pepper_id", "VARCHAR(40)")
+}
+
+/// Version 5 - salts used to be stored as lossy text, rebuild the account table
+/// with a BLOB salt column and convert the stored salts
+fn blob_salt(connection: &Connection) -> Result<(), rusqlite::Error> {
+    connection.execute_batch(
+        "CREATE TABLE account_new (
+            id              CHAR(36)                PRIMARY KEY,
+            username        VARCHAR(40)             NOT NULL UNIQUE,
+            password        VARCHAR(150)            NOT NULL,
+            salt            BLOB                    NOT NULL,
+            logged_in       BOOLEAN                 NOT NULL DEFAULT 0,
+            signed_in       BOOLEAN                 NOT NULL DEFAULT 0,
+            created_at      DATETIME                NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at      DATETIME                NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            pepper_id       VARCHAR(40)
+        );
+
+        INSERT INTO account_new (id, username, password, salt, logged_in, signed_in, created_at, updated_at, pepper_id)
+            SELECT id, username, password, CAST(salt AS BLOB), logged_in, signed_in, created_at, updated_at, pepper_id
+            FROM account;
+
+        DROP TABLE account;
+        ALTER TABLE account_new RENAME TO account;")
+}
+
+/// Version 6 - credentials are always looked up by account and listed by app
+fn index_credentials(connection: &Connection) -> Result<(), rusqlite::Error> {
+    connection.execute_batch(
+        "CREATE INDEX IF NOT EXISTS password_manager_account_app
+            ON password_manager (account, app COLLATE NOCASE, username COLLATE NOCASE);")
+}
+
+/// Version 7 - second factor secrets
+/// - secret : totp secret encrypted with the vault key of the account
+/// - nonce : nonce the secret was encrypted with
+/// - last_step : last time step a code was accepted for, older codes are refused
+fn create_totp(connection: &Connection) -> Result<(), rusqlite::Error> {
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS totp (
+            account         CHAR(36)                PRIMARY KEY,
+            secret          BLOB                    NOT NULL,
+            nonce           BLOB                    NOT NULL,
+            last_step       INTEGER                 NOT NULL DEFAULT 0,
+            created_at      DATETIME                NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (account) REFERENCES account(id)
+        );")
+}
+
+/// Version 8 - single use recovery codes of accounts with a second factor
+/// - password : hash of the code, made like account passwords
+/// - pepper_id : id of the pepper key the code was hashed with
+/// - used_at : when the code was used, NULL while it can still be used
+fn create_recovery_codes(connection: &Connection) -> Result<(), rusqlite::Error> {
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS recovery_codes (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            account         CHAR(36)                NOT NULL,
+            password        VARCHAR(150)            NOT NULL,
+            pepper_id       VARCHAR(40)             NOT NULL,
+            used_at         DATETIME,
+            created_at      DATETIME                NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (account) REFERENCES account(id)
+        );
+
+        CREATE INDEX IF NOT EXISTS recovery_codes_account ON recovery_codes (account);")
+}
+
+/// Version 9 - sessions replace the unused logged_in and signed_in flags of accounts
+/// - tty : terminal the session was started from, if any
+/// - created_at, last_seen, expires_at and ended_at are unix times
+fn create_sessions(connection: &Connection) -> Result<(), rusqlite::Error> {
+    connection.execute_batch(
+        "CREATE TABLE account_new (
+            id              CHAR(36)                PRIMARY KEY,
+            username        VARCHAR(40)             NOT NULL UNIQUE,
+            password        VARCHAR(150)            NOT NULL,
+            salt            BLOB                    NOT NULL,
+            created_at      DATETIME                NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at      DATETIME                NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            pepper_id       VARCHAR(40)
+        );
+
+        INSERT INTO account_new (id, username, password, salt, created_at, updated_at, pepper_id)
+            SELECT id, username, password, salt, created_at, updated_at, pepper_id
+            FROM account;
+
+        DROP TABLE account;
+        ALTER TABLE account_new RENAME TO account;
+
+        CREATE TABLE IF NOT EXISTS sessions (
+            id              CHAR(36)                PRIMARY KEY,
+            account         CHAR(36)                NOT NULL,
+            tty             VARCHAR(64),
+            created_at      INTEGER                 NOT NULL,
+            last_seen       INTEGER                 NOT NULL,
+            expires_at      INTEGER                 NOT NULL,
+            ended_at        INTEGER,
+            FOREIGN KEY (account) REFERENCES account(id)
+        );
+
+        CREATE INDEX IF NOT EXISTS sessions_account ON sessions (account);")
+}
+
+/// Version 10 - append only audit log, each event is hash chained to the one before it
+/// - account : NULL when the username did not match an account
+/// - created_at is a unix time
+/// - prev_hash and hash : see audit::chain_hash
+fn create_audit_events(connection: &Connection) -> Result<(), rusqlite::Error> {
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS audit_events (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            account         CHAR(36),
+            username        VARCHAR(40)             NOT NULL,
+            event           VARCHAR(20)             NOT NULL,
+            outcome         VARCHAR(10)             NOT NULL,
+            detail          VARCHAR(40)             NOT NULL DEFAULT '',
+            created_at      INTEGER                 NOT NULL,
+            prev_hash       CHAR(64)                NOT NULL,
+            hash            CHAR(64)                NOT NULL,
+            key_id          VARCHAR(40)             NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS audit_events_account ON audit_events (account);
+
+        CREATE TRIGGER IF NOT EXISTS audit_events_no_update BEFORE UPDATE ON audit_events
+        BEGIN
+            SELECT RAISE(ABORT, 'audit_events is append only');
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS audit_events_no_delete BEFORE DELETE ON audit_events
+        BEGIN
+            SELECT RAISE(ABORT, 'audit_events is append only');
+        END;")
+}
+
+/// Version 11 - role of the account, selecting the password rules of the policy
+fn add_role(connection: &Connection) -> Result<(), rusqlite::Error> {
+    add_column_if_missing(connection, "account", "role", "VARCHAR(20) NOT NULL DEFAULT 'user'")
+}
+
+/// Version 12 - canonical form of the username, unique so usernames differing only by case or
+/// compatibility characters cannot both exist, and the skeleton of look alike usernames
+/// - an account differing only by case from an older one gets its canonical form with a `~2`, `~3`...
+///   suffix no username can hold, it keeps logging in with its exact username until renamed
+fn add_canonical_username(connection: &Connection) -> Result<(), rusqlite::Error> {
+    add_column_if_missing(connection, "account", "canonical", "VARCHAR(40)")?;
+    add_column_if_missing(connection, "account", "skeleton", "VARCHAR(80)")?;
+
+    let mut stmt = connection.prepare("SELECT `id`, `username` FROM `account` ORDER BY `created_at`, `rowid`")?;
+    let accounts = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut seen: HashMap<String, String> = HashMap::new();
+    for (id, username) in accounts {
+        let mut canonical = canonical(username.as_str());
+        if let Some(existing) = seen.get(&canonical) {
+            let suffixed = (2..)
+                .map(|suffix| format!("{}~{}", canonical, suffix))
+                .find(|suffixed| !seen.contains_key(suffixed))
+                .unwrap_or_default();
+            logger::warn("migration", "username only differs by case from an older account, rename it",
+                &[("username", &username), ("existing", existing), ("canonical", &suffixed)]);
+            canonical = suffixed;
+        }
+        connection.execute("UPDATE `account` SET `canonical` = ?1, `skeleton` = ?2 WHERE `id` = ?3",
+            (&canonical, skeleton(username.as_str()), &id))?;
+        seen.insert(canonical, username);
+    }
+
+    connection.execute_batch(
+        "CREATE UNIQUE INDEX IF NOT EXISTS account_canonical ON account (canonical);
+        CREATE INDEX IF NOT EXISTS account_skeleton ON account (skeleton);")
+}
+
+/// Version 13 - hashes of the last passwords of each account, refused when the password is changed
+/// - password : hash of the password, made like account passwords
+/// - pepper_id : id of the pepper key the password was hashed with
+/// - created_at and password_changed_at are unix times, the current password of every account
+///   is its first entry, changed when the account was last updated
+fn create_password_history(connection: &Connection) -> Result<(), rusqlite::Error> {
+    add_column_if_missing(connection, "account", "password_changed_at", "INTEGER")?;
+
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS password_history (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            account         CHAR(36)                NOT NULL,
+            password        VARCHAR(150)            NOT NULL,
+            pepper_id       VARCHAR(40),
+            created_at      INTEGER                 NOT NULL,
+            FOREIGN KEY (account) REFERENCES account(id)
+        );
+
+        CREATE INDEX IF NOT EXISTS password_history_account ON password_history (account);
+
+        UPDATE account SET password_changed_at = CAST(COALESCE(strftime('%s', updated_at), strftime('%s', 'now')) AS INTEGER)
+            WHERE password_changed_at IS NULL;
+
+        INSERT INTO password_history (account, password, pepper_id, created_at)
+            SELECT id, password, pepper_id, password_changed_at FROM account;")
+}
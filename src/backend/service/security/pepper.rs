@@ -0,0 +1,275 @@
+use std::{fmt, fs, env, os::unix::fs::{MetadataExt, PermissionsExt}, sync::OnceLock};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use super::super::utility::logger;
+
+/// Environment variable holding a single pepper key as `id:secret`
+const PEPPER_ENV: &str = "TERMAUTH_PEPPER";
+
+/// Environment variable overriding the location of the pepper key file
+const PEPPER_FILE_ENV: &str = "TERMAUTH_PEPPER_FILE";
+
+/// Default location of the pepper key file
+const PEPPER_FILE: &str = "/etc/termauth/pepper";
+
+/// Key id recorded for hashes made while no pepper is configured
+pub const NO_PEPPER_ID: &str = "none";
+
+/// Pepper concatenated to passwords before peppers were externalised. Only used
+/// to verify hashes recorded without a pepper key id, which are replaced at the
+/// next successful login.
+const LEGACY_PEPPER: &str = "PkCt&farjdWL2&WTaoddA2u7S4hfxDkbtNFxxU92";
+
+/// Label the key of the audit chain is derived under, so it never equals the pepper of a password
+const AUDIT_KEY_LABEL: &[u8] = b"termauth audit chain";
+
+static PEPPER: OnceLock<Pepper> = OnceLock::new();
+
+/// Errors while loading the pepper
+#[derive(Debug)]
+pub enum PepperError {
+    Io(String, std::io::Error),
+    Permissions(String, u32),
+    Owner(String, u32),
+    Format(String),
+}
+
+impl fmt::Display for PepperError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PepperError::Io(path, error) => write!(f, "unable to read pepper file {}: {}", path, error),
+            PepperError::Permissions(path, mode) => write!(f,
+                "pepper file {} must only be readable by its owner (mode {:o}), run: chmod 600 {}", path, mode, path),
+            PepperError::Owner(path, uid) => write!(f,
+                "pepper file {} must be owned by root or by the user running termauth (owner uid {})", path, uid),
+            PepperError::Format(source) => write!(f, "invalid pepper key in {}, expected `id:secret`", source),
+        }
+    }
+}
+
+impl std::error::Error for PepperError {}
+
+/// A pepper key, the secret is zeroed on drop
+struct PepperKey {
+    id: String,
+    secret: Zeroizing<Vec<u8>>,
+}
+
+/// Server side secret mixed into every password before it is hashed.
+///
+/// Keys are read from the `TERMAUTH_PEPPER` environment variable or from a key file
+/// readable only by its owner (`/etc/termauth/pepper`, or `TERMAUTH_PEPPER_FILE`).
+/// The key file holds one `id:secret` per line, the first key is the active one and
+/// the others are kept to verify hashes made before a rotation.
+///
+/// The pepper is applied as an HMAC-SHA256 of the password, so the input to the
+/// password hash has a fixed length whatever the length of the password.
+#[derive(Default)]
+pub struct Pepper {
+    keys: Vec<PepperKey>,
+}
+
+impl Pepper {
+
+    /// Load the pepper once for the whole process, fails if the key file is unsafe or invalid
+    pub fn init() -> Result<&'static Pepper, PepperError> {
+        if let Some(pepper) = PEPPER.get() {
+            return Ok(pepper);
+        }
+
+        let pepper = Pepper::load()?;
+        if pepper.keys.is_empty() {
+            logger::warn("pepper", "no pepper key configured, passwords are hashed without a pepper",
+                &[("pepper_id", &NO_PEPPER_ID)]);
+        }
+        Ok(PEPPER.get_or_init(|| pepper))
+    }
+
+    /// Get the pepper of the process, loaded on first use
+    /// - panics if the key file is unsafe or invalid, startup already refuses it through init
+    pub fn global() -> &'static Pepper {
+        PEPPER.get_or_init(|| Pepper::load().unwrap_or_else(|error| panic!("{}", error)))
+    }
+
+    /// Load the pepper keys from the environment, else from the key file
+    pub fn load() -> Result<Pepper, PepperError> {
+
+        // Environment secret, removed from the environment once read
+        if let Ok(value) = env::var(PEPPER_ENV) {
+            env::remove_var(PEPPER_ENV);
+            let value = Zeroizing::new(value);
+            let key = parse_key(value.trim()).ok_or_else(|| PepperError::Format(PEPPER_ENV.to_string()))?;
+            return Ok(Pepper { keys: vec![key] });
+        }
+
+        let path = env::var(PEPPER_FILE_ENV).unwrap_or_else(|_| PEPPER_FILE.to_string());
+        Pepper::load_file(path)
+    }
+
+    /// Load the pepper keys of a key file, no keys if there is none
+    /// - refused when it is readable by the group or others, or owned by another user than root
+    fn load_file(path: String) -> Result<Pepper, PepperError> {
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Pepper::default()),
+            Err(error) => return Err(PepperError::Io(path, error)),
+        };
+
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            return Err(PepperError::Permissions(path, mode));
+        }
+
+        // getuid always succeeds and has no side effect
+        let uid = unsafe { libc::getuid() };
+        if metadata.uid() != 0 && metadata.uid() != uid {
+            return Err(PepperError::Owner(path, metadata.uid()));
+        }
+
+        let contents = Zeroizing::new(fs::read_to_string(&path).map_err(|error| PepperError::Io(path.clone(), error))?);
+        let keys = contents
+            .lines()
+            .enumerate()
+            .map(|(number, line)| (number, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(number, line)| parse_key(line)
+                .ok_or_else(|| PepperError::Format(format!("{} line {}", path, number + 1))))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Pepper { keys })
+    }
+
+    /// Id of the key new hashes are peppered with
+    pub fn active_id(&self) -> String {
+        match self.keys.first() {
+            Some(key) => key.id.clone(),
+            None => NO_PEPPER_ID.to_string(),
+        }
+    }
+
+    /// Pepper a password with the key of the given id
+    /// - None : legacy hashes, the old constant is concatenated to the password
+    /// - "none" : hashes made while no pepper was configured
+    /// - returns None if the key is no longer available
+    pub fn apply(&self, key_id: Option<&str>, password: &str) -> Option<Zeroizing<String>> {
+        match key_id {
+            None => Some(Zeroizing::new(format!("{}{}", password, LEGACY_PEPPER))),
+            Some(NO_PEPPER_ID) => Some(Zeroizing::new(password.to_string())),
+            Some(key_id) => {
+                let key = self.keys.iter().find(|key| key.id == key_id)?;
+                let mut mac = Hmac::<Sha256>::new_from_slice(&key.secret).expect("hmac accepts any key length");
+                mac.update(password.as_bytes());
+
+                let digest = mac.finalize().into_bytes();
+                Some(Zeroizing::new(digest.iter().map(|byte| format!("{:02x}", byte)).collect()))
+            }
+        }
+    }
+
+    /// HMAC-SHA256 keyed for the audit chain, with a key derived from the pepper key of the given id
+    /// - "none" : events recorded while no pepper was configured, keyed with an empty key
+    /// - returns None if the key is no longer available
+    pub fn audit_mac(&self, key_id: &str) -> Option<Hmac<Sha256>> {
+        let secret: &[u8] = match key_id {
+            NO_PEPPER_ID => &[],
+            key_id => &self.keys.iter().find(|key| key.id == key_id)?.secret,
+        };
+
+        let mut derive = Hmac::<Sha256>::new_from_slice(secret).expect("hmac accepts any key length");
+        derive.update(AUDIT_KEY_LABEL);
+        let audit_key = Zeroizing::new(derive.finalize().into_bytes().to_vec());
+
+        Some(Hmac::<Sha256>::new_from_slice(&audit_key).expect("hmac accepts any key length"))
+    }
+}
+
+/// Parse a `id:secret` pepper key
+fn parse_key(line: &str) -> Option<PepperKey> {
+    match line.split_once(':') {
+        Some((id, secret)) if !id.trim().is_empty() && id.trim() != NO_PEPPER_ID && !secret.is_empty() => Some(PepperKey {
+            id: id.trim().to_string(),
+            secret: Zeroizing::new(secret.as_bytes().to_vec()),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pepper(lines: &[&str]) -> Pepper {
+        Pepper { keys: lines.iter().map(|line| parse_key(line).unwrap()).collect() }
+    }
+
+    fn key_file(name: &str, contents: &str, mode: u32) -> String {
+        let path = env::temp_dir().join(format!("termauth-pepper-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn parses_key_ids() {
+        let key = parse_key(" 2024-01 :s3cr:et").unwrap();
+        assert_eq!(key.id, "2024-01");
+        assert_eq!(key.secret.as_slice(), b"s3cr:et");
+
+        assert!(parse_key("secret").is_none());
+        assert!(parse_key(":secret").is_none());
+        assert!(parse_key("id:").is_none());
+        assert!(parse_key("none:secret").is_none());
+    }
+
+    #[test]
+    fn verifies_with_rotated_keys() {
+        let before = pepper(&["old:first secret"]);
+        let after = pepper(&["new:second secret", "old:first secret"]);
+        assert_eq!(after.active_id(), "new");
+        assert_eq!(Pepper::default().active_id(), NO_PEPPER_ID);
+
+        let hashed = before.apply(Some("old"), "password").unwrap();
+        assert_eq!(after.apply(Some("old"), "password").unwrap(), hashed);
+        assert_ne!(after.apply(Some("new"), "password").unwrap(), hashed);
+        assert!(before.apply(Some("new"), "password").is_none());
+        assert!(after.audit_mac("old").is_some());
+        assert!(before.audit_mac("new").is_none());
+    }
+
+    #[test]
+    fn refuses_readable_key_file() {
+        let path = key_file("readable", "new:secret\nold:secret\n", 0o640);
+        let loaded = Pepper::load_file(path.clone());
+        assert!(matches!(loaded, Err(PepperError::Permissions(_, 0o640))));
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(Pepper::load_file(path.clone()).unwrap().active_id(), "new");
+        fs::remove_file(&path).unwrap();
+
+        assert!(Pepper::load_file(path).unwrap().keys.is_empty());
+    }
+
+    #[test]
+    fn refuses_key_file_of_another_user() {
+        let path = key_file("owner", "new:secret\n", 0o600);
+
+        // Only root can give the file away, nothing to check for other users
+        let given = std::os::unix::fs::chown(&path, Some(u32::MAX - 1), None).is_ok();
+        let loaded = Pepper::load_file(path.clone());
+        fs::remove_file(&path).unwrap();
+        if given {
+            assert!(matches!(loaded, Err(PepperError::Owner(_, uid)) if uid == u32::MAX - 1));
+        }
+    }
+
+    #[test]
+    fn refuses_invalid_key_file() {
+        let path = key_file("invalid", "# rotated yearly\nnew:secret\nsecret\n", 0o600);
+        let loaded = Pepper::load_file(path.clone());
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(PepperError::Format(source)) if source.ends_with("line 3")));
+    }
+}
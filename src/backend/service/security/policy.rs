@@ -0,0 +1,604 @@
+use std::{collections::HashMap, fmt, fs, io, path::Path, sync::{Arc, OnceLock}};
+
+use super::blocklists::{Blocklists, ListKind};
+use super::breach::BreachIndex;
+use super::secret::SecretString;
+use super::super::utility::{constants::ENROLMENT_INSTRUCTIONS, logger, paths};
+
+/// Role of accounts unless another one is given
+pub const DEFAULT_ROLE: &str = "user";
+
+/// Role of accounts allowed to administer the installation, such as reloading the username lists
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Marker of ENROLMENT_INSTRUCTIONS replaced by the rules of the active policy
+const RULES_MARKER: &str = "{password_rules}";
+
+/// Indentation of the rules within ENROLMENT_INSTRUCTIONS
+const RULES_INDENT: &str = "                ";
+
+/// Usernames shorter than this are too common to be refused within a password
+const MIN_CONTEXT_LENGTH: usize = 3;
+
+static POLICY: OnceLock<Policy> = OnceLock::new();
+
+/// Errors while loading the policy
+/// - Io : the policy file could not be read
+/// - Syntax : a line of the policy file is invalid
+/// - Invalid : the rules of a role contradict each other
+#[derive(Debug)]
+pub enum PolicyError {
+    Io(String, io::Error),
+    Syntax { path: String, line: usize, message: String },
+    Invalid(String, String),
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyError::Io(path, error) => write!(f, "unable to read policy file {}: {}", path, error),
+            PolicyError::Syntax { path, line, message } => write!(f, "invalid policy file {}, line {}: {}", path, line, message),
+            PolicyError::Invalid(path, message) => write!(f, "invalid policy file {}: {}", path, message),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+/// Password rules of a role
+/// - min_length and max_length : bounds of the length, in Unicode code points
+/// - blocklists : lists of refused passwords, bundled lists by name or other files, one password per line
+/// - breach_index : index of breached passwords built by `breach import`, None to skip the check
+/// - context_words : words refused within a password, along with the username
+/// - max_repeated : longest run of the same character, 0 for no limit
+/// - max_sequential : longest run of sequential characters such as abcd or 4321, 0 for no limit
+/// - password_history : last passwords of an account refused when it is changed, the current one
+///   included, which is refused whatever the rule
+/// - blocked : passwords of the blocklists with their rank, the line they are found on
+/// - breached : opened breach index, None if there is no index at its location
+#[derive(Clone)]
+pub struct PasswordRules {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub blocklists: Vec<String>,
+    pub breach_index: Option<String>,
+    pub context_words: Vec<String>,
+    pub max_repeated: usize,
+    pub max_sequential: usize,
+    pub password_history: usize,
+    blocked: HashMap<String, usize>,
+    breached: Option<Arc<BreachIndex>>,
+}
+
+impl Default for PasswordRules {
+    fn default() -> PasswordRules {
+        PasswordRules {
+            min_length: 8,
+            max_length: 64,
+            blocklists: vec!["weakpasswords.txt".to_string(), "breachedpasswords.txt".to_string()],
+            breach_index: Some(paths::breach_index().display().to_string()),
+            context_words: vec!["termauth".to_string()],
+            max_repeated: 3,
+            max_sequential: 4,
+            password_history: 5,
+            blocked: HashMap::new(),
+            breached: None,
+        }
+    }
+}
+
+impl PasswordRules {
+
+    /// Check a password against every rule, the reasons of every failed rule are returned
+    pub fn check(&self, username: &str, password: &SecretString) -> Vec<String> {
+        let password = password.expose();
+        let characters: Vec<char> = password.chars().collect();
+        let lowercase = password.to_lowercase();
+        let mut reasons = Vec::new();
+
+        // Length in code points, a multi byte character counts once
+        if characters.len() < self.min_length {
+            reasons.push(format!("Password must be at least {} characters long", self.min_length));
+        }
+        if characters.len() > self.max_length {
+            reasons.push(format!("Password must be at most {} characters long", self.max_length));
+        }
+
+        if self.blocked.contains_key(password) {
+            reasons.push("Password is commonly used or was found in a breach, use another".to_string());
+        } else if self.is_breached(password) {
+            reasons.push("Password was exposed in a data breach, use another".to_string());
+        }
+
+        let username = username.to_lowercase();
+        if username.chars().count() >= MIN_CONTEXT_LENGTH && lowercase.contains(username.as_str()) {
+            reasons.push("Password must not contain your username".to_string());
+        }
+        for word in &self.context_words {
+            if lowercase.contains(word.to_lowercase().as_str()) {
+                reasons.push(format!("Password must not contain \"{}\"", word));
+            }
+        }
+
+        if self.max_repeated > 0 && longest_run(&characters, |a, b| a == b) > self.max_repeated {
+            reasons.push(format!("Password must not repeat a character more than {} times in a row", self.max_repeated));
+        }
+
+        if self.max_sequential > 0 {
+            let ascending = longest_run(&characters, |a, b| b as u32 == a as u32 + 1);
+            let descending = longest_run(&characters, |a, b| a as u32 == b as u32 + 1);
+            if ascending.max(descending) > self.max_sequential {
+                reasons.push(format!("Password must not have more than {} sequential characters, such as abcd or 4321",
+                    self.max_sequential));
+            }
+        }
+
+        reasons
+    }
+
+    /// Rank of a password within the blocklists, 1 being the most common
+    pub fn rank(&self, password: &str) -> Option<usize> {
+        self.blocked.get(password).copied()
+    }
+
+    /// Check if a password is in the breach index, false without an index
+    pub fn is_breached(&self, password: &str) -> bool {
+        self.breached.as_ref().map(|index| index.contains(password)).unwrap_or(false)
+    }
+
+    /// Hashes in the breach index, None without an index
+    pub fn breached_count(&self) -> Option<u64> {
+        self.breached.as_ref().map(|index| index.count())
+    }
+
+    /// Words refused within a password, the username first
+    pub fn context(&self, username: &str) -> Vec<String> {
+        let mut words: Vec<String> = self.context_words.iter().map(|word| word.to_lowercase()).collect();
+        if username.chars().count() >= MIN_CONTEXT_LENGTH {
+            words.insert(0, username.to_lowercase());
+        }
+        words
+    }
+
+    /// Rules in plain words, one per line
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![format!("{} to {} characters long, spaces and any Unicode character allowed",
+            self.min_length, self.max_length)];
+
+        if !self.blocklists.is_empty() {
+            lines.push(format!("Not a common or breached password ({})", self.blocklists.join(", ")));
+        }
+        if let Some(index) = &self.breached {
+            lines.push(format!("Not exposed in a data breach ({} {} hashes indexed)", index.count(), index.kind().as_str()));
+        }
+
+        let mut words = vec!["your username".to_string()];
+        words.extend(self.context_words.iter().map(|word| format!("\"{}\"", word)));
+        lines.push(format!("Without {}", words.join(" or ")));
+
+        if self.max_repeated > 0 {
+            lines.push(format!("No character repeated more than {} times in a row", self.max_repeated));
+        }
+        if self.max_sequential > 0 {
+            lines.push(format!("No more than {} sequential characters, such as abcd or 4321", self.max_sequential));
+        }
+        if self.password_history > 1 {
+            lines.push(format!("When changed, not one of your last {} passwords", self.password_history));
+        }
+
+        lines
+    }
+
+    /// Apply a `key = value` line of the policy file
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        let number = || value.parse::<usize>().map_err(|_| format!("`{}` expects a number, found `{}`", key, value));
+        let list = || value.split(',').map(str::trim).filter(|item| !item.is_empty()).map(str::to_string).collect();
+
+        match key {
+            "min_length" => self.min_length = number()?,
+            "max_length" => self.max_length = number()?,
+            "blocklists" => self.blocklists = list(),
+            "breach_index" => self.breach_index = Some(value.to_string()).filter(|path| !path.is_empty()),
+            "context_words" => self.context_words = list(),
+            "max_repeated" => self.max_repeated = number()?,
+            "max_sequential" => self.max_sequential = number()?,
+            "password_history" => self.password_history = number()?,
+            _ => return Err(format!("unknown rule `{}`", key)),
+        }
+
+        Ok(())
+    }
+}
+
+/// Password policy, declared in `policy.conf` within the configuration directory.
+///
+/// The file holds `rule = value` lines for every account, followed by `[role.<name>]`
+/// sections overriding some of the rules for accounts of that role. Without a file the
+/// default rules apply.
+pub struct Policy {
+    rules: PasswordRules,
+    roles: HashMap<String, PasswordRules>,
+    instructions: String,
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Policy::build(PasswordRules::default(), HashMap::new())
+    }
+}
+
+impl Policy {
+
+    /// Load the policy once for the whole process, fails if the policy file is invalid
+    pub fn init() -> Result<&'static Policy, PolicyError> {
+        if let Some(policy) = POLICY.get() {
+            return Ok(policy);
+        }
+
+        let policy = Policy::load()?;
+        Ok(POLICY.get_or_init(|| policy))
+    }
+
+    /// Get the policy of the process, loaded on first use
+    pub fn global() -> &'static Policy {
+        POLICY.get_or_init(|| Policy::load().unwrap_or_default())
+    }
+
+    /// Load the policy file of the configuration directory, the defaults if there is none
+    pub fn load() -> Result<Policy, PolicyError> {
+        let path = paths::policy_file();
+        let display = path.display().to_string();
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => Policy::parse(contents.as_str(), display.as_str()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Policy::default()),
+            Err(error) => Err(PolicyError::Io(display, error)),
+        }
+    }
+
+    /// Parse a policy file, roles start from the rules declared before their section and
+    /// each role has a single section
+    pub fn parse(contents: &str, path: &str) -> Result<Policy, PolicyError> {
+        let mut rules = PasswordRules::default();
+        let mut roles: HashMap<String, PasswordRules> = HashMap::new();
+        let mut role: Option<String> = None;
+
+        for (index, line) in contents.lines().enumerate() {
+            let syntax = |message: String| PolicyError::Syntax { path: path.to_string(), line: index + 1, message };
+            // Comments start with # and run to the end of the line
+            let line = line.split('#').next().unwrap_or_default().trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(section) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                let name = section.trim().strip_prefix("role.")
+                    .filter(|name| !name.is_empty())
+                    .ok_or_else(|| syntax(format!("unknown section `{}`, expected `[role.<name>]`", section)))?;
+                if roles.insert(name.to_string(), rules.clone()).is_some() {
+                    return Err(syntax(format!("duplicate section `[role.{}]`", name)));
+                }
+                role = Some(name.to_string());
+                continue;
+            }
+
+            let (key, value) = line.split_once('=')
+                .ok_or_else(|| syntax(format!("expected `rule = value`, found `{}`", line)))?;
+            let target = match &role {
+                Some(role) => roles.get_mut(role).expect("role section"),
+                None => &mut rules,
+            };
+            target.set(key.trim(), value.trim()).map_err(syntax)?;
+        }
+
+        for (name, rules) in std::iter::once((DEFAULT_ROLE, &rules)).chain(roles.iter().map(|(name, rules)| (name.as_str(), rules))) {
+            if rules.min_length > rules.max_length {
+                return Err(PolicyError::Invalid(path.to_string(),
+                    format!("min_length is greater than max_length for the {} role", name)));
+            }
+        }
+
+        Ok(Policy::build(rules, roles))
+    }
+
+    /// Read the blocklists and render the instructions of a parsed policy
+    fn build(mut rules: PasswordRules, mut roles: HashMap<String, PasswordRules>) -> Policy {
+        let mut files: HashMap<String, HashMap<String, usize>> = HashMap::new();
+        let mut indexes: HashMap<String, Option<Arc<BreachIndex>>> = HashMap::new();
+        rules.blocked = read_blocklists(&rules.blocklists, &mut files);
+        rules.breached = open_breach_index(rules.breach_index.as_deref(), &mut indexes);
+        for rules in roles.values_mut() {
+            rules.blocked = read_blocklists(&rules.blocklists, &mut files);
+            rules.breached = open_breach_index(rules.breach_index.as_deref(), &mut indexes);
+        }
+
+        let mut policy = Policy { rules, roles, instructions: String::new() };
+        policy.instructions = policy.render_instructions();
+        policy
+    }
+
+    /// Get the rules of a role, the default rules for roles without a section
+    pub fn rules(&self, role: &str) -> &PasswordRules {
+        self.roles.get(role).unwrap_or(&self.rules)
+    }
+
+    /// Check a password of an account of the given role, the reasons of every failed rule are returned
+    pub fn check(&self, role: &str, username: &str, password: &SecretString) -> Vec<String> {
+        self.rules(role).check(username, password)
+    }
+
+    /// Enrollment instructions stating the rules of the policy
+    pub fn enrolment_instructions(&self) -> &str {
+        self.instructions.as_str()
+    }
+
+    /// Fill the rules into ENROLMENT_INSTRUCTIONS, roles with a section are listed after the defaults
+    fn render_instructions(&self) -> String {
+        let mut lines: Vec<String> = self.rules.describe()
+            .into_iter()
+            .enumerate()
+            .map(|(index, line)| format!("{}{}. {}", RULES_INDENT, index + 1, line))
+            .collect();
+
+        let mut roles: Vec<&String> = self.roles.keys().collect();
+        roles.sort();
+        for role in roles {
+            lines.push(String::new());
+            lines.push(format!("{}Accounts with the {} role:", RULES_INDENT, role));
+            lines.extend(self.roles[role].describe()
+                .into_iter()
+                .enumerate()
+                .map(|(index, line)| format!("{}{}. {}", RULES_INDENT, index + 1, line)));
+        }
+
+        ENROLMENT_INSTRUCTIONS.replace(RULES_MARKER, lines.join("\n").trim_start())
+    }
+}
+
+/// Union of the passwords of blocklists with their best rank, each extra file is only read once
+/// - bundled lists come from Blocklists, with their override files merged
+/// - other files are read relative to the configuration directory
+/// - missing files are skipped with a warning rather than failing every sign up
+fn read_blocklists(blocklists: &[String], files: &mut HashMap<String, HashMap<String, usize>>) -> HashMap<String, usize> {
+    let mut blocked: HashMap<String, usize> = HashMap::new();
+
+    for file in blocklists {
+        let passwords = files.entry(file.clone()).or_insert_with(|| match ListKind::from_password_file(file) {
+            Some(kind) => Blocklists::global().list(kind).entries().clone(),
+            None => match fs::read_to_string(paths::config_dir().join(file)) {
+                Ok(contents) => {
+                    let mut passwords = HashMap::new();
+                    for (index, password) in contents.lines().enumerate() {
+                        passwords.entry(password.to_string()).or_insert(index + 1);
+                    }
+                    passwords
+                },
+                Err(error) => {
+                    logger::warn("policy", "blocklist not loaded", &[("file", file), ("error", &error.to_string())]);
+                    HashMap::new()
+                }
+            },
+        });
+        for (password, rank) in passwords.iter() {
+            let best = blocked.entry(password.clone()).or_insert(*rank);
+            *best = (*best).min(*rank);
+        }
+    }
+
+    blocked
+}
+
+/// Open a breach index, each index is only opened once
+/// - a missing index skips the check, `breach import` builds one
+fn open_breach_index(path: Option<&str>, indexes: &mut HashMap<String, Option<Arc<BreachIndex>>>) -> Option<Arc<BreachIndex>> {
+    let path = path?;
+
+    indexes.entry(path.to_string()).or_insert_with(|| match Path::new(path).exists() {
+        true => match BreachIndex::open(Path::new(path)) {
+            Ok(index) => {
+                logger::info("policy", "breach index loaded", &[("path", &path), ("hashes", &index.count())]);
+                Some(Arc::new(index))
+            },
+            Err(error) => {
+                logger::warn("policy", "breach index not loaded", &[("error", &error.to_string())]);
+                None
+            },
+        },
+        false => None,
+    }).clone()
+}
+
+/// Length of the longest run of characters where each follows the one before it
+fn longest_run(characters: &[char], follows: impl Fn(char, char) -> bool) -> usize {
+    let mut longest = characters.len().min(1);
+    let mut current = longest;
+
+    for pair in characters.windows(2) {
+        current = match follows(pair[0], pair[1]) {
+            true => current + 1,
+            false => 1,
+        };
+        longest = longest.max(current);
+    }
+
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::breach::{self, HashKind};
+    use data_encoding::HEXUPPER;
+
+    /// Rules without blocklists nor breach index, so only the rule under test can fail
+    const BARE: &str = "blocklists =\nbreach_index =\n";
+
+    fn parse(contents: &str) -> Result<Policy, PolicyError> {
+        Policy::parse(format!("{}{}", BARE, contents).as_str(), "policy.conf")
+    }
+
+    fn rules(contents: &str) -> PasswordRules {
+        parse(contents).unwrap().rules(DEFAULT_ROLE).clone()
+    }
+
+    fn check(rules: &PasswordRules, username: &str, password: &str) -> Vec<String> {
+        rules.check(username, &password.into())
+    }
+
+    /// Line of a syntax error within the contents given to parse, after the lines of BARE
+    fn syntax_line(result: Result<Policy, PolicyError>) -> usize {
+        match result {
+            Err(PolicyError::Syntax { line, .. }) => line - 2,
+            Err(error) => panic!("unexpected error: {}", error),
+            Ok(_) => panic!("policy accepted"),
+        }
+    }
+
+    /// Scratch directory of a test
+    fn scratch_dir(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("termauth-policy-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn parses_rules_and_roles() {
+        let policy = parse("
+            # defaults of every account
+            min_length = 10      # trailing comment
+            context_words = acme, termauth ,
+            max_repeated = 0
+
+            [role.admin]
+            min_length = 16
+            password_history = 10
+        ").unwrap();
+
+        let rules = policy.rules(DEFAULT_ROLE);
+        assert_eq!(rules.min_length, 10);
+        assert_eq!(rules.max_length, 64);
+        assert_eq!(rules.context_words, vec!["acme", "termauth"]);
+        assert_eq!(rules.max_repeated, 0);
+        assert!(rules.blocklists.is_empty());
+        assert_eq!(rules.breach_index, None);
+        assert_eq!(rules.password_history, 5);
+
+        let admin = policy.rules(ADMIN_ROLE);
+        assert_eq!(admin.min_length, 16);
+        assert_eq!(admin.password_history, 10);
+        assert_eq!(admin.context_words, vec!["acme", "termauth"]);
+        assert_eq!(admin.max_repeated, 0);
+    }
+
+    #[test]
+    fn roles_without_section_fall_back_to_defaults() {
+        let policy = parse("min_length = 12\n[role.admin]\nmin_length = 20\n[role.auditor]\n").unwrap();
+
+        assert_eq!(policy.rules(DEFAULT_ROLE).min_length, 12);
+        assert_eq!(policy.rules("guest").min_length, 12);
+        assert_eq!(policy.rules("auditor").min_length, 12);
+        assert_eq!(policy.rules(ADMIN_ROLE).min_length, 20);
+
+        // Rules after a section only apply to its role
+        let policy = parse("[role.admin]\nmin_length = 20\n").unwrap();
+        assert_eq!(policy.rules(DEFAULT_ROLE).min_length, PasswordRules::default().min_length);
+        assert_eq!(Policy::default().rules(ADMIN_ROLE).min_length, PasswordRules::default().min_length);
+    }
+
+    #[test]
+    fn refuses_invalid_files() {
+        assert_eq!(syntax_line(parse("min_length = 8\nmin_lenght = 10\n")), 2);
+        assert_eq!(syntax_line(parse("min_length = eight\n")), 1);
+        assert_eq!(syntax_line(parse("min_length 8\n")), 1);
+        assert_eq!(syntax_line(parse("[admin]\n")), 1);
+        assert_eq!(syntax_line(parse("[role.]\n")), 1);
+        assert_eq!(syntax_line(parse("[role.admin]\nmin_length = 16\n\n[role.admin]\nmax_length = 20\n")), 4);
+
+        assert!(matches!(parse("min_length = 20\nmax_length = 10\n"), Err(PolicyError::Invalid(..))));
+        assert!(matches!(parse("[role.admin]\nmax_length = 4\n"), Err(PolicyError::Invalid(_, message)) if message.contains("admin")));
+    }
+
+    #[test]
+    fn checks_length_in_code_points() {
+        let rules = rules("min_length = 4\nmax_length = 6\ncontext_words =\n");
+
+        assert!(check(&rules, "alice", "kgqz").is_empty());
+        assert!(check(&rules, "alice", "ñéüß").is_empty());
+        assert_eq!(check(&rules, "alice", "kgq"), vec!["Password must be at least 4 characters long"]);
+        assert_eq!(check(&rules, "alice", "kgqzwmx"), vec!["Password must be at most 6 characters long"]);
+    }
+
+    #[test]
+    fn checks_blocklists() {
+        let mut rules = rules("context_words =\n");
+        rules.blocked.insert("qwertyuiop".to_string(), 1);
+
+        assert_eq!(check(&rules, "alice", "qwertyuiop"), vec!["Password is commonly used or was found in a breach, use another"]);
+        assert!(check(&rules, "alice", "Qwertyuiop").is_empty());
+        assert_eq!(rules.rank("qwertyuiop"), Some(1));
+    }
+
+    #[test]
+    fn checks_breach_index() {
+        let scratch = scratch_dir("breach");
+        let corpus = scratch.join("corpus.txt");
+        fs::write(&corpus, format!("{}:3\n", HEXUPPER.encode(&HashKind::Sha1.digest("wdKq-jz8p-Nn")))).unwrap();
+        let index = scratch.join("breach.idx");
+        breach::import(&corpus, &index, false).unwrap();
+
+        let rules = rules(format!("breach_index = {}\ncontext_words =\n", index.display()).as_str());
+        fs::remove_dir_all(&scratch).unwrap();
+
+        assert_eq!(rules.breached_count(), Some(1));
+        assert_eq!(check(&rules, "alice", "wdKq-jz8p-Nn"), vec!["Password was exposed in a data breach, use another"]);
+        assert!(check(&rules, "alice", "wdKq-jz8p-Nm").is_empty());
+    }
+
+    #[test]
+    fn checks_context_words() {
+        let rules = rules("context_words = Acme\n");
+
+        assert_eq!(check(&rules, "Alice", "xALICEx-wdkq"), vec!["Password must not contain your username"]);
+        assert_eq!(check(&rules, "Alice", "wdkq-acme-xz"), vec!["Password must not contain \"Acme\""]);
+        // Short usernames are too common to be refused
+        assert!(check(&rules, "al", "wdkq-al-xzpt").is_empty());
+        assert_eq!(rules.context("Alice"), vec!["alice", "acme"]);
+    }
+
+    #[test]
+    fn checks_repeated_and_sequential_characters() {
+        let limited = rules("context_words =\nmax_repeated = 3\nmax_sequential = 4\n");
+
+        assert!(check(&limited, "alice", "wdkq-aaa-xz").is_empty());
+        assert_eq!(check(&limited, "alice", "wdkq-aaaa-xz"), vec!["Password must not repeat a character more than 3 times in a row"]);
+        assert!(check(&limited, "alice", "wdkq-abcd-xz").is_empty());
+        assert_eq!(check(&limited, "alice", "wdkq-abcde-xz").len(), 1);
+        assert_eq!(check(&limited, "alice", "wdkq-54321-xz").len(), 1);
+
+        let unlimited = rules("context_words =\nmax_repeated = 0\nmax_sequential = 0\n");
+        assert!(check(&unlimited, "alice", "aaaaaaaa-abcdefgh").is_empty());
+    }
+
+    #[test]
+    fn reports_every_failed_rule() {
+        let rules = rules("context_words = acme\n");
+        assert_eq!(check(&rules, "alice", "acmeaaaa").len(), 2);
+        assert_eq!(check(&rules, "alice", "acme").len(), 2);
+    }
+
+    #[test]
+    fn renders_rules_of_each_role() {
+        let policy = parse("min_length = 10\n[role.zeta]\nmin_length = 30\n[role.admin]\nmin_length = 20\n").unwrap();
+        let instructions = policy.enrolment_instructions();
+
+        assert!(!instructions.contains(RULES_MARKER));
+        assert!(instructions.contains("1. 10 to 64 characters long"));
+        let admin = instructions.find("Accounts with the admin role:").unwrap();
+        let zeta = instructions.find("Accounts with the zeta role:").unwrap();
+        assert!(admin < zeta);
+        assert!(instructions[admin..zeta].contains("1. 20 to 64 characters long"));
+        assert!(instructions[zeta..].contains("1. 30 to 64 characters long"));
+    }
+}
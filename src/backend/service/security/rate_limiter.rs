@@ -1,2 +1,262 @@
-/// Suppposed rate limiter not implemented
-/// This was a required feature but it was not completed on time.
+use std::time::Duration;
+
+use chrono::Utc;
+
+use super::canonical::canonical;
+use super::database::{Database, AttemptManager, AttemptRecord};
+
+/// Scope under which failures against every username are counted
+const GLOBAL_SCOPE: &str = "*";
+
+/// Failed attempts allowed for a username before backing off
+const FREE_ATTEMPTS: i64 = 3;
+
+/// First backoff delay in seconds, doubled on every further failure
+const BACKOFF_BASE: i64 = 2;
+
+/// Failed attempts for a username after which the username is locked out
+const LOCKOUT_THRESHOLD: i64 = 10;
+
+/// Seconds a locked out username has to wait
+const LOCKOUT_DURATION: i64 = 15 * 60;
+
+/// Seconds without failures after which the failures of a username are forgotten
+const FAILURE_RESET: i64 = 24 * 60 * 60;
+
+/// Window in seconds over which failures against every username are counted
+const GLOBAL_WINDOW: i64 = 60;
+
+/// Failures against every username within the window before all logins are throttled
+const GLOBAL_THRESHOLD: i64 = 50;
+
+/// Seconds all logins are throttled once the global threshold is reached
+const GLOBAL_LOCKOUT_DURATION: i64 = 60;
+
+/// Traits of a login rate limiter
+/// - get_wait : time left before a login attempt is accepted
+/// - record_failure : count a failed attempt and apply backoff or lockout
+/// - record_success : forget the failures of a username
+pub trait Limiter {
+    fn new(database: Database) -> Self;
+    fn get_wait(&mut self, username: &str) -> Result<Option<Duration>, rusqlite::Error>;
+    fn record_failure(&mut self, username: &str) -> Result<Option<Duration>, rusqlite::Error>;
+    fn record_success(&mut self, username: &str) -> Result<(), rusqlite::Error>;
+}
+
+/// Rate limiter for login attempts, persisted in the database so restarting
+/// the application does not reset it.
+///
+/// Failures are counted per username and across every username. After a few
+/// free attempts every failure doubles the wait before the next attempt, and
+/// enough consecutive failures lock the username out for a while. A flood of
+/// failures across usernames throttles every login. Usernames are counted by their
+/// canonical form, so changing their case does not escape the backoff.
+pub struct RateLimiter {
+    database: Database,
+}
+
+impl Limiter for RateLimiter {
+
+    /// Create a new rate limiter persisting attempts in the given database
+    fn new(database: Database) -> RateLimiter {
+        RateLimiter { database }
+    }
+
+    /// Get the time left before a login attempt for the username is accepted
+    fn get_wait(&mut self, username: &str) -> Result<Option<Duration>, rusqlite::Error> {
+        let now = Utc::now().timestamp();
+        let username = canonical(username);
+
+        let locked_until = [username.as_str(), GLOBAL_SCOPE]
+            .iter()
+            .map(|scope| self.database.get_attempts(scope))
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .flatten()
+            .map(|attempts| attempts.locked_until)
+            .max()
+            .unwrap_or(0);
+
+        Ok(wait_until(now, locked_until))
+    }
+
+    /// Count a failed attempt for the username and globally, returns the time
+    /// left before the next attempt is accepted
+    fn record_failure(&mut self, username: &str) -> Result<Option<Duration>, rusqlite::Error> {
+        let now = Utc::now().timestamp();
+        let username = canonical(username);
+
+        // Per username - exponential backoff then lockout
+        let mut attempts = self.database.get_attempts(username.as_str())?
+            .filter(|attempts| now - attempts.last_failure < FAILURE_RESET)
+            .unwrap_or_else(|| empty_attempts(username.as_str()));
+
+        attempts.failures += 1;
+        attempts.last_failure = now;
+        attempts.locked_until = now + backoff(attempts.failures);
+        self.database.save_attempts(&attempts)?;
+
+        // Every username - count failures within a fixed window
+        let mut global = self.database.get_attempts(GLOBAL_SCOPE)?
+            .filter(|global| now - global.last_failure < GLOBAL_WINDOW)
+            .unwrap_or_else(|| AttemptRecord { last_failure: now, ..empty_attempts(GLOBAL_SCOPE) });
+
+        global.failures += 1;
+        if global.failures >= GLOBAL_THRESHOLD {
+            global.locked_until = now + GLOBAL_LOCKOUT_DURATION;
+            global.failures = 0;
+            global.last_failure = now;
+        }
+        self.database.save_attempts(&global)?;
+
+        Ok(wait_until(now, attempts.locked_until.max(global.locked_until)))
+    }
+
+    /// Forget the failures of the username after a successful login
+    fn record_success(&mut self, username: &str) -> Result<(), rusqlite::Error> {
+        self.database.clear_attempts(canonical(username).as_str())
+    }
+}
+
+/// Seconds to wait after the given number of consecutive failures
+fn backoff(failures: i64) -> i64 {
+    if failures >= LOCKOUT_THRESHOLD {
+        LOCKOUT_DURATION
+    } else if failures > FREE_ATTEMPTS {
+        (BACKOFF_BASE << (failures - FREE_ATTEMPTS - 1)).min(LOCKOUT_DURATION)
+    } else {
+        0
+    }
+}
+
+/// Time left from now until the given unix time, if any
+fn wait_until(now: i64, locked_until: i64) -> Option<Duration> {
+    if locked_until > now {
+        Some(Duration::from_secs((locked_until - now) as u64))
+    } else {
+        None
+    }
+}
+
+/// Attempts record without any failures
+fn empty_attempts(scope: &str) -> AttemptRecord {
+    AttemptRecord {
+        scope: scope.to_string(),
+        failures: 0,
+        last_failure: 0,
+        locked_until: 0,
+    }
+}
+
+/// Format a wait as minutes and seconds for display
+pub fn format_wait(wait: Duration) -> String {
+    let seconds = wait.as_secs();
+    if seconds >= 60 {
+        format!("{}m {}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::database::{DatabaseManager, DatabasePath, MigrationManager};
+
+    fn limiter() -> RateLimiter {
+        let mut database = Database::open(&DatabasePath::Memory).unwrap();
+        database.migrate(false).unwrap();
+        RateLimiter::new(database)
+    }
+
+    /// Wait rounded to whole seconds, a second may tick between two calls
+    fn seconds(wait: Option<Duration>) -> i64 {
+        wait.map(|wait| wait.as_secs() as i64).unwrap_or(0)
+    }
+
+    /// Move the attempts of a scope back in time, as if the given seconds had passed
+    fn rewind(limiter: &mut RateLimiter, scope: &str, elapsed: i64) {
+        let mut attempts = limiter.database.get_attempts(scope).unwrap().unwrap();
+        attempts.last_failure -= elapsed;
+        attempts.locked_until -= elapsed;
+        limiter.database.save_attempts(&attempts).unwrap();
+    }
+
+    #[test]
+    fn free_attempts_are_not_throttled() {
+        let mut limiter = limiter();
+
+        for _ in 0..FREE_ATTEMPTS {
+            assert_eq!(limiter.record_failure("alice").unwrap(), None);
+            assert_eq!(limiter.get_wait("alice").unwrap(), None);
+        }
+
+        let wait = seconds(limiter.record_failure("alice").unwrap());
+        assert!((BACKOFF_BASE - 1..=BACKOFF_BASE).contains(&wait));
+        assert!(limiter.get_wait("ALICE").unwrap().is_some());
+        assert_eq!(limiter.get_wait("bob").unwrap(), None);
+    }
+
+    #[test]
+    fn backoff_doubles_until_lockout() {
+        assert_eq!(backoff(FREE_ATTEMPTS), 0);
+        assert_eq!(backoff(FREE_ATTEMPTS + 1), BACKOFF_BASE);
+        assert_eq!(backoff(FREE_ATTEMPTS + 2), BACKOFF_BASE * 2);
+        assert_eq!(backoff(FREE_ATTEMPTS + 3), BACKOFF_BASE * 4);
+        assert_eq!(backoff(LOCKOUT_THRESHOLD - 1), BACKOFF_BASE << (LOCKOUT_THRESHOLD - FREE_ATTEMPTS - 2));
+        assert_eq!(backoff(LOCKOUT_THRESHOLD), LOCKOUT_DURATION);
+        assert_eq!(backoff(LOCKOUT_THRESHOLD * 10), LOCKOUT_DURATION);
+
+        let mut limiter = limiter();
+        let waits: Vec<i64> = (0..LOCKOUT_THRESHOLD)
+            .map(|_| seconds(limiter.record_failure("alice").unwrap()))
+            .collect();
+        assert!(waits.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(waits[waits.len() - 1] >= LOCKOUT_DURATION - 1);
+    }
+
+    #[test]
+    fn lockout_expires() {
+        let mut limiter = limiter();
+        for _ in 0..LOCKOUT_THRESHOLD {
+            limiter.record_failure("alice").unwrap();
+        }
+        assert!(seconds(limiter.get_wait("alice").unwrap()) >= LOCKOUT_DURATION - 1);
+
+        rewind(&mut limiter, "alice", LOCKOUT_DURATION);
+        assert_eq!(limiter.get_wait("alice").unwrap(), None);
+
+        // Still counted until the failures are old enough to be forgotten
+        assert!(seconds(limiter.record_failure("alice").unwrap()) >= LOCKOUT_DURATION - 1);
+        rewind(&mut limiter, "alice", FAILURE_RESET);
+        assert_eq!(limiter.record_failure("alice").unwrap(), None);
+    }
+
+    #[test]
+    fn success_forgets_failures() {
+        let mut limiter = limiter();
+        for _ in 0..FREE_ATTEMPTS + 2 {
+            limiter.record_failure("Alice").unwrap();
+        }
+        assert!(limiter.get_wait("alice").unwrap().is_some());
+
+        limiter.record_success("alice").unwrap();
+        assert_eq!(limiter.get_wait("Alice").unwrap(), None);
+        assert_eq!(limiter.record_failure("alice").unwrap(), None);
+    }
+
+    #[test]
+    fn failures_across_usernames_throttle_every_login() {
+        let mut limiter = limiter();
+        for attempt in 0..GLOBAL_THRESHOLD - 1 {
+            assert_eq!(limiter.record_failure(format!("user{}", attempt).as_str()).unwrap(), None);
+        }
+        assert_eq!(limiter.get_wait("alice").unwrap(), None);
+
+        assert!(limiter.record_failure("mallory").unwrap().is_some());
+        assert!(seconds(limiter.get_wait("alice").unwrap()) >= GLOBAL_LOCKOUT_DURATION - 1);
+
+        rewind(&mut limiter, GLOBAL_SCOPE, GLOBAL_LOCKOUT_DURATION);
+        assert_eq!(limiter.get_wait("alice").unwrap(), None);
+    }
+}
@@ -0,0 +1,151 @@
+use rand::Rng;
+
+use super::secret::SecretString;
+use super::super::utility::error::AuthError;
+
+/// Recovery codes generated for an account
+const CODE_COUNT: usize = 10;
+
+/// Characters of a recovery code, without the ones easily mistaken for each other
+const ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// Characters of a recovery code, shown as two groups
+const CODE_LENGTH: usize = 10;
+
+/// Traits of an account with recovery codes
+/// - generate_recovery_codes : replace the codes of the account, the new codes are only returned once
+/// - verify_recovery_code : complete a login with a code instead of the second factor
+/// - count_recovery_codes : codes of the account not used yet
+pub trait Recovery {
+    fn generate_recovery_codes(&mut self) -> Result<Vec<SecretString>, AuthError>;
+    fn verify_recovery_code(&mut self, code: &str) -> Result<(), AuthError>;
+    fn count_recovery_codes(&self) -> Result<usize, AuthError>;
+}
+
+/// Generate a set of recovery codes, formatted as `xxxxx-xxxxx`
+pub fn generate_codes() -> Vec<SecretString> {
+    let mut rng = rand::thread_rng();
+
+    (0..CODE_COUNT)
+        .map(|_| {
+            let mut code = SecretString::default();
+            for position in 0..CODE_LENGTH {
+                if position == CODE_LENGTH / 2 {
+                    code.push('-');
+                }
+                code.push(ALPHABET[rng.gen_range(0..ALPHABET.len())] as char);
+            }
+            code
+        })
+        .collect()
+}
+
+/// Code as it is hashed, ignoring case, spaces and the group separator
+pub fn normalize(code: &str) -> SecretString {
+    let mut normalized = SecretString::default();
+    code.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .flat_map(char::to_lowercase)
+        .for_each(|c| normalized.push(c));
+    normalized
+}
+
+/// Check if an entered code is a recovery code rather than a one time code
+pub fn is_recovery_code(code: &str) -> bool {
+    normalize(code).expose().chars().count() == CODE_LENGTH
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use data_encoding::BASE32_NOPAD;
+    use zeroize::Zeroizing;
+
+    use super::*;
+    use super::super::authenticator::{Account, Credential};
+    use super::super::database::{Database, DatabaseManager, DatabasePath, MigrationManager, RecoveryManager};
+    use super::super::totp::{Totp, TwoFactor};
+    use super::super::vault::Vault;
+
+    const PASSWORD: &str = "Correct-horse-battery-staple-42";
+
+    /// Logged in account enrolled in totp, with the database it is stored in
+    fn enrolled() -> (Database, Account) {
+        let mut database = Database::open(&DatabasePath::Memory).unwrap();
+        database.migrate(false).unwrap();
+
+        let mut account = Account::new(database.clone());
+        account.create_account("alice".to_string(), &PASSWORD.into()).unwrap();
+        account.login("alice".to_string(), &PASSWORD.into()).unwrap();
+
+        let enrollment = account.enroll_totp().unwrap();
+        let secret = BASE32_NOPAD.decode(enrollment.secret.expose().as_bytes()).unwrap();
+        let code = Totp::from_secret(Zeroizing::new(secret)).code_at(Utc::now().timestamp() / 30);
+        account.confirm_totp(code.as_str()).unwrap();
+
+        (database, account)
+    }
+
+    /// Account of a new login waiting for its second factor
+    fn awaiting_code(database: &Database) -> Account {
+        let mut account = Account::new(database.clone());
+        assert_eq!(account.login("alice".to_string(), &PASSWORD.into()), Err(AuthError::CodeRequired));
+        account
+    }
+
+    #[test]
+    fn generates_distinct_codes() {
+        let codes = generate_codes();
+        assert_eq!(codes.len(), CODE_COUNT);
+        assert!(codes.iter().all(|code| code.expose().len() == CODE_LENGTH + 1 && is_recovery_code(code.expose())));
+
+        let mut unique: Vec<&str> = codes.iter().map(|code| code.expose()).collect();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), CODE_COUNT);
+
+        assert_eq!(normalize(" ABCDE-fghjk ").expose(), "abcdefghjk");
+        assert!(!is_recovery_code("123456"));
+    }
+
+    #[test]
+    fn stores_codes_hashed() {
+        let (mut database, mut account) = enrolled();
+        let codes = account.generate_recovery_codes().unwrap();
+
+        let stored = database.get_recovery_codes(account.get_account_id().as_str()).unwrap();
+        assert_eq!(stored.len(), CODE_COUNT);
+        for code in &codes {
+            let normalized = normalize(code.expose());
+            assert!(stored.iter().all(|record| !record.password.contains(code.expose())
+                && !record.password.contains(normalized.expose())));
+        }
+    }
+
+    #[test]
+    fn consumes_code_once() {
+        let (database, mut account) = enrolled();
+        let codes = account.generate_recovery_codes().unwrap();
+
+        let mut login = awaiting_code(&database);
+        login.verify_recovery_code(codes[0].expose().to_uppercase().as_str()).unwrap();
+        assert_eq!(login.count_recovery_codes().unwrap(), CODE_COUNT - 1);
+
+        let mut login = awaiting_code(&database);
+        assert_eq!(login.verify_recovery_code(codes[0].expose()), Err(AuthError::InvalidCredentials));
+        login.verify_recovery_code(codes[1].expose()).unwrap();
+        assert_eq!(login.count_recovery_codes().unwrap(), CODE_COUNT - 2);
+    }
+
+    #[test]
+    fn regenerating_replaces_codes() {
+        let (database, mut account) = enrolled();
+        let old_codes = account.generate_recovery_codes().unwrap();
+        let new_codes = account.generate_recovery_codes().unwrap();
+        assert_eq!(account.count_recovery_codes().unwrap(), CODE_COUNT);
+
+        let mut login = awaiting_code(&database);
+        assert_eq!(login.verify_recovery_code(old_codes[0].expose()), Err(AuthError::InvalidCredentials));
+        login.verify_recovery_code(new_codes[0].expose()).unwrap();
+    }
+}
@@ -0,0 +1,72 @@
+use zeroize::{Zeroize, Zeroizing};
+
+/// Capacity reserved for a secret typed one character at a time, enough for
+/// most passwords to never reallocate
+const INITIAL_CAPACITY: usize = 64;
+
+/// Text holding a secret such as a password or recovery code.
+///
+/// The whole buffer is zeroed when the value is dropped or cleared. The type is neither
+/// `Debug`, `Display` nor `Clone`, so a secret cannot be printed or copied by
+/// accident, and the text is only read through `expose`.
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+
+    /// Take ownership of a secret
+    pub fn new(secret: String) -> SecretString {
+        SecretString(Zeroizing::new(secret))
+    }
+
+    /// Read the secret, keep the borrow as short as possible
+    pub fn expose(&self) -> &str {
+        self.0.as_str()
+    }
+
+    /// Add a character, growing into a new buffer so the old one is zeroed rather than
+    /// left behind by a reallocation
+    pub fn push(&mut self, character: char) {
+        if self.0.len() + character.len_utf8() > self.0.capacity() {
+            let capacity = (self.0.capacity() * 2).max(INITIAL_CAPACITY);
+            let mut grown = Zeroizing::new(String::with_capacity(capacity));
+            grown.push_str(self.0.as_str());
+            self.0 = grown;
+        }
+
+        self.0.push(character);
+    }
+
+    /// Remove the last character, its bytes stay in the buffer until it is zeroed
+    pub fn pop(&mut self) {
+        self.0.pop();
+    }
+
+    /// Zero and empty the secret
+    pub fn clear(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Default for SecretString {
+    fn default() -> SecretString {
+        SecretString(Zeroizing::new(String::with_capacity(INITIAL_CAPACITY)))
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> SecretString {
+        SecretString::new(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> SecretString {
+        SecretString::new(secret.to_string())
+    }
+}
+
+impl From<Zeroizing<String>> for SecretString {
+    fn from(secret: Zeroizing<String>) -> SecretString {
+        SecretString(secret)
+    }
+}
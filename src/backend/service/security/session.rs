@@ -0,0 +1,242 @@
+use std::ffi::CStr;
+#[cfg(test)]
+use std::{cell::Cell, rc::Rc};
+
+use chrono::Utc;
+use rand::Rng;
+
+use super::database::{Database, SessionManager, SessionRecord};
+use super::super::utility::logger;
+
+/// Seconds without input after which the screen is locked
+const IDLE_TIMEOUT: i64 = 5 * 60;
+
+/// Seconds a session lasts, after which the password is required to log in again
+const SESSION_LIFETIME: i64 = 12 * 60 * 60;
+
+/// Seconds between writes of the last seen time, input in between is only kept in memory
+const TOUCH_INTERVAL: i64 = 30;
+
+/// State of a session
+/// - Active : in use
+/// - Idle : no input for longer than the idle timeout, the screen must be locked
+/// - Expired : past its lifetime, the account must log in again
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionStatus {
+    Active,
+    Idle,
+    Expired,
+}
+
+/// Source of the current time, in seconds since the epoch
+pub trait Clock {
+    fn now(&self) -> i64;
+}
+
+/// Time of the system
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        Utc::now().timestamp()
+    }
+}
+
+/// Time set by hand, shared by its clones so a test can move it forward
+#[cfg(test)]
+#[derive(Clone)]
+pub struct ManualClock(Rc<Cell<i64>>);
+
+#[cfg(test)]
+impl ManualClock {
+    pub fn new(now: i64) -> ManualClock {
+        ManualClock(Rc::new(Cell::new(now)))
+    }
+
+    pub fn advance(&self, seconds: i64) {
+        self.0.set(self.0.get() + seconds);
+    }
+}
+
+#[cfg(test)]
+impl Clock for ManualClock {
+    fn now(&self) -> i64 {
+        self.0.get()
+    }
+}
+
+/// Traits of a session tracker
+/// - start : create and store a session for an account
+/// - touch : record input, the session is no longer idle
+/// - status : state of the session at the current time
+/// - end : end the session
+pub trait Tracker {
+    fn start(database: Database, account: &str) -> Result<Self, rusqlite::Error> where Self: Sized;
+    fn touch(&mut self) -> Result<(), rusqlite::Error>;
+    fn status(&self) -> SessionStatus;
+    fn end(&mut self) -> Result<(), rusqlite::Error>;
+}
+
+/// Session of a logged in account, stored in the database with the terminal it was started from.
+///
+/// Input is tracked in memory and written back every TOUCH_INTERVAL seconds, so
+/// the last seen time stays close without a write on every key press.
+pub struct Session {
+    database: Database,
+    record: SessionRecord,
+    last_input: i64,
+    clock: Box<dyn Clock>,
+}
+
+impl Session {
+
+    /// Start a session for the account on the current terminal, timed by the given clock
+    pub fn start_with_clock(mut database: Database, account: &str, clock: Box<dyn Clock>) -> Result<Session, rusqlite::Error> {
+        let now = clock.now();
+
+        let random_bytes = rand::thread_rng().gen::<[u8; 16]>();
+        let record = SessionRecord {
+            id: uuid::Builder::from_random_bytes(random_bytes).into_uuid().to_string(),
+            account: account.to_string(),
+            tty: current_tty(),
+            created_at: now,
+            last_seen: now,
+            expires_at: now + SESSION_LIFETIME,
+        };
+        database.create_session(&record)?;
+        logger::info("session", "session started",
+            &[("id", &record.id), ("account", &record.account), ("tty", &record.tty)]);
+
+        Ok(Session { database, record, last_input: now, clock })
+    }
+}
+
+impl Tracker for Session {
+
+    /// Start a session for the account on the current terminal
+    fn start(database: Database, account: &str) -> Result<Session, rusqlite::Error> {
+        Session::start_with_clock(database, account, Box::new(SystemClock))
+    }
+
+    /// Record input on the session
+    fn touch(&mut self) -> Result<(), rusqlite::Error> {
+        let now = self.clock.now();
+        self.last_input = now;
+
+        if now - self.record.last_seen >= TOUCH_INTERVAL {
+            self.database.touch_session(self.record.id.as_str(), now)?;
+            self.record.last_seen = now;
+            logger::trace("session", "session touched", &[("id", &self.record.id)]);
+        }
+
+        Ok(())
+    }
+
+    /// Check if the session expired or has been idle for too long
+    fn status(&self) -> SessionStatus {
+        let now = self.clock.now();
+
+        if now >= self.record.expires_at {
+            SessionStatus::Expired
+        } else if now - self.last_input >= IDLE_TIMEOUT {
+            SessionStatus::Idle
+        } else {
+            SessionStatus::Active
+        }
+    }
+
+    /// End the session
+    fn end(&mut self) -> Result<(), rusqlite::Error> {
+        logger::info("session", "session ended", &[("id", &self.record.id), ("account", &self.record.account)]);
+        self.database.end_session(self.record.id.as_str(), self.clock.now())
+    }
+}
+
+/// Terminal attached to standard input, if any
+fn current_tty() -> Option<String> {
+    // ttyname returns a pointer to a static buffer, copied before any other call can reuse it
+    unsafe {
+        let name = libc::ttyname(libc::STDIN_FILENO);
+        match name.is_null() {
+            true => None,
+            false => Some(CStr::from_ptr(name).to_string_lossy().into_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::database::{AccountManager, DatabaseManager, DatabasePath, MigrationManager};
+
+    const NOW: i64 = 1_700_000_000;
+
+    fn session() -> (Database, ManualClock, Session) {
+        let mut database = Database::open(&DatabasePath::Memory).unwrap();
+        database.migrate(false).unwrap();
+        database.create_account("alice", "hash", [7; 16], "pepper").unwrap();
+        let account = database.get_account("alice").unwrap().id;
+
+        let clock = ManualClock::new(NOW);
+        let session = Session::start_with_clock(database.clone(), account.as_str(), Box::new(clock.clone())).unwrap();
+        (database, clock, session)
+    }
+
+    fn stored_times(database: &Database, session: &Session) -> (i64, Option<i64>) {
+        database.connection()
+            .query_row("SELECT `last_seen`, `ended_at` FROM `sessions` WHERE `id` = ?1", [&session.record.id],
+                |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+    }
+
+    #[test]
+    fn idles_without_input() {
+        let (_, clock, mut session) = session();
+        assert_eq!(session.status(), SessionStatus::Active);
+
+        clock.advance(IDLE_TIMEOUT - 1);
+        assert_eq!(session.status(), SessionStatus::Active);
+        session.touch().unwrap();
+
+        clock.advance(IDLE_TIMEOUT - 1);
+        assert_eq!(session.status(), SessionStatus::Active);
+        clock.advance(1);
+        assert_eq!(session.status(), SessionStatus::Idle);
+
+        session.touch().unwrap();
+        assert_eq!(session.status(), SessionStatus::Active);
+    }
+
+    #[test]
+    fn expires_whatever_the_input() {
+        let (database, clock, mut session) = session();
+
+        while clock.now() < NOW + SESSION_LIFETIME - 60 {
+            clock.advance(60);
+            session.touch().unwrap();
+            assert_eq!(session.status(), SessionStatus::Active);
+        }
+        assert_eq!(stored_times(&database, &session), (NOW + SESSION_LIFETIME - 60, None));
+
+        clock.advance(60);
+        assert_eq!(session.status(), SessionStatus::Expired);
+        session.touch().unwrap();
+        assert_eq!(session.status(), SessionStatus::Expired);
+
+        session.end().unwrap();
+        assert_eq!(stored_times(&database, &session), (NOW + SESSION_LIFETIME, Some(NOW + SESSION_LIFETIME)));
+    }
+
+    #[test]
+    fn writes_input_every_interval() {
+        let (database, clock, mut session) = session();
+
+        clock.advance(TOUCH_INTERVAL - 1);
+        session.touch().unwrap();
+        assert_eq!(stored_times(&database, &session).0, NOW);
+
+        clock.advance(1);
+        session.touch().unwrap();
+        assert_eq!(stored_times(&database, &session).0, NOW + TOUCH_INTERVAL);
+    }
+}
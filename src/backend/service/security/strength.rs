@@ -0,0 +1,667 @@
+use chrono::{Datelike, Utc};
+
+use super::policy::PasswordRules;
+
+/// Guesses per second of an attacker holding the database, against a slow hash such as argon2
+const OFFLINE_GUESSES_PER_SECOND: f64 = 1e4;
+
+/// Least guesses of a pattern found within a longer password, a single character or more
+const MIN_SUBMATCH_GUESSES_SINGLE: f64 = 10.0;
+const MIN_SUBMATCH_GUESSES_MULTI: f64 = 50.0;
+
+/// Guesses added for each pattern after the first, the attacker does not know how many there are
+const MIN_GUESSES_PER_EXTRA_PATTERN: f64 = 1e4;
+
+/// Guesses per character of a part of the password without any pattern
+const BRUTEFORCE_CARDINALITY: f64 = 10.0;
+
+/// Characters analysed, the rest of a longer password is counted as bruteforce
+const MAX_ANALYSED_LENGTH: usize = 128;
+
+/// Upper bound of the guesses of a score, 0 to 3, any more guesses score 4
+const SCORE_THRESHOLDS: [f64; 4] = [1e3, 1e6, 1e8, 1e10];
+
+/// Name of each score
+const SCORE_LABELS: [&str; 5] = ["very weak", "weak", "fair", "strong", "very strong"];
+
+/// Rows of a qwerty keyboard, unshifted then shifted, and how far each row is shifted right in half keys
+const KEYBOARD_ROWS: [&str; 4] = ["`1234567890-=", "qwertyuiop[]\\", "asdfghjkl;'", "zxcvbnm,./"];
+const SHIFTED_ROWS: [&str; 4] = ["~!@#$%^&*()_+", "QWERTYUIOP{}|", "ASDFGHJKL:\"", "ZXCVBNM<>?"];
+const ROW_OFFSETS: [i32; 4] = [0, 3, 4, 5];
+
+/// Keys of the keyboard, and their average number of neighbours
+const KEYBOARD_KEYS: f64 = 47.0;
+const KEYBOARD_DEGREE: f64 = 4.6;
+
+/// Substitutions undone before looking a word up in the blocklists
+const L33T: [(char, char); 15] = [
+    ('4', 'a'), ('@', 'a'), ('8', 'b'), ('(', 'c'), ('3', 'e'), ('6', 'g'), ('1', 'i'), ('!', 'i'),
+    ('|', 'l'), ('0', 'o'), ('$', 's'), ('5', 's'), ('7', 't'), ('+', 't'), ('2', 'z'),
+];
+
+/// Pattern found in a password
+/// - Blocklist : a password of the blocklists, with its rank
+/// - Breached : the whole password is in the breach index
+/// - Context : the username, or a context word of the policy
+/// - Repeat : a character or a block of characters repeated
+/// - Sequence : characters following each other, such as abcd or 9753
+/// - Keyboard : keys next to each other, such as qwerty or zxcvfr
+/// - Year : a year between 1900 and 2099
+/// - Bruteforce : anything else
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Pattern {
+    Blocklist(usize),
+    Breached,
+    Context,
+    Repeat { block: bool },
+    Sequence,
+    Keyboard { turns: usize },
+    Year,
+    Bruteforce,
+}
+
+/// Pattern found between two characters of the password, both included
+/// - guesses : guesses needed to find this part knowing the pattern
+/// - uppercase, reversed and l33t : variations of a blocklist or context word
+#[derive(Clone, Copy, Debug)]
+struct Match {
+    start: usize,
+    end: usize,
+    guesses: f64,
+    pattern: Pattern,
+    uppercase: bool,
+    reversed: bool,
+    l33t: bool,
+}
+
+impl Match {
+
+    fn new(start: usize, end: usize, guesses: f64, pattern: Pattern) -> Match {
+        Match { start, end, guesses, pattern, uppercase: false, reversed: false, l33t: false }
+    }
+
+    fn len(&self) -> usize {
+        self.end - self.start + 1
+    }
+}
+
+/// Estimated strength of a password, in the way of zxcvbn: the password is split into the
+/// patterns an attacker would try first, and the guesses to find each of them are multiplied
+/// - guesses : guesses needed to find the password
+/// - score : 0 to 4, from guesses
+/// - crack_time : time to find the password offline, against the slow hash of the database
+/// - warning : the main weakness, if any
+/// - suggestions : how to make the password stronger
+#[derive(Clone, Debug)]
+pub struct Estimate {
+    pub guesses: f64,
+    pub score: usize,
+    pub crack_time: String,
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>,
+}
+
+impl Estimate {
+
+    /// Name of the score
+    pub fn label(&self) -> &'static str {
+        SCORE_LABELS[self.score]
+    }
+
+    /// Score as a ratio, for gauges, an empty password has none
+    pub fn ratio(&self) -> f64 {
+        match self.guesses > 1.0 {
+            true => (self.score + 1) as f64 / SCORE_LABELS.len() as f64,
+            false => 0.0,
+        }
+    }
+}
+
+/// Estimate the strength of a password
+/// - rules : rules of the role of the account, their blocklists and context words are tried first
+/// - username : refused by the policy too, and the first word an attacker would try
+pub fn estimate(rules: &PasswordRules, username: &str, password: &str) -> Estimate {
+    let characters: Vec<char> = password.chars().collect();
+    let context = rules.context(username);
+
+    // Characters past the analysed ones cannot be guessed with patterns
+    let analysed = &characters[..characters.len().min(MAX_ANALYSED_LENGTH)];
+    let (mut guesses, mut sequence) = most_guessable(analysed, rules, &context);
+
+    // A breached password is in every corpus an attacker tries, at worst the last of the index
+    if let Some(count) = rules.breached_count().filter(|_| !password.is_empty() && rules.is_breached(password)) {
+        if (count as f64) < guesses {
+            guesses = count as f64;
+            sequence = vec![Match::new(0, analysed.len() - 1, guesses, Pattern::Breached)];
+        }
+    }
+
+    let guesses = (guesses * BRUTEFORCE_CARDINALITY.powi((characters.len() - analysed.len()) as i32)).min(f64::MAX);
+
+    let score = SCORE_THRESHOLDS.iter().take_while(|threshold| guesses >= **threshold + 5.0).count();
+    let (warning, suggestions) = feedback(score, &sequence, analysed);
+
+    Estimate {
+        guesses,
+        score,
+        crack_time: display_time(guesses / OFFLINE_GUESSES_PER_SECOND),
+        warning,
+        suggestions,
+    }
+}
+
+/// Sequence of patterns covering the password with the least guesses. The guesses of a sequence
+/// are the factorial of its length times the product of the guesses of its patterns, as the
+/// attacker does not know in which order they come
+fn most_guessable(characters: &[char], rules: &PasswordRules, context: &[String]) -> (f64, Vec<Match>) {
+    let length = characters.len();
+    if length == 0 {
+        return (1.0, Vec::new());
+    }
+
+    let mut matches = find_matches(characters, rules, context);
+    for start in 0..length {
+        for end in start..length {
+            let guesses = BRUTEFORCE_CARDINALITY.powi((end - start + 1) as i32);
+            matches.push(Match::new(start, end, guesses, Pattern::Bruteforce));
+        }
+    }
+    matches.sort_by_key(|found| found.end);
+
+    // best[end][count] : least product of guesses of `count` patterns covering up to `end`,
+    // with the last of those patterns
+    let mut best: Vec<Vec<Option<(f64, usize)>>> = vec![vec![None; length + 1]; length];
+    for (index, found) in matches.iter().enumerate() {
+        let guesses = match found.len() < length {
+            true if found.len() == 1 => found.guesses.max(MIN_SUBMATCH_GUESSES_SINGLE),
+            true => found.guesses.max(MIN_SUBMATCH_GUESSES_MULTI),
+            false => found.guesses,
+        };
+
+        let candidates: Vec<(usize, f64)> = match found.start {
+            0 => vec![(1, guesses)],
+            start => best[start - 1].iter()
+                .enumerate()
+                .filter_map(|(count, entry)| entry.map(|(product, _)| (count + 1, product * guesses)))
+                .collect(),
+        };
+
+        for (count, product) in candidates {
+            match best[found.end][count] {
+                Some((least, _)) if least <= product => {},
+                _ => best[found.end][count] = Some((product, index)),
+            }
+        }
+    }
+
+    // Pick the count of patterns with the least guesses overall
+    let (count, guesses) = best[length - 1].iter()
+        .enumerate()
+        .filter_map(|(count, entry)| entry.map(|(product, _)| {
+            (count, factorial(count) * product + MIN_GUESSES_PER_EXTRA_PATTERN.powi(count as i32 - 1))
+        }))
+        .fold((1, f64::MAX), |least, candidate| match candidate.1 < least.1 {
+            true => candidate,
+            false => least,
+        });
+
+    let mut sequence = Vec::new();
+    let mut end = length - 1;
+    for count in (1..=count).rev() {
+        let (_, index) = best[end][count].expect("pattern of the sequence");
+        sequence.push(matches[index]);
+        match matches[index].start {
+            0 => break,
+            start => end = start - 1,
+        }
+    }
+    sequence.reverse();
+
+    (guesses, sequence)
+}
+
+/// Every pattern found in the password, bruteforce aside
+fn find_matches(characters: &[char], rules: &PasswordRules, context: &[String]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    matches.extend(word_matches(characters, rules, context));
+    matches.extend(repeat_matches(characters, rules, context));
+    matches.extend(sequence_matches(characters));
+    matches.extend(keyboard_matches(characters));
+    matches.extend(year_matches(characters));
+    matches
+}
+
+/// Words of the blocklists and of the context, as typed, reversed or with l33t substitutions
+fn word_matches(characters: &[char], rules: &PasswordRules, context: &[String]) -> Vec<Match> {
+    let lowercase: Vec<char> = characters.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let mut matches = Vec::new();
+
+    let lookup = |word: &str| -> Option<(f64, Pattern)> {
+        match context.iter().any(|context| context == word) {
+            true => Some((1.0, Pattern::Context)),
+            false => rules.rank(word).map(|rank| (rank as f64, Pattern::Blocklist(rank))),
+        }
+    };
+
+    for start in 0..lowercase.len() {
+        for end in start..lowercase.len() {
+            let word: String = lowercase[start..=end].iter().collect();
+            let variations = uppercase_variations(&characters[start..=end]);
+            let uppercase = variations > 1.0;
+
+            if let Some((rank, pattern)) = lookup(word.as_str()) {
+                let mut found = Match::new(start, end, rank * variations, pattern);
+                found.uppercase = uppercase;
+                matches.push(found);
+            }
+
+            let reversed: String = word.chars().rev().collect();
+            if reversed != word {
+                if let Some((rank, pattern)) = lookup(reversed.as_str()) {
+                    let mut found = Match::new(start, end, rank * variations * 2.0, pattern);
+                    found.uppercase = uppercase;
+                    found.reversed = true;
+                    matches.push(found);
+                }
+            }
+
+            let substitutions = word.chars().filter(|c| L33T.iter().any(|(l33t, _)| l33t == c)).count();
+            if substitutions > 0 {
+                let plain: String = word.chars()
+                    .map(|c| L33T.iter().find(|(l33t, _)| *l33t == c).map(|(_, letter)| *letter).unwrap_or(c))
+                    .collect();
+                if let Some((rank, pattern)) = lookup(plain.as_str()) {
+                    let mut found = Match::new(start, end, rank * variations * 2f64.powi(substitutions as i32), pattern);
+                    found.uppercase = uppercase;
+                    found.l33t = true;
+                    matches.push(found);
+                }
+            }
+        }
+    }
+
+    matches
+}
+
+/// A character or a block of characters repeated, such as aaa or abcabc
+/// - the longest repeat is taken at each position and the search goes on after it
+fn repeat_matches(characters: &[char], rules: &PasswordRules, context: &[String]) -> Vec<Match> {
+    let mut matches = Vec::new();
+
+    let mut start = 0;
+    while start + 2 < characters.len() {
+        // (block, repeats) covering the most characters, single characters only count from three in a row
+        let longest = (1..=(characters.len() - start) / 2)
+            .map(|block| {
+                let base = &characters[start..start + block];
+                (block, characters[start..].chunks_exact(block).take_while(|chunk| *chunk == base).count())
+            })
+            .filter(|(block, repeats)| *repeats >= 3 || (*block > 1 && *repeats == 2))
+            .fold(None, |longest: Option<(usize, usize)>, (block, repeats)| match longest {
+                Some((longest_block, longest_repeats)) if longest_block * longest_repeats >= block * repeats => longest,
+                _ => Some((block, repeats)),
+            });
+
+        let (block, repeats) = match longest {
+            Some(longest) => longest,
+            None => {
+                start += 1;
+                continue;
+            },
+        };
+
+        let base = &characters[start..start + block];
+        let base_guesses = match block {
+            1 => cardinality(base[0]),
+            _ => most_guessable(base, rules, context).0,
+        };
+        matches.push(Match::new(start, start + block * repeats - 1, base_guesses * repeats as f64,
+            Pattern::Repeat { block: block > 1 }));
+
+        start += block * repeats;
+    }
+
+    matches
+}
+
+/// Characters following each other by a constant step of up to five, such as abc, 2468 or zyx
+fn sequence_matches(characters: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+    let step = |index: usize| characters[index + 1] as i64 - characters[index] as i64;
+
+    let mut start = 0;
+    while start + 2 < characters.len() {
+        let delta = step(start);
+        let mut end = start + 1;
+        while end + 1 < characters.len() && step(end) == delta {
+            end += 1;
+        }
+
+        if delta != 0 && delta.abs() <= 5 && end - start >= 2 {
+            let first = characters[start];
+            let base = match first {
+                'a' | 'A' | 'z' | 'Z' | '0' | '1' | '9' => 4.0,
+                _ if first.is_ascii_digit() => 10.0,
+                _ => 26.0,
+            };
+            let direction = if delta < 0 { 2.0 } else { 1.0 };
+            matches.push(Match::new(start, end, base * direction * (end - start + 1) as f64, Pattern::Sequence));
+        }
+
+        start = end;
+    }
+
+    matches
+}
+
+/// Keys next to each other on a qwerty keyboard, such as qwerty or 1qaz
+/// - each change of direction is a turn, more turns need more guesses
+fn keyboard_matches(characters: &[char]) -> Vec<Match> {
+    let mut matches = Vec::new();
+
+    let mut start = 0;
+    while start + 2 < characters.len() {
+        let mut end = start;
+        let mut turns = 0;
+        let mut direction = None;
+        while end + 1 < characters.len() {
+            let next = match (key_position(characters[end]), key_position(characters[end + 1])) {
+                (Some(from), Some(to)) => key_direction(from, to),
+                _ => None,
+            };
+            match next {
+                Some(next) => {
+                    if direction != Some(next) {
+                        turns += 1;
+                        direction = Some(next);
+                    }
+                    end += 1;
+                },
+                None => break,
+            }
+        }
+
+        if end - start >= 2 {
+            let keys = &characters[start..=end];
+            let shifted = keys.iter().filter(|c| key_position(**c).map(|(_, _, shifted)| shifted).unwrap_or(false)).count();
+            let guesses = keyboard_guesses(keys.len(), turns) * shift_variations(shifted, keys.len() - shifted);
+            matches.push(Match::new(start, end, guesses, Pattern::Keyboard { turns }));
+        }
+
+        start = end.max(start + 1);
+    }
+
+    matches
+}
+
+/// Years between 1900 and 2099, recent years being tried first
+fn year_matches(characters: &[char]) -> Vec<Match> {
+    let current = Utc::now().year() as f64;
+
+    characters.windows(4)
+        .enumerate()
+        .filter_map(|(start, digits)| {
+            let year: String = digits.iter().collect();
+            match year.parse::<u32>() {
+                Ok(year) if (1900..=2099).contains(&year) && digits.iter().all(char::is_ascii_digit) =>
+                    Some(Match::new(start, start + 3, (year as f64 - current).abs().max(20.0), Pattern::Year)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Main weakness of the password and how to make it stronger, none once the password is strong
+fn feedback(score: usize, sequence: &[Match], characters: &[char]) -> (Option<String>, Vec<String>) {
+    if characters.is_empty() {
+        return (None, vec![
+            "Use a few words, avoid common phrases".to_string(),
+            "No need for symbols, digits, or uppercase letters".to_string(),
+        ]);
+    }
+
+    if score > 2 {
+        return (None, Vec::new());
+    }
+
+    let mut suggestions = vec!["Add another word or two, uncommon words are better".to_string()];
+    let longest = match sequence.iter().max_by_key(|found| found.len()) {
+        Some(longest) => *longest,
+        None => return (None, suggestions),
+    };
+
+    let warning = match longest.pattern {
+        Pattern::Blocklist(rank) => {
+            let word = &characters[longest.start..=longest.end];
+            if longest.uppercase && word.iter().all(|c| !c.is_lowercase()) {
+                suggestions.push("All-uppercase is almost as easy to guess as all-lowercase".to_string());
+            } else if longest.uppercase {
+                suggestions.push("Capitalization doesn't help very much".to_string());
+            }
+            if longest.reversed {
+                suggestions.push("Reversed words aren't much harder to guess".to_string());
+            }
+            if longest.l33t {
+                suggestions.push("Predictable substitutions like '@' instead of 'a' don't help very much".to_string());
+            }
+
+            match sequence.len() == 1 {
+                true if rank <= 10 => Some("This is a top-10 common password".to_string()),
+                true if rank <= 100 => Some("This is a top-100 common password".to_string()),
+                true => Some("This is a very common password".to_string()),
+                false => Some("This is similar to a commonly used password".to_string()),
+            }
+        },
+        Pattern::Breached => Some("This password was exposed in a data breach".to_string()),
+        Pattern::Context => Some("Passwords containing your username or the application name are easy to guess".to_string()),
+        Pattern::Repeat { block } => {
+            suggestions.push("Avoid repeated words and characters".to_string());
+            match block {
+                true => Some("Repeats like \"abcabc\" are only slightly harder to guess than \"abc\"".to_string()),
+                false => Some("Repeats like \"aaa\" are easy to guess".to_string()),
+            }
+        },
+        Pattern::Sequence => {
+            suggestions.push("Avoid sequences".to_string());
+            Some("Sequences like abc or 6543 are easy to guess".to_string())
+        },
+        Pattern::Keyboard { turns } => {
+            suggestions.push("Use a longer keyboard pattern with more turns".to_string());
+            match turns {
+                1 => Some("Straight rows of keys are easy to guess".to_string()),
+                _ => Some("Short keyboard patterns are easy to guess".to_string()),
+            }
+        },
+        Pattern::Year => {
+            suggestions.push("Avoid recent years, and years that are associated with you".to_string());
+            Some("Recent years are easy to guess".to_string())
+        },
+        Pattern::Bruteforce => None,
+    };
+
+    (warning, suggestions)
+}
+
+/// Guesses needed to try the uppercase variations of a word, only lowercase words have none
+/// - capitalised words, words ending with an uppercase letter and uppercase words are tried first
+fn uppercase_variations(word: &[char]) -> f64 {
+    let upper = word.iter().filter(|c| c.is_uppercase()).count();
+    let lower = word.iter().filter(|c| c.is_lowercase()).count();
+
+    if upper == 0 {
+        return 1.0;
+    }
+    if lower == 0 || (upper == 1 && (word[0].is_uppercase() || word[word.len() - 1].is_uppercase())) {
+        return 2.0;
+    }
+
+    (1..=upper.min(lower)).map(|count| binomial(upper + lower, count)).sum()
+}
+
+/// Guesses needed to try the shifted variations of a keyboard pattern
+fn shift_variations(shifted: usize, unshifted: usize) -> f64 {
+    match (shifted, unshifted) {
+        (0, _) => 1.0,
+        (_, 0) => 2.0,
+        _ => (1..=shifted.min(unshifted)).map(|count| binomial(shifted + unshifted, count)).sum(),
+    }
+}
+
+/// Guesses of a keyboard pattern of a length and a number of turns, starting from any key
+fn keyboard_guesses(length: usize, turns: usize) -> f64 {
+    let mut guesses = 0.0;
+    for length in 2..=length {
+        for turn in 1..=turns.min(length - 1) {
+            guesses += binomial(length - 1, turn - 1) * KEYBOARD_KEYS * KEYBOARD_DEGREE.powi(turn as i32);
+        }
+    }
+    guesses
+}
+
+/// Position of a key in half key widths, with whether shift is held to type it
+fn key_position(key: char) -> Option<(i32, i32, bool)> {
+    for (row, (keys, shifted)) in KEYBOARD_ROWS.iter().zip(SHIFTED_ROWS.iter()).enumerate() {
+        if let Some(column) = keys.chars().position(|c| c == key) {
+            return Some((row as i32, 2 * column as i32 + ROW_OFFSETS[row], false));
+        }
+        if let Some(column) = shifted.chars().position(|c| c == key) {
+            return Some((row as i32, 2 * column as i32 + ROW_OFFSETS[row], true));
+        }
+    }
+    None
+}
+
+/// Direction from a key to one of its neighbours, None if the keys are not next to each other
+fn key_direction(from: (i32, i32, bool), to: (i32, i32, bool)) -> Option<(i32, i32)> {
+    match (to.0 - from.0, to.1 - from.1) {
+        (0, 2) | (0, -2) | (1, 1) | (1, -1) | (-1, 1) | (-1, -1) => Some((to.0 - from.0, to.1 - from.1)),
+        _ => None,
+    }
+}
+
+/// Characters of the class of a character
+fn cardinality(character: char) -> f64 {
+    match character {
+        '0'..='9' => 10.0,
+        'a'..='z' | 'A'..='Z' => 26.0,
+        _ => 33.0,
+    }
+}
+
+fn binomial(n: usize, k: usize) -> f64 {
+    (0..k.min(n)).fold(1.0, |result, index| result * (n - index) as f64 / (index + 1) as f64)
+}
+
+fn factorial(n: usize) -> f64 {
+    (1..=n).fold(1.0, |result, index| result * index as f64)
+}
+
+/// Time in plain words, rounded to its largest unit
+fn display_time(seconds: f64) -> String {
+    const MINUTE: f64 = 60.0;
+    const HOUR: f64 = MINUTE * 60.0;
+    const DAY: f64 = HOUR * 24.0;
+    const MONTH: f64 = DAY * 31.0;
+    const YEAR: f64 = DAY * 365.0;
+    const CENTURY: f64 = YEAR * 100.0;
+
+    let (amount, unit) = match seconds {
+        seconds if seconds < 1.0 => return "less than a second".to_string(),
+        seconds if seconds < MINUTE => (seconds, "second"),
+        seconds if seconds < HOUR => (seconds / MINUTE, "minute"),
+        seconds if seconds < DAY => (seconds / HOUR, "hour"),
+        seconds if seconds < MONTH => (seconds / DAY, "day"),
+        seconds if seconds < YEAR => (seconds / MONTH, "month"),
+        seconds if seconds < CENTURY => (seconds / YEAR, "year"),
+        _ => return "centuries".to_string(),
+    };
+
+    match amount.round() as u64 {
+        1 => format!("1 {}", unit),
+        amount => format!("{} {}s", amount, unit),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::policy::{Policy, DEFAULT_ROLE};
+
+    /// Default rules with the bundled blocklists, without a breach index
+    fn policy() -> Policy {
+        Policy::parse("breach_index =\n", "policy.conf").unwrap()
+    }
+
+    fn score(password: &str) -> usize {
+        estimate(policy().rules(DEFAULT_ROLE), "alice", password).score
+    }
+
+    #[test]
+    fn scores_weak_passwords() {
+        for password in ["password", "123456", "qwerty", "P@ssw0rd", "drowssap", "aaaaaaaa", "abcdefgh", "qwertyuiop", "1990"] {
+            assert_eq!(score(password), 0, "{}", password);
+        }
+        assert_eq!(score("alice1990"), 1);
+        assert_eq!(score("termauth2024"), 1);
+    }
+
+    #[test]
+    fn scores_strong_passwords() {
+        for password in ["correct horse battery staple", "xK9#mQ2$vL7!pR4&", "Tr0ub4dor&3"] {
+            assert_eq!(score(password), 4, "{}", password);
+        }
+    }
+
+    #[test]
+    fn explains_weak_passwords() {
+        let policy = policy();
+        let rules = policy.rules(DEFAULT_ROLE);
+
+        let estimate_of = |password| estimate(rules, "alice", password);
+        assert_eq!(estimate_of("password").warning.as_deref(), Some("This is a top-10 common password"));
+        assert!(estimate_of("P@ssw0rd").suggestions.iter().any(|suggestion| suggestion.contains("substitutions")));
+        assert!(estimate_of("drowssap").suggestions.iter().any(|suggestion| suggestion.starts_with("Reversed")));
+        assert!(estimate_of("alice1990").warning.unwrap().contains("username"));
+        assert!(estimate_of("correct horse battery staple").warning.is_none());
+    }
+
+    #[test]
+    fn scores_single_character() {
+        let policy = policy();
+        for password in ["a", "Z", "7", "é"] {
+            let estimate = estimate(policy.rules(DEFAULT_ROLE), "alice", password);
+            assert_eq!(estimate.score, 0, "{}", password);
+            assert!(estimate.ratio() > 0.0);
+        }
+
+        let empty = estimate(policy.rules(DEFAULT_ROLE), "alice", "");
+        assert_eq!((empty.score, empty.guesses, empty.ratio()), (0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn scores_non_ascii_passwords() {
+        assert_eq!(score("пароль"), 1);
+        assert_eq!(score("пароль123"), 3);
+        assert_eq!(score("密码密码密码"), 0);
+        assert_eq!(score("Ωμέγα-ζ7-Κρύπτο"), 4);
+    }
+
+    #[test]
+    fn counts_characters_past_the_analysed_ones_as_bruteforce() {
+        let policy = policy();
+        let rules = policy.rules(DEFAULT_ROLE);
+        let password: String = "Tr0ub4dor&3-".repeat(11);
+        let analysed: String = password.chars().take(MAX_ANALYSED_LENGTH).collect();
+        let longer: String = password.chars().take(MAX_ANALYSED_LENGTH + 1).collect();
+
+        let estimate_analysed = estimate(rules, "alice", analysed.as_str());
+        assert_eq!(estimate_analysed.score, 4);
+        let estimate_longer = estimate(rules, "alice", longer.as_str());
+        assert_eq!(estimate_longer.guesses, estimate_analysed.guesses * BRUTEFORCE_CARDINALITY);
+
+        // The same character repeated is found as a single repeat, a character and a count to guess
+        let repeated = estimate(rules, "alice", "a".repeat(MAX_ANALYSED_LENGTH).as_str());
+        assert_eq!(repeated.score, 1);
+        assert!(repeated.guesses < 1e4);
+    }
+}
@@ -0,0 +1,186 @@
+use chrono::Utc;
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use qrcode::{QrCode, render::unicode::Dense1x2};
+use rand::Rng;
+use sha1::Sha1;
+use zeroize::Zeroizing;
+
+use super::secret::SecretString;
+use super::super::utility::error::AuthError;
+
+/// Issuer shown by authenticator apps
+const ISSUER: &str = "termauth";
+
+/// Length of a generated secret in bytes, the size of an HMAC-SHA1 key
+const SECRET_LENGTH: usize = 20;
+
+/// Seconds each code is valid for
+const PERIOD: i64 = 30;
+
+/// Digits of a code
+const DIGITS: u32 = 6;
+
+/// Steps before and after the current one accepted to allow for clock skew
+const SKEW: i64 = 1;
+
+/// Traits of an account with a second factor
+/// - has_totp : check if the account is enrolled
+/// - enroll_totp : generate a new secret, kept pending until confirmed
+/// - confirm_totp : confirm the pending secret with a code then store it
+/// - verify_totp : complete a login with a code
+/// - cancel_totp : abandon a pending enrollment or login
+pub trait TwoFactor {
+    fn has_totp(&self) -> Result<bool, AuthError>;
+    fn enroll_totp(&mut self) -> Result<TotpEnrollment, AuthError>;
+    fn confirm_totp(&mut self, code: &str) -> Result<(), AuthError>;
+    fn verify_totp(&mut self, code: &str) -> Result<(), AuthError>;
+    fn cancel_totp(&mut self);
+}
+
+/// Secret of a pending enrollment, as shown to the user
+/// - secret : base32 secret to type into an authenticator app
+/// - qr_code : provisioning uri rendered with unicode blocks
+pub struct TotpEnrollment {
+    pub secret: SecretString,
+    pub qr_code: String,
+}
+
+/// Time-based one time password generator (RFC 6238) using HMAC-SHA1,
+/// 6 digits and 30 second steps, the defaults every authenticator app supports.
+pub struct Totp {
+    secret: Zeroizing<Vec<u8>>,
+}
+
+impl Totp {
+
+    /// Generate a new random secret
+    pub fn generate() -> Totp {
+        let mut secret = Zeroizing::new(vec![0u8; SECRET_LENGTH]);
+        rand::thread_rng().fill(&mut secret[..]);
+        Totp { secret }
+    }
+
+    /// Use an existing secret
+    pub fn from_secret(secret: Zeroizing<Vec<u8>>) -> Totp {
+        Totp { secret }
+    }
+
+    /// Raw secret, to be sealed before it is stored
+    pub fn secret(&self) -> &[u8] {
+        &self.secret
+    }
+
+    /// Enrollment details of the secret for the given username
+    pub fn enrollment(&self, username: &str) -> TotpEnrollment {
+        let secret = SecretString::new(BASE32_NOPAD.encode(&self.secret));
+        let uri = SecretString::new(format!("otpauth://totp/{}:{}?secret={}&issuer={}", ISSUER, username, secret.expose(), ISSUER));
+
+        // Light modules are drawn with blocks so the code reads on a dark terminal
+        let qr_code = QrCode::new(uri.expose().as_bytes())
+            .map(|code| code
+                .render::<Dense1x2>()
+                .dark_color(Dense1x2::Light)
+                .light_color(Dense1x2::Dark)
+                .build())
+            .unwrap_or_default();
+
+        TotpEnrollment { secret, qr_code }
+    }
+
+    /// Code of the given time step (RFC 4226 dynamic truncation)
+    pub fn code_at(&self, step: i64) -> String {
+        let mut mac = Hmac::<Sha1>::new_from_slice(&self.secret).expect("hmac accepts any key length");
+        mac.update(&step.to_be_bytes());
+        let digest = mac.finalize().into_bytes();
+
+        let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+        let binary = u32::from_be_bytes([digest[offset] & 0x7f, digest[offset + 1], digest[offset + 2], digest[offset + 3]]);
+        format!("{:0width$}", binary % 10u32.pow(DIGITS), width = DIGITS as usize)
+    }
+
+    /// Verify a code against the steps around the current time, returns the matching step.
+    /// - last_step : last step a code was accepted for, it and earlier steps are refused so codes cannot be replayed
+    pub fn verify(&self, code: &str, last_step: i64) -> Option<i64> {
+        self.verify_at(code, last_step, current_step())
+    }
+
+    /// Verify a code against the steps around the given time step
+    fn verify_at(&self, code: &str, last_step: i64, now: i64) -> Option<i64> {
+        let code = code.trim();
+        if code.len() != DIGITS as usize || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+
+        (now - SKEW..=now + SKEW)
+            .filter(|step| *step > last_step)
+            .find(|step| constant_time_eq(self.code_at(*step).as_bytes(), code.as_bytes()))
+    }
+}
+
+/// Time step of the current time
+fn current_step() -> i64 {
+    Utc::now().timestamp() / PERIOD
+}
+
+/// Compare two codes without returning early on the first difference
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shared secret of the RFC 6238 SHA-1 test vectors
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    fn rfc_totp() -> Totp {
+        Totp::from_secret(Zeroizing::new(RFC_SECRET.to_vec()))
+    }
+
+    #[test]
+    fn matches_rfc6238_sha1_vectors() {
+        // RFC 6238 appendix B, the 8 digit codes truncated to the last 6 digits
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+
+        let totp = rfc_totp();
+        for (time, code) in vectors {
+            assert_eq!(totp.code_at(time / PERIOD), code, "time {}", time);
+        }
+    }
+
+    #[test]
+    fn accepts_codes_within_skew() {
+        let totp = rfc_totp();
+        let now = 1234567890 / PERIOD;
+
+        for step in now - SKEW..=now + SKEW {
+            assert_eq!(totp.verify_at(&totp.code_at(step), 0, now), Some(step));
+        }
+        assert_eq!(totp.verify_at(&totp.code_at(now - SKEW - 1), 0, now), None);
+        assert_eq!(totp.verify_at(&totp.code_at(now + SKEW + 1), 0, now), None);
+        assert_eq!(totp.verify_at(&format!(" {} ", totp.code_at(now)), 0, now), Some(now));
+        assert_eq!(totp.verify_at("12345", 0, now), None);
+        assert_eq!(totp.verify_at("12a456", 0, now), None);
+    }
+
+    #[test]
+    fn refuses_reused_steps() {
+        let totp = rfc_totp();
+        let now = 1234567890 / PERIOD;
+        let code = totp.code_at(now);
+
+        let step = totp.verify_at(&code, now - SKEW - 1, now).unwrap();
+        assert_eq!(totp.verify_at(&code, step, now), None);
+        assert_eq!(totp.verify_at(&totp.code_at(now - 1), step, now), None);
+        assert_eq!(totp.verify_at(&totp.code_at(now + 1), step, now), Some(now + 1));
+    }
+}
@@ -0,0 +1,182 @@
+use argon2::{Argon2, Algorithm, Version, Params};
+use chacha20poly1305::{aead::{Aead, KeyInit, Payload}, XChaCha20Poly1305, XNonce};
+use rand::Rng;
+use zeroize::Zeroizing;
+
+/// Argon2id parameters for deriving the key encryption key from the master password
+/// - memory : 19 MiB
+/// - iterations : 2
+/// - parallelism : 1
+const KDF_MEMORY_COST: u32 = 19456;
+const KDF_TIME_COST: u32 = 2;
+const KDF_PARALLELISM: u32 = 1;
+
+/// Length of the kdf salt stored with each vault
+pub const SALT_LENGTH: usize = 16;
+
+/// Length of the XChaCha20-Poly1305 nonce stored with each entry
+pub const NONCE_LENGTH: usize = 24;
+
+/// Traits of an account vault
+/// - unlock_vault : derive the key from the master password and unwrap the vault key
+/// - rewrap_vault : wrap the unlocked vault key under a new master password, with the salt of the new
+///   key, stored along with the new password
+/// - lock_vault : forget the vault key until the vault is unlocked again
+/// - get_vault_key : vault key of the unlocked vault
+/// - get_account_id : id of the account owning the vault
+pub trait Vault {
+    fn unlock_vault(&mut self, password: &str) -> Result<(), rusqlite::Error>;
+    fn rewrap_vault(&self, password: &str) -> Option<([u8; SALT_LENGTH], Sealed)>;
+    fn lock_vault(&mut self);
+    fn get_vault_key(&self) -> Option<&VaultKey>;
+    fn get_account_id(&self) -> String;
+}
+
+/// A sealed (encrypted) piece of data with the nonce used to seal it
+#[derive(Clone)]
+pub struct Sealed {
+    pub nonce: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+}
+
+/// Symmetric key used to encrypt vault entries, zeroed on drop.
+///
+/// Every account owns a random vault key. The vault key is never stored as is,
+/// it is wrapped by a key derived from the master password so that changing
+/// the password only requires wrapping the same vault key again.
+pub struct VaultKey {
+    key: Zeroizing<[u8; 32]>,
+}
+
+impl VaultKey {
+
+    /// Generate a new random vault key
+    pub fn generate() -> VaultKey {
+        let mut key = Zeroizing::new([0u8; 32]);
+        rand::thread_rng().fill(&mut key[..]);
+        VaultKey { key }
+    }
+
+    /// Generate a new random kdf salt
+    pub fn generate_salt() -> [u8; SALT_LENGTH] {
+        let mut salt = [0u8; SALT_LENGTH];
+        rand::thread_rng().fill(&mut salt);
+        salt
+    }
+
+    /// Derive a key from the master password and kdf salt using Argon2id
+    pub fn derive(password: &str, salt: &[u8]) -> VaultKey {
+        let params = Params::new(KDF_MEMORY_COST, KDF_TIME_COST, KDF_PARALLELISM, Some(32))
+            .expect("valid argon2 parameters");
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
+        let mut key = Zeroizing::new([0u8; 32]);
+        argon2.hash_password_into(password.as_bytes(), salt, &mut key[..])
+            .expect("argon2 key derivation");
+        VaultKey { key }
+    }
+
+    /// Encrypt plaintext under this key with a fresh random nonce.
+    /// - associated_data : authenticated but not encrypted, binds the entry to its owner
+    pub fn seal(&self, plaintext: &[u8], associated_data: &[u8]) -> Sealed {
+        let cipher = XChaCha20Poly1305::new(self.key.as_ref().into());
+
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill(&mut nonce);
+
+        let ciphertext = cipher
+            .encrypt(XNonce::from_slice(&nonce), Payload { msg: plaintext, aad: associated_data })
+            .expect("xchacha20poly1305 encryption");
+
+        Sealed { nonce: nonce.to_vec(), ciphertext }
+    }
+
+    /// Decrypt a sealed entry, returns None if the key, nonce or associated
+    /// data do not match or the ciphertext has been tampered with
+    pub fn open(&self, sealed: &Sealed, associated_data: &[u8]) -> Option<Zeroizing<Vec<u8>>> {
+        if sealed.nonce.len() != NONCE_LENGTH {
+            return None;
+        }
+
+        let cipher = XChaCha20Poly1305::new(self.key.as_ref().into());
+        cipher
+            .decrypt(XNonce::from_slice(&sealed.nonce), Payload { msg: &sealed.ciphertext, aad: associated_data })
+            .ok()
+            .map(Zeroizing::new)
+    }
+
+    /// Wrap (encrypt) another key under this key
+    pub fn wrap(&self, key: &VaultKey, associated_data: &[u8]) -> Sealed {
+        self.seal(key.key.as_ref(), associated_data)
+    }
+
+    /// Unwrap (decrypt) a key previously wrapped under this key
+    pub fn unwrap(&self, sealed: &Sealed, associated_data: &[u8]) -> Option<VaultKey> {
+        let plaintext = self.open(sealed, associated_data)?;
+        if plaintext.len() != 32 {
+            return None;
+        }
+
+        let mut key = Zeroizing::new([0u8; 32]);
+        key.copy_from_slice(&plaintext);
+        Some(VaultKey { key })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::authenticator::{Account, Credential};
+    use super::super::database::{Database, DatabaseManager, DatabasePath, MigrationManager};
+
+    #[test]
+    fn seal_open_round_trip() {
+        let key = VaultKey::generate();
+        let sealed = key.seal(b"hunter2", b"account:app");
+
+        assert_eq!(key.open(&sealed, b"account:app").unwrap().as_slice(), b"hunter2");
+        assert!(key.open(&sealed, b"account:other").is_none());
+        assert!(VaultKey::generate().open(&sealed, b"account:app").is_none());
+
+        let mut tampered = sealed.clone();
+        tampered.ciphertext[0] ^= 1;
+        assert!(key.open(&tampered, b"account:app").is_none());
+
+        let mut truncated = sealed;
+        truncated.nonce.pop();
+        assert!(key.open(&truncated, b"account:app").is_none());
+    }
+
+    #[test]
+    fn wrap_unwrap_with_derived_key() {
+        let salt = VaultKey::generate_salt();
+        let vault_key = VaultKey::generate();
+        let wrapped = VaultKey::derive("master password", &salt).wrap(&vault_key, b"account");
+        let sealed = vault_key.seal(b"entry", b"account");
+
+        let unwrapped = VaultKey::derive("master password", &salt).unwrap(&wrapped, b"account").unwrap();
+        assert_eq!(unwrapped.open(&sealed, b"account").unwrap().as_slice(), b"entry");
+        assert!(VaultKey::derive("wrong password", &salt).unwrap(&wrapped, b"account").is_none());
+    }
+
+    #[test]
+    fn vault_unlocks_after_password_change() {
+        let mut database = Database::open(&DatabasePath::Memory).unwrap();
+        database.migrate(false).unwrap();
+        Account::new(database.clone()).create_account("vaulty".into(), &"correct horse battery".into()).unwrap();
+
+        let mut account = Account::new(database.clone());
+        account.login("vaulty".into(), &"correct horse battery".into()).unwrap();
+        let sealed = account.get_vault_key().unwrap().seal(b"entry", b"vaulty");
+
+        account.change_password(&"correct horse battery".into(), &"Tangerine-submarine-42".into()).unwrap();
+        account.lock_vault();
+        assert!(account.get_vault_key().is_none());
+
+        account.unlock_vault("correct horse battery").unwrap();
+        assert!(account.get_vault_key().is_none());
+
+        account.unlock_vault("Tangerine-submarine-42").unwrap();
+        assert_eq!(account.get_vault_key().unwrap().open(&sealed, b"vaulty").unwrap().as_slice(), b"entry");
+    }
+}
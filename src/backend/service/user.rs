@@ -1,5 +1,24 @@
-use crate::component::state::{TabsState, LoggedInState};
-use super::{security::authenticator::{Account, Credential, Verifier, Username, Password}, utility::constants::{VISITOR, MEMBER}};
+use std::time::{Duration, Instant};
+
+use crate::component::state::{TabsState, LoggedInState, StateList};
+use super::{security::authenticator::{Account, Credential, Verifier, Username}, utility::constants::{VISITOR, MEMBER}};
+use super::credential_manager::{Credential as VaultCredential, Manager};
+use super::security::audit::{AuditLog, Auditor, ChainStatus};
+use super::security::blocklists::Blocklists;
+use super::security::database::{Database, CredentialRecord, AuditRecord};
+use super::utility::error::AuthError;
+use super::security::policy::ADMIN_ROLE;
+use super::security::rate_limiter::{Limiter, RateLimiter};
+use super::security::recovery::{self, Recovery};
+use super::security::secret::SecretString;
+use super::security::session::{Session, SessionStatus, Tracker};
+use super::security::strength::Estimate;
+use super::security::vault::Vault;
+use super::security::totp::{TotpEnrollment, TwoFactor};
+use zeroize::Zeroize;
+
+/// Pause in typing before the signup username is checked against the database
+const USERNAME_CHECK_DELAY: Duration = Duration::from_millis(400);
 
 /// Enum system user mode containing the following fields for mode behavior
 /// - Normal : default mode 
@@ -7,15 +26,20 @@ use super::{security::authenticator::{Account, Credential, Verifier, Username, P
 /// - App : app system mode 
 /// - Username : username system mode 
 /// - Password : password system mode
+/// - Search : search prompt mode
+/// - Code : one time code mode
 pub enum UserMode {
     Normal,
     Insert,
     App,
+    Search,
     Profile,
     Username,
     Password,
     OldPassword,
     NewPassword,
+    ConfirmPassword,
+    Code,
 }
 
 /// Enum signup containing the following fields for mode behaviour
@@ -27,29 +51,50 @@ pub enum SignUp {
     Password,
 }
 
+/// Enum username status of the signup username, checked once typing pauses
+/// - Checking : the username was edited, the check waits for typing to pause
+/// - Available : the username is valid and free
+/// - Invalid : the username breaks the username rules, with the reason
+/// - Taken : the username, or one looking like it, is taken, with free usernames to suggest
+#[derive(Debug, Clone, PartialEq)]
+pub enum UsernameStatus {
+    Checking,
+    Available,
+    Invalid(AuthError),
+    Taken(Vec<String>),
+}
+
 /// Enum login containing the following fields for mode behavior
 /// - Username : required for username input
 /// - Password : required for password input
+/// - Code : required for one time code input, once the password is accepted
 #[derive(Debug, Clone)]
 pub enum Login {
     Username,
     Password,
+    Code,
 }
 
 /// Enum Profile containing the following fields for mode behaviour
 /// - OldPassword : required for password input
 /// - NewPassword : required for password input
+/// - ConfirmPassword : required to confirm the new password
+/// - Code : required to confirm a two factor enrollment
 #[derive(Debug, Clone)]
 pub enum Profile {
     OldPassword,
     NewPassword,
+    ConfirmPassword,
+    Code,
 }
 
 /// Enum credential manager containing the following fields for mode behavior
+/// - List : browsing the saved credentials
 /// - App : required for app input
 /// - Username : required for username input
 /// - Password : required for password input
 pub enum CredentialManager {
+    List,
     App,
     Username,
     Password,
@@ -64,61 +109,111 @@ enum Auth {
 /// User Servcie 
 /// - A user object with properties and methods
 /// - account : account object
+/// - activity : audit events of the logged in account, latest first
+/// - activity_status : state of the audit log hash chain when the events were loaded
+/// - blocklists_status : outcome of the last reload of the username lists, shown on the Diagnostics tab
+/// - database : database shared by every service
 /// - app : credential manager object
 /// - app_name : app app_name 
+/// - app_credentials : saved credentials of the account
+/// - app_delete_pending : the selected credential is deleted when d is pressed again
+/// - app_editing : id of the credential loaded into the form, saving replaces it
 /// - app_password : app password 
+/// - app_revealed_password : decrypted password of the selected credential
 /// - app_search_list : app search list 
 /// - app_search_query : app search query 
 /// - app_secure_password : app secure password 
 /// - app_username : app username 
+/// - confirm_password : confirmation of the new password 
+/// - confirm_secure_password : confirmation of the new secure password 
 /// - login : login object 
+/// - login_code : one time code of the login
+/// - login_code_required : the password was accepted, the login waits for a code
 /// - login_password : login password 
+/// - login_retry_at : when the next login attempt is accepted, if throttled
 /// - login_secure_password : login secure password 
 /// - login_username : login username 
 /// - notepad : notepad 
+/// - password_changed : the last password change succeeded, shown until the next one
+/// - profile_code : one time code confirming a two factor enrollment
+/// - recovery_codes : recovery codes just generated, shown until dismissed
+/// - recovery_codes_remaining : recovery codes of the account not used yet
 /// - scratchpad : scratchpad 
 /// - secure_password : secure password 
-/// - signed_in : signed in 
+/// - session : session of the logged in account, None while signed out
+/// - session_locked : the session was idle, the password is needed to continue
+/// - session_password : password entered on the lock screen
+/// - session_secure_password : secure password entered on the lock screen
+/// - session_error : error of the last unlock attempt
 /// - signup_password : signup password 
 /// - signup_secure_password : signup secure password 
 /// - signup : signup object 
 /// - signup_username : signup username 
+/// - signup_username_edited : when the signup username was last edited, until it is checked
+/// - signup_username_status : outcome of the last check of the signup username
 /// - tab : tab object 
+/// - totp_enabled : two factor authentication is enabled for the account
+/// - totp_enrollment : secret of a two factor enrollment waiting for confirmation
 /// - user_mode : user mode 
 pub struct User<'a> {
 
     account: Account,
+    pub activity: StateList<AuditRecord>,
+    activity_status: Option<ChainStatus>,
+    blocklists_status: Option<Result<String, String>>,
+    database: Database,
     auth: Auth,
     pub app: CredentialManager,
     pub app_name: String,
+    app_credentials: Vec<CredentialRecord>,
+    app_delete_pending: bool,
+    app_editing: Option<i64>,
     pub app_password: String,
-    app_search_list: Vec<String>,
-    app_search_query: String,
-    pub app_secure_password: String,
+    app_revealed_password: Option<SecretString>,
+    pub app_search_list: StateList<CredentialRecord>,
+    pub app_search_query: String,
+    pub app_secure_password: SecretString,
     pub app_username: String,
     old_password: String,
-    old_secure_password: String,
+    old_secure_password: SecretString,
     new_password: String,
-    new_secure_password: String,
-    new_secure_password_error_message: String,
+    new_secure_password: SecretString,
+    new_secure_password_error: Option<AuthError>,
+    confirm_password: String,
+    confirm_secure_password: SecretString,
     login: Login,
+    login_code: SecretString,
+    login_code_required: bool,
     login_username: String,
     login_password: String,
-    login_secure_password: String,
-    login_error_message: String,
+    login_secure_password: SecretString,
+    login_error: Option<AuthError>,
+    login_retry_at: Option<Instant>,
     pub notepad: Vec<String>,
+    password_changed: bool,
     pub profile: Profile,
+    profile_code: String,
+    recovery_codes: Option<Vec<SecretString>>,
+    recovery_codes_remaining: usize,
     pub scratchpad: String,
-    pub secure_password: String,
-    signed_in: bool,
+    pub secure_password: SecretString,
+    session: Option<Session>,
+    session_locked: bool,
+    session_password: String,
+    session_secure_password: SecretString,
+    session_error: Option<AuthError>,
     pub signup: SignUp,
     signup_username: String,
     signup_password: String,
-    signup_secure_password: String,
-    signup_username_error_message: String,
-    signup_password_error_message: String,
+    signup_secure_password: SecretString,
+    signup_username_error: Option<AuthError>,
+    signup_username_edited: Option<Instant>,
+    signup_username_status: Option<UsernameStatus>,
+    signup_password_error: Option<AuthError>,
     pub tab: TabsState<'a>,
     pub logged_in_tab: LoggedInState<'a>,
+    totp_enabled: bool,
+    totp_enrollment: Option<TotpEnrollment>,
     pub user_mode: UserMode,
 
 }
@@ -134,6 +229,7 @@ impl <'a>User<'a> {
 
     /// User Service - Instantiate a new user with properties and methods
     /// - account : account object
+    /// - database : database shared by every service
     /// - app : credential manager object
     /// - app_name : app name 
     /// - app_password : app password 
@@ -149,49 +245,74 @@ impl <'a>User<'a> {
     /// - notepad : notepad object 
     /// - scratchpad : scratchpad object 
     /// - secure_password : secure password 
-    /// - signed_in : signed in status 
+    /// - session : session of the logged in account 
     /// - signup_password : signup password 
     /// - signup_secure_password : signup secure password 
     /// - signup : signup object 
     /// - signup_username : signup username 
     /// - tab : tab object 
     /// - user_mode : user mode object
-    pub fn new() -> User<'a> {
+    pub fn new(database: Database) -> User<'a> {
 
         User {
-            account: Account::new(),
-            app: CredentialManager::App,
+            account: Account::new(database.clone()),
+            activity: StateList::all_items(Vec::new()),
+            activity_status: None,
+            blocklists_status: None,
+            app: CredentialManager::List,
             app_name: String::new(),
-            app_search_list: Vec::new(),
+            app_credentials: Vec::new(),
+            app_delete_pending: false,
+            app_editing: None,
+            app_revealed_password: None,
+            app_search_list: StateList::all_items(Vec::new()),
             app_search_query: String::new(),
-            app_secure_password: String::new(),
+            app_secure_password: SecretString::default(),
             app_password: String::new(),
             app_username: String::new(),
             old_password: String::new(),
-            old_secure_password: String::new(),
+            old_secure_password: SecretString::default(),
             new_password: String::new(),
-            new_secure_password: String::new(),
-            new_secure_password_error_message: String::new(),
-            auth: Auth::Account(Account::new()),
+            new_secure_password: SecretString::default(),
+            new_secure_password_error: None,
+            confirm_password: String::new(),
+            confirm_secure_password: SecretString::default(),
+            auth: Auth::Account(Account::new(database.clone())),
             login: Login::Username,
+            login_code: SecretString::default(),
+            login_code_required: false,
             login_username: String::new(),
             login_password: String::new(),
-            login_secure_password: String::new(),
-            login_error_message: String::new(),
+            login_secure_password: SecretString::default(),
+            login_error: None,
+            login_retry_at: None,
             notepad: Vec::new(),
+            password_changed: false,
             profile: Profile::OldPassword,
+            profile_code: String::new(),
+            recovery_codes: None,
+            recovery_codes_remaining: 0,
             scratchpad: String::new(),
-            secure_password: String::new(),
-            signed_in: false,
+            secure_password: SecretString::default(),
+            session: None,
+            session_locked: false,
+            session_password: String::new(),
+            session_secure_password: SecretString::default(),
+            session_error: None,
             signup: SignUp::Username,
             signup_username: String::new(),
             signup_password: String::new(),
-            signup_secure_password: String::new(),
-            signup_username_error_message: String::new(),
-            signup_password_error_message: String::new(),
+            signup_secure_password: SecretString::default(),
+            signup_username_error: None,
+            signup_username_edited: None,
+            signup_username_status: None,
+            signup_password_error: None,
             tab: TabsState::new(VISITOR.to_vec()),
             logged_in_tab: LoggedInState::new(MEMBER.to_vec()),
+            totp_enabled: false,
+            totp_enrollment: None,
             user_mode: UserMode::Normal,
+            database,
         }
     }
 
@@ -205,14 +326,25 @@ impl <'a>User<'a> {
         self.new_password.clone()
     }
 
-    /// User Service - Get the old secure password 
-    pub fn get_old_secure_password(&mut self) -> String {
-        self.old_secure_password.clone()
+    /// User Service - Take the old secure password, the input is left empty
+    pub fn take_old_secure_password(&mut self) -> SecretString {
+        let password = std::mem::take(&mut self.old_secure_password);
+        self.clear_old_password();
+        password
+    }
+
+    /// User Service - Take the new secure password, the input is left empty
+    pub fn take_new_secure_password(&mut self) -> SecretString {
+        let password = std::mem::take(&mut self.new_secure_password);
+        self.clear_new_password();
+        password
     }
 
-    /// User Service - Get the new secure password
-    pub fn get_new_secure_password(&mut self) -> String {
-        self.new_secure_password.clone()
+    /// User Service - Take the confirmation of the new secure password, the input is left empty
+    pub fn take_confirm_secure_password(&mut self) -> SecretString {
+        let password = std::mem::take(&mut self.confirm_secure_password);
+        self.clear_confirm_password();
+        password
     }
 
     /// User Service - Remove old password
@@ -235,6 +367,16 @@ impl <'a>User<'a> {
         self.new_secure_password.pop();
     }
 
+    /// User Service - Remove confirmation of the new password
+    pub fn pop_confirm_password(&mut self) {
+        self.confirm_password.pop();
+    }
+
+    /// User Service - Remove confirmation of the new secure password
+    pub fn pop_confirm_secure_password(&mut self) {
+        self.confirm_secure_password.pop();
+    }
+
     /// User Service - Set old password
     /// - old_password : old password
     pub fn set_old_password(&mut self, character: char) {
@@ -259,20 +401,46 @@ impl <'a>User<'a> {
         self.new_secure_password.push(character);
     }
 
+    /// User Service - Get confirmation of the new password
+    pub fn get_confirm_password(&mut self) -> String {
+        self.confirm_password.clone()
+    }
+
+    /// User Service - Set confirmation of the new password
+    /// - confirm_password : confirmation of the new password
+    pub fn set_confirm_password(&mut self, character: char) {
+        self.confirm_password.push(character);
+    }
+
+    /// User Service - Set confirmation of the new secure password
+    /// - confirm_secure_password : confirmation of the new secure password
+    pub fn set_confirm_secure_password(&mut self, character: char) {
+        self.confirm_secure_password.push(character);
+    }
+
+    /// User Service - Estimate the strength of the new password as it is typed,
+    /// None until a character is typed
+    pub fn get_new_password_strength(&self) -> Option<Estimate> {
+        match self.new_secure_password.expose().is_empty() {
+            true => None,
+            false => Some(self.account.estimate_password(self.account.get_username().as_str(), &self.new_secure_password)),
+        }
+    }
+
     /// User Service - Get login error message
-    pub fn get_new_secure_password_error_message(&self) -> String {
-        self.new_secure_password_error_message.clone()
+    pub fn get_new_secure_password_error(&self) -> Option<AuthError> {
+        self.new_secure_password_error.clone()
     }
 
     /// User Service - Set login message
-    /// - message : message
-    pub fn set_new_secure_password_error_message(&mut self, message: String) {
-        self.new_secure_password_error_message = message;
+    /// - error : error
+    pub fn set_new_secure_password_error(&mut self, error: AuthError) {
+        self.new_secure_password_error = Some(error);
     }
 
     /// User Service - Clear login error message
-    pub fn clear_new_secure_password_error_message(&mut self) {
-        self.new_secure_password_error_message.clear();
+    pub fn clear_new_secure_password_error(&mut self) {
+        self.new_secure_password_error = None;
     }
 
     /// User Service - Clear old password 
@@ -280,19 +448,24 @@ impl <'a>User<'a> {
         self.old_password.clear();
     }
 
-    /// User Service - Clear old secure password
-    pub fn clear_old_secure_password(&mut self) {
-        self.old_secure_password.clear();
-    }
-
     /// User Service - Clear new password
     pub fn clear_new_password(&mut self) {
         self.new_password.clear();
     }
 
-    /// User Serivce - Clear new secure password
-    pub fn clear_new_secure_password(&mut self) {
-        self.new_secure_password.clear();
+    /// User Service - Clear confirmation of the new password
+    pub fn clear_confirm_password(&mut self) {
+        self.confirm_password.clear();
+    }
+
+    /// User Service - Check if the last password change succeeded
+    pub fn get_password_changed(&self) -> bool {
+        self.password_changed
+    }
+
+    /// User Service - Forget the outcome of the last password change
+    pub fn clear_password_changed(&mut self) {
+        self.password_changed = false;
     }
 
     /// User Service - Getting the user login mode
@@ -337,16 +510,204 @@ impl <'a>User<'a> {
         self.app_username = app_username;
     }
 
-    /// User Service - Set the app Password for password manager 
-    /// - app_password : password
-    pub fn set_app_password(&mut self, app_password: String) {
-        self.app_password = app_password;
+    /// User Service - Add a character to the app secure password for password manager,
+    /// the visible field only shows a mask
+    /// - character : character of the password
+    pub fn set_app_secure_password(&mut self, character: char) {
+        self.app_password.push('*');
+        self.app_secure_password.push(character);
+    }
+
+    /// User Service - Remove a character from the app secure password for password manager
+    pub fn pop_app_secure_password(&mut self) {
+        self.app_password.pop();
+        self.app_secure_password.pop();
+    }
+
+    /// User Service - Store the app name, username and password as an encrypted
+    /// entry in the vault of the logged in account, replacing the entry being edited
+    pub fn save_credential(&mut self) -> bool {
+        let mut credential = VaultCredential::new(self.database.clone());
+        let saved = match self.app_editing {
+            Some(id) => credential.update_credential(
+                &self.account,
+                id,
+                self.app_name.clone(),
+                self.app_username.clone(),
+                &self.app_secure_password,
+            ),
+            None => credential.new_credential(
+                &self.account,
+                self.app_name.clone(),
+                self.app_username.clone(),
+                &self.app_secure_password,
+            ),
+        };
+
+        if saved {
+            self.refresh_credentials();
+        }
+        saved
+    }
+
+    /// User Service - Load the saved credentials of the logged in account
+    pub fn refresh_credentials(&mut self) {
+        let credential = VaultCredential::new(self.database.clone());
+        self.app_credentials = credential.get_credentials(&self.account);
+        self.filter_credentials();
+    }
+
+    /// User Service - Narrow the credential list down to the entries whose app or
+    /// username contains the search query, ignoring case
+    pub fn filter_credentials(&mut self) {
+        let query = self.app_search_query.to_lowercase();
+        let items: Vec<CredentialRecord> = self.app_credentials
+            .iter()
+            .filter(|record| {
+                query.is_empty()
+                    || record.app.to_lowercase().contains(&query)
+                    || record.username.to_lowercase().contains(&query)
+            })
+            .cloned()
+            .collect();
+
+        self.app_search_list = StateList::all_items(items);
+        self.app_search_list.next();
+        self.app_revealed_password = None;
+        self.app_delete_pending = false;
+    }
+
+    /// User Service - Add a character to the search query
+    pub fn push_app_search_query(&mut self, character: char) {
+        self.app_search_query.push(character);
+        self.filter_credentials();
+    }
+
+    /// User Service - Remove a character from the search query
+    pub fn pop_app_search_query(&mut self) {
+        self.app_search_query.pop();
+        self.filter_credentials();
+    }
+
+    /// User Service - Clear the search query
+    pub fn clear_app_search_query(&mut self) {
+        self.app_search_query.clear();
+        self.filter_credentials();
+    }
+
+    /// User Service - Select the next credential in the list
+    pub fn next_credential(&mut self) {
+        self.app_search_list.next();
+        self.app_revealed_password = None;
+        self.app_delete_pending = false;
+    }
+
+    /// User Service - Select the previous credential in the list
+    pub fn previous_credential(&mut self) {
+        self.app_search_list.previous();
+        self.app_revealed_password = None;
+        self.app_delete_pending = false;
+    }
+
+    /// User Service - Get the number of saved credentials before filtering
+    pub fn app_credentials_count(&self) -> usize {
+        self.app_credentials.len()
+    }
+
+    /// User Service - Get the selected credential
+    pub fn get_selected_credential(&self) -> Option<&CredentialRecord> {
+        self.app_search_list.selected()
+    }
+
+    /// User Service - Decrypt the password of the selected credential, or hide it again
+    pub fn toggle_reveal_credential(&mut self) {
+        if self.app_revealed_password.is_some() {
+            self.app_revealed_password = None;
+            return;
+        }
+
+        let credential = VaultCredential::new(self.database.clone());
+        self.app_revealed_password = self.app_search_list
+            .selected()
+            .and_then(|record| credential.reveal_password(&self.account, record));
+    }
+
+    /// User Service - Get the decrypted password of the selected credential, if revealed
+    pub fn get_revealed_password(&self) -> Option<&str> {
+        self.app_revealed_password.as_ref().map(SecretString::expose)
+    }
+
+    /// User Service - Load the selected credential into the form and move to it, its password is
+    /// decrypted so it can be kept or replaced, the next save replaces the entry
+    pub fn edit_selected_credential(&mut self) -> bool {
+        let credential = VaultCredential::new(self.database.clone());
+        let (id, app_name, app_username, password) = match self.app_search_list.selected() {
+            Some(record) => match credential.reveal_password(&self.account, record) {
+                Some(password) => (record.id, record.app.clone(), record.username.clone(), password),
+                None => return false,
+            },
+            None => return false,
+        };
+
+        self.clear_credential();
+        self.app_name = app_name;
+        self.app_username = app_username;
+        password.expose().chars().for_each(|character| self.set_app_secure_password(character));
+        self.app_editing = Some(id);
+        self.app = CredentialManager::App;
+        true
+    }
+
+    /// User Service - Get the app name of the credential being edited, if any
+    pub fn get_editing_credential(&self) -> Option<&str> {
+        let id = self.app_editing?;
+        self.app_credentials.iter()
+            .find(|record| record.id == id)
+            .map(|record| record.app.as_str())
+    }
+
+    /// User Service - Delete the selected credential, the first call only asks for a
+    /// confirmation, the second call deletes it
+    pub fn delete_selected_credential(&mut self) -> bool {
+        if !self.app_delete_pending {
+            self.app_delete_pending = self.app_search_list.selected().is_some();
+            return false;
+        }
+        self.app_delete_pending = false;
+
+        let id = match self.app_search_list.selected() {
+            Some(record) => record.id,
+            None => return false,
+        };
+
+        let mut credential = VaultCredential::new(self.database.clone());
+        let deleted = credential.delete_credential(&self.account, id);
+        if deleted {
+            if self.app_editing == Some(id) {
+                self.clear_credential();
+            }
+            self.refresh_credentials();
+        }
+        deleted
+    }
+
+    /// User Service - Check whether the selected credential waits for a delete confirmation
+    pub fn get_delete_pending(&self) -> bool {
+        self.app_delete_pending
     }
 
-    /// User Service - Set the app secure password for password manager 
-    /// - app_secure_password : secure password
-    pub fn set_app_secure_password(&mut self, app_secure_password: String) {
-        self.app_secure_password = app_secure_password;
+    /// User Service - Forget a pending delete confirmation
+    pub fn cancel_delete_credential(&mut self) {
+        self.app_delete_pending = false;
+    }
+
+    /// User Service - Clear the credential manager input
+    pub fn clear_credential(&mut self) {
+        self.app_name.clear();
+        self.app_username.clear();
+        self.app_password.clear();
+        self.app_secure_password.clear();
+        self.app_editing = None;
     }
 
     /// User Service - Get login username at login screen
@@ -371,9 +732,11 @@ impl <'a>User<'a> {
         self.login_password.push(character);
     }
 
-    /// User Service - Get secure login password at login screen
-    pub fn get_login_secure_password(&self) -> String {
-        self.login_secure_password.clone()
+    /// User Service - Take secure login password at login screen, the input is left empty
+    pub fn take_login_secure_password(&mut self) -> SecretString {
+        let password = std::mem::take(&mut self.login_secure_password);
+        self.clear_login_password();
+        password
     }
 
     /// User Service - Clear login username
@@ -413,41 +776,144 @@ impl <'a>User<'a> {
     }
 
     /// User Service - Get login error message
-    pub fn get_login_error_message(&self) -> String {
-        self.login_error_message.clone()
+    pub fn get_login_error(&self) -> Option<AuthError> {
+        self.login_error.clone()
     }
 
     /// User Service - Set login message
-    /// - message : message
-    pub fn set_login_error_message(&mut self, message: String) {
-        self.login_error_message = message;
+    /// - error : error
+    pub fn set_login_error(&mut self, error: AuthError) {
+        self.login_error = Some(error);
     }
 
     /// User Service - Clear login error message
-    pub fn clear_login_error_message(&mut self) {
-        self.login_error_message.clear();
+    pub fn clear_login_error(&mut self) {
+        self.login_error = None;
     }
 
     /// User Service - Login to an account using provided credentials
-    pub fn login(&mut self, username: String, password: String) -> bool {
+    /// - accounts with two factor authentication wait for a code, see verify_login_code
+    pub fn login(&mut self, username: String, password: &SecretString) -> Result<(), AuthError> {
         // let mut account = Account::new();
-        let result = self.account.login(username.clone(), password.clone());
+        let result = self.account.login(username.clone(), password);
 
-        match result.validity {
-            true => {
+        match result {
+            Ok(()) => {
                 // self.tab = TabsState::new(MEMBER.to_vec());
-                self.account.set_username(username.clone());
-                self.account.set_password(password.clone());
-                self.set_signed_in(true);
-                true
+                self.totp_enabled = false;
+                self.finish_login()
             },
-            false => {
-                self.set_login_error_message(result.message);
-                false
+            Err(AuthError::CodeRequired) => {
+                self.clear_login_password();
+                self.clear_login_secure_password();
+                self.login_code_required = true;
+                self.login = Login::Code;
+                self.user_mode = UserMode::Code;
+                Err(AuthError::CodeRequired)
+            },
+            Err(error) => {
+                self.set_login_error(error.clone());
+                self.update_login_wait(username.as_str());
+                Err(error)
+            }
+        }
+    }
+
+    /// User Service - Finish a login waiting for a one time code, or a recovery code
+    pub fn verify_login_code(&mut self) -> Result<(), AuthError> {
+        let code = std::mem::take(&mut self.login_code);
+
+        let result = match recovery::is_recovery_code(code.expose()) {
+            true => self.account.verify_recovery_code(code.expose()),
+            false => self.account.verify_totp(code.expose()),
+        };
+        drop(code);
+
+        match result {
+            Ok(()) => {
+                self.login_code_required = false;
+                self.login = Login::Username;
+                self.totp_enabled = true;
+                self.finish_login()
+            },
+            Err(error) => {
+                self.set_login_error(error.clone());
+                self.update_login_wait(self.account.get_username().as_str());
+                Err(error)
             }
         }
     }
 
+    /// User Service - Abandon a login waiting for a one time code
+    pub fn cancel_login_code(&mut self) {
+        self.account.cancel_totp();
+        self.clear_login_code();
+        self.login_code_required = false;
+        self.login = Login::Username;
+    }
+
+    /// User Service - Check if the login waits for a one time code
+    pub fn get_login_code_required(&self) -> bool {
+        self.login_code_required
+    }
+
+    /// User Service - Get one time code of the login
+    pub fn get_login_code(&self) -> &str {
+        self.login_code.expose()
+    }
+
+    /// User Service - Add a character to the one time code or recovery code of the login
+    pub fn set_login_code(&mut self, character: char) {
+        if character.is_ascii_alphanumeric() || character == '-' {
+            self.login_code.push(character);
+        }
+    }
+
+    /// User Service - Remove a character from the one time code of the login
+    pub fn pop_login_code(&mut self) {
+        self.login_code.pop();
+    }
+
+    /// User Service - Clear one time code of the login
+    pub fn clear_login_code(&mut self) {
+        self.login_code.clear();
+    }
+
+    /// Signed in, start a session and load what the member tabs show
+    fn finish_login(&mut self) -> Result<(), AuthError> {
+        let session = match Session::start(self.database.clone(), self.account.get_account_id().as_str()) {
+            Ok(session) => session,
+            Err(error) => {
+                let error = AuthError::from(error);
+                self.account.lock_vault();
+                self.set_login_error(error.clone());
+                return Err(error);
+            }
+        };
+
+        self.session = Some(session);
+        self.session_locked = false;
+        self.login_retry_at = None;
+        self.recovery_codes_remaining = self.account.count_recovery_codes().unwrap_or(0);
+        self.refresh_credentials();
+        Ok(())
+    }
+
+    /// Remember when the next login attempt for the username is accepted
+    fn update_login_wait(&mut self, username: &str) {
+        self.login_retry_at = RateLimiter::new(self.database.clone())
+            .get_wait(username)
+            .unwrap_or(None)
+            .map(|wait| Instant::now() + wait);
+    }
+
+    /// User Service - Get the time left before the next login attempt is accepted
+    pub fn get_login_wait(&self) -> Option<Duration> {
+        self.login_retry_at
+            .and_then(|retry_at| retry_at.checked_duration_since(Instant::now()))
+            .filter(|wait| !wait.is_zero())
+    }
+
     /// User Service - Set username to a temp signup object
     /// - username : username
     pub fn get_signup_username(&mut self) -> String {
@@ -458,32 +924,71 @@ impl <'a>User<'a> {
     /// - username : username
     pub fn set_signup_username(&mut self, character: char) {
         self.signup_username.push(character);
+        self.edit_signup_username();
     }
 
     /// User Service - Remove username character from a temp signup object
     /// - username : username
     pub fn pop_signup_username(&mut self) {
         self.signup_username.pop();
+        self.edit_signup_username();
     }
 
     /// User Service - Clear signup username
     pub fn clear_signup_username(&mut self) {
         self.signup_username.clear();
+        self.edit_signup_username();
+    }
+
+    /// User Service - Wait for typing to pause before checking the edited signup username
+    fn edit_signup_username(&mut self) {
+        match self.signup_username.is_empty() {
+            true => {
+                self.signup_username_edited = None;
+                self.signup_username_status = None;
+            },
+            false => {
+                self.signup_username_edited = Some(Instant::now());
+                self.signup_username_status = Some(UsernameStatus::Checking);
+            },
+        }
+    }
+
+    /// User Service - Check the signup username once typing paused, called on every tick.
+    /// Free usernames are suggested in place of a taken one
+    pub fn check_signup_username(&mut self) {
+        match self.signup_username_edited {
+            Some(edited) if edited.elapsed() >= USERNAME_CHECK_DELAY => self.signup_username_edited = None,
+            _ => return,
+        }
+
+        let username = self.signup_username.as_str();
+        self.signup_username_status = Some(match self.account.check_username(username) {
+            Ok(()) => UsernameStatus::Available,
+            Err(AuthError::UsernameTaken) => UsernameStatus::Taken(self.account.suggest_usernames(username)),
+            Err(error) => UsernameStatus::Invalid(error),
+        });
+    }
+
+    /// User Service - Get the status of the signup username, None until a character is typed
+    pub fn get_signup_username_status(&self) -> Option<&UsernameStatus> {
+        self.signup_username_status.as_ref()
     }
 
     /// User Service - Get signup username error message
-    pub fn get_signup_username_error_message(&mut self) -> String {
-        self.signup_username_error_message.clone()
+    pub fn get_signup_username_error(&self) -> Option<AuthError> {
+        self.signup_username_error.clone()
     }
 
     /// User Service - Set signup username error message
-    pub fn set_signup_username_error_message(&mut self, message: String) {
-        self.signup_username_error_message = message;
+    /// - error : error
+    pub fn set_signup_username_error(&mut self, error: AuthError) {
+        self.signup_username_error = Some(error);
     }
 
     /// User Service - Clear signup username error message
-    pub fn clear_signup_username_error_message(&mut self) {
-        self.signup_username_error_message.clear();
+    pub fn clear_signup_username_error(&mut self) {
+        self.signup_username_error = None;
     }
 
     /// User Service - Get password to a temp signup object
@@ -509,24 +1014,27 @@ impl <'a>User<'a> {
     }
 
     /// User Service - Get signup password error message
-    pub fn get_signup_password_error_message(&mut self) -> String {
-        self.signup_password_error_message.clone()
+    pub fn get_signup_password_error(&self) -> Option<AuthError> {
+        self.signup_password_error.clone()
     }
 
     /// User Service - Set signup password error message
-    pub fn set_signup_password_error_message(&mut self, message: String) {
-        self.signup_password_error_message = message;
+    /// - error : error
+    pub fn set_signup_password_error(&mut self, error: AuthError) {
+        self.signup_password_error = Some(error);
     }
 
     /// User Service - Clear signup password error message
-    pub fn clear_signup_password_error_message(&mut self) {
-        self.signup_password_error_message.clear();
+    pub fn clear_signup_password_error(&mut self) {
+        self.signup_password_error = None;
     }
 
-    /// User Service - Get secure password to an account using provided credentials
-    /// - secure_password : secure password
-    pub fn get_signup_secure_password(&mut self) -> String {
-        self.signup_secure_password.clone()
+    /// User Service - Take secure password to an account using provided credentials,
+    /// the input is left empty
+    pub fn take_signup_secure_password(&mut self) -> SecretString {
+        let password = std::mem::take(&mut self.signup_secure_password);
+        self.clear_signup_password();
+        password
     }
 
     /// User Service - Set secure password to an account using provided credentials
@@ -546,14 +1054,195 @@ impl <'a>User<'a> {
         self.signup_secure_password.clear();
     }
 
+    /// User Service - Estimate the strength of the signup password as it is typed,
+    /// None until a character is typed
+    pub fn get_signup_strength(&self) -> Option<Estimate> {
+        match self.signup_secure_password.expose().is_empty() {
+            true => None,
+            false => Some(self.account.estimate_password(self.signup_username.as_str(), &self.signup_secure_password)),
+        }
+    }
+
     /// User Service = Get the current signed in state of the user
     pub fn get_signed_in(&self) -> bool {
-        self.signed_in
+        self.session.is_some()
     }
 
-    /// User Service - Set the signed in state of the user
-    pub fn set_signed_in(&mut self, signed_in: bool) {
-        self.signed_in = signed_in;
+    /// User Service - Record input on the session, it is no longer idle
+    pub fn touch_session(&mut self) {
+        if self.session_locked {
+            return;
+        }
+
+        if let Some(session) = self.session.as_mut() {
+            let _ = session.touch();
+        }
+    }
+
+    /// User Service - Lock an idle session and sign out of an expired one, called on every tick
+    pub fn check_session(&mut self) {
+        let status = match &self.session {
+            Some(session) => session.status(),
+            None => return,
+        };
+
+        match status {
+            SessionStatus::Expired => {
+                self.switch_user();
+                self.set_login_error(AuthError::SessionExpired);
+            },
+            SessionStatus::Idle if !self.session_locked => self.lock_session(),
+            _ => {}
+        }
+    }
+
+    /// User Service - Lock the session, the vault key and revealed password are forgotten
+    pub fn lock_session(&mut self) {
+        self.account.lock_vault();
+        self.app_revealed_password = None;
+        self.cancel_totp_enrollment();
+        self.dismiss_recovery_codes();
+        self.session_locked = true;
+        self.user_mode = UserMode::Normal;
+    }
+
+    /// User Service - Unlock the session with the password of the account
+    pub fn unlock_session(&mut self) -> Result<(), AuthError> {
+        let password = std::mem::take(&mut self.session_secure_password);
+        self.clear_session_password();
+
+        match self.account.unlock(&password) {
+            Ok(()) => {
+                self.session_locked = false;
+                self.session_error = None;
+                self.touch_session();
+                Ok(())
+            },
+            Err(error) => {
+                self.session_error = Some(error.clone());
+                Err(error)
+            }
+        }
+    }
+
+    /// User Service - Log out, the session is ended and every buffer of the user is wiped
+    /// before returning to the welcome tab of the visitor tabs
+    pub fn logout(&mut self) {
+        if let Some(mut session) = self.session.take() {
+            let _ = session.end();
+        }
+
+        self.account.lock_vault();
+        self.account = Account::new(self.database.clone());
+
+        // Wipe what the user typed or revealed, zeroed before the memory is released
+        self.notepad.iter_mut().for_each(|note| note.zeroize());
+        self.notepad.clear();
+        for buffer in [
+            &mut self.scratchpad,
+            &mut self.app_name,
+            &mut self.app_username,
+            &mut self.app_password,
+            &mut self.app_search_query,
+            &mut self.old_password,
+            &mut self.new_password,
+            &mut self.confirm_password,
+            &mut self.login_username,
+            &mut self.login_password,
+            &mut self.signup_username,
+            &mut self.signup_password,
+            &mut self.profile_code,
+            &mut self.session_password,
+        ] {
+            buffer.zeroize();
+        }
+        for secret in [
+            &mut self.secure_password,
+            &mut self.app_secure_password,
+            &mut self.old_secure_password,
+            &mut self.new_secure_password,
+            &mut self.confirm_secure_password,
+            &mut self.login_secure_password,
+            &mut self.login_code,
+            &mut self.signup_secure_password,
+            &mut self.session_secure_password,
+        ] {
+            secret.clear();
+        }
+        self.app_revealed_password = None;
+        self.app_credentials.clear();
+        self.app_search_list = StateList::all_items(Vec::new());
+        self.activity = StateList::all_items(Vec::new());
+        self.activity_status = None;
+        self.blocklists_status = None;
+
+        self.totp_enabled = false;
+        self.totp_enrollment = None;
+        self.recovery_codes = None;
+        self.recovery_codes_remaining = 0;
+        self.login_code_required = false;
+        self.session_locked = false;
+
+        self.login_error = None;
+        self.signup_username_error = None;
+        self.signup_username_edited = None;
+        self.signup_username_status = None;
+        self.signup_password_error = None;
+        self.new_secure_password_error = None;
+        self.password_changed = false;
+        self.session_error = None;
+
+        self.app = CredentialManager::List;
+        self.login = Login::Username;
+        self.profile = Profile::OldPassword;
+        self.signup = SignUp::Username;
+        self.tab = TabsState::new(VISITOR.to_vec());
+        self.logged_in_tab = LoggedInState::new(MEMBER.to_vec());
+        self.user_mode = UserMode::Normal;
+    }
+
+    /// User Service - Log out and go straight to the login tab, to hand the terminal over to another user
+    pub fn switch_user(&mut self) {
+        self.logout();
+        self.tab.index = VISITOR.iter().position(|title| *title == "Login").unwrap_or(0);
+    }
+
+    /// User Service - Get username of the logged in account
+    pub fn get_account_username(&self) -> String {
+        self.account.get_username()
+    }
+
+    /// User Service - Check if the session is locked
+    pub fn get_session_locked(&self) -> bool {
+        self.session_locked
+    }
+
+    /// User Service - Get password of the lock screen
+    pub fn get_session_password(&self) -> String {
+        self.session_password.clone()
+    }
+
+    /// User Service - Add a character to the password of the lock screen
+    pub fn set_session_password(&mut self, character: char) {
+        self.session_password.push('*');
+        self.session_secure_password.push(character);
+    }
+
+    /// User Service - Remove a character from the password of the lock screen
+    pub fn pop_session_password(&mut self) {
+        self.session_password.pop();
+        self.session_secure_password.pop();
+    }
+
+    /// User Service - Clear password of the lock screen
+    pub fn clear_session_password(&mut self) {
+        self.session_password.clear();
+        self.session_secure_password.clear();
+    }
+
+    /// User Service - Get error of the last unlock attempt
+    pub fn get_session_error(&self) -> Option<AuthError> {
+        self.session_error.clone()
     }
 
     /// User Service - Create an account using provided credentials, the account is then
@@ -561,57 +1250,280 @@ impl <'a>User<'a> {
     /// and stored in an in-memory database or secure file.
     /// - username : username 
     /// - password : password
-     pub fn create_account(&mut self, username: String, password: String) -> bool {
+     pub fn create_account(&mut self, username: String, password: &SecretString) -> Result<(), AuthError> {
 
         // let mut account = Account::new();
-        let password_response = self.account.validate_password(password.clone());
+        let password_response = self.account.validate_password(username.as_str(), password);
         let username_response = self.account.validate_username(username.clone());
 
-        match username_response.validity {
-            true => {}
-            _ => {
-                self.clear_signup_username();
-                self.set_signup_username_error_message(
-                    username_response.message
-                );
-                return false;
+        if let Err(error) = username_response.clone() {
+            self.clear_signup_username();
+            self.set_signup_username_error(error);
+        }
+
+        if let Err(error) = password_response.clone() {
+            self.clear_signup_password();
+            self.clear_signup_secure_password();
+            self.set_signup_password_error(error);
+        }
+
+        username_response?;
+        password_response?;
+
+        self.account.set_username(username.clone());
+        self.account.create_account(username.clone(), password)
+            .map_err(|error| {
+                if error == AuthError::UsernameTaken {
+                    self.signup_username_status = Some(UsernameStatus::Taken(self.account.suggest_usernames(username.as_str())));
+                }
+                self.set_signup_username_error(error.clone());
+                error
+            })
+    }
+
+    /// User Service - Start a two factor enrollment, the secret is shown until confirmed
+    pub fn start_totp_enrollment(&mut self) -> Result<(), AuthError> {
+        self.clear_new_secure_password_error();
+        self.clear_profile_code();
+
+        match self.account.enroll_totp() {
+            Ok(enrollment) => {
+                self.totp_enrollment = Some(enrollment);
+                self.profile = Profile::Code;
+                Ok(())
+            },
+            Err(error) => {
+                self.set_new_secure_password_error(error.clone());
+                Err(error)
             }
         }
+    }
+
+    /// User Service - Confirm the two factor enrollment with a code from the authenticator app
+    pub fn confirm_totp_enrollment(&mut self) -> Result<(), AuthError> {
+        let code = self.profile_code.clone();
+        self.clear_profile_code();
 
-        match password_response.validity {
-            true => {}
-            _ => {
-                self.clear_signup_password();
-                self.clear_signup_secure_password();
-                self.set_signup_password_error_message(
-                    password_response.message
-                );
-                return false;
+        match self.account.confirm_totp(code.as_str()) {
+            Ok(()) => {
+                self.totp_enrollment = None;
+                self.totp_enabled = true;
+                self.profile = Profile::OldPassword;
+                self.regenerate_recovery_codes()
+            },
+            Err(error) => {
+                self.set_new_secure_password_error(error.clone());
+                Err(error)
             }
         }
+    }
 
-        self.account.set_username(username.clone());
-        self.account.set_password(password.clone());
-        self.account.create_account(username.clone(), password.clone())
+    /// User Service - Abandon the two factor enrollment
+    pub fn cancel_totp_enrollment(&mut self) {
+        self.account.cancel_totp();
+        self.totp_enrollment = None;
+        self.clear_profile_code();
+        self.profile = Profile::OldPassword;
     }
 
-    /// User Service - Modify a user's password
-    /// - password : current password
-    /// - new_password : new password
-    pub fn change_account_password(&mut self, password: String, new_password: String) -> bool {
-        // let account = Account::new();
-        let response = self.account.change_password(password, new_password);
+    /// User Service - Get the two factor enrollment waiting for confirmation
+    pub fn get_totp_enrollment(&self) -> Option<&TotpEnrollment> {
+        self.totp_enrollment.as_ref()
+    }
 
-        match response.validity {
-            true => {
-                true
+    /// User Service - Check if two factor authentication is enabled for the account
+    pub fn get_totp_enabled(&self) -> bool {
+        self.totp_enabled
+    }
+
+    /// User Service - Replace the recovery codes of the account, the new codes are shown once
+    pub fn regenerate_recovery_codes(&mut self) -> Result<(), AuthError> {
+        if !self.totp_enabled {
+            let error = AuthError::PolicyViolation(vec!["Two-factor authentication is not enabled".to_string()]);
+            self.set_new_secure_password_error(error.clone());
+            return Err(error);
+        }
+
+        match self.account.generate_recovery_codes() {
+            Ok(codes) => {
+                self.recovery_codes_remaining = codes.len();
+                self.recovery_codes = Some(codes);
+                Ok(())
             },
-            false => {
-                self.set_new_secure_password_error_message(response.message);
-                false
+            Err(error) => {
+                self.set_new_secure_password_error(error.clone());
+                Err(error)
             }
         }
     }
 
+    /// User Service - Get the recovery codes just generated
+    pub fn get_recovery_codes(&self) -> Option<&[SecretString]> {
+        self.recovery_codes.as_deref()
+    }
+
+    /// User Service - Hide the recovery codes just generated, they cannot be shown again
+    pub fn dismiss_recovery_codes(&mut self) {
+        self.recovery_codes = None;
+    }
+
+    /// User Service - Get how many recovery codes of the account were not used yet
+    pub fn get_recovery_codes_remaining(&self) -> usize {
+        self.recovery_codes_remaining
+    }
+
+    /// User Service - Get the unix time the password of the account was last changed
+    pub fn get_password_changed_at(&self) -> Option<i64> {
+        self.account.get_password_changed_at()
+    }
+
+    /// User Service - Load the audit events of the logged in account and check the hash chain
+    pub fn refresh_activity(&mut self) {
+        let audit = AuditLog::new(self.database.clone());
+        let events = audit.get_events(self.account.get_account_id().as_str()).unwrap_or_default();
+
+        self.activity = StateList::all_items(events);
+        self.activity.next();
+        self.activity_status = audit.verify().ok();
+    }
+
+    /// User Service - Get the state of the audit log hash chain, None if it could not be read
+    pub fn get_activity_status(&self) -> Option<ChainStatus> {
+        self.activity_status
+    }
+
+    /// User Service - Reload the username lists and settings without a restart, accounts with
+    /// the admin role only. The lists in use are kept if a file is invalid
+    pub fn reload_blocklists(&mut self) {
+        if self.account.get_role() != ADMIN_ROLE {
+            self.blocklists_status = Some(Err("Only administrators can reload the username lists".to_string()));
+            return;
+        }
+
+        self.blocklists_status = Some(match Blocklists::reload() {
+            Ok(blocklists) => Ok(format!("Username lists reloaded, strictness {}", blocklists.strictness().as_str())),
+            Err(error) => Err(format!("Reload failed, the previous lists are kept: {}", error)),
+        });
+    }
+
+    /// User Service - Get the outcome of the last reload of the username lists, None until reloaded
+    pub fn get_blocklists_status(&self) -> Option<&Result<String, String>> {
+        self.blocklists_status.as_ref()
+    }
+
+    /// User Service - Get one time code of the enrollment
+    pub fn get_profile_code(&self) -> String {
+        self.profile_code.clone()
+    }
+
+    /// User Service - Add a digit to the one time code of the enrollment
+    pub fn set_profile_code(&mut self, character: char) {
+        if character.is_ascii_digit() {
+            self.profile_code.push(character);
+        }
+    }
+
+    /// User Service - Remove a digit from the one time code of the enrollment
+    pub fn pop_profile_code(&mut self) {
+        self.profile_code.pop();
+    }
+
+    /// User Service - Clear one time code of the enrollment
+    pub fn clear_profile_code(&mut self) {
+        self.profile_code.clear();
+    }
+
+    /// User Service - Modify a user's password once the new password matches its confirmation,
+    /// the account checks it like at signup and every reason it is refused for is reported at once
+    /// - password : current password
+    /// - new_password : new password
+    /// - confirm_password : new password typed again
+    pub fn change_account_password(&mut self, password: &SecretString, new_password: &SecretString, confirm_password: &SecretString) -> Result<(), AuthError> {
+        self.password_changed = false;
+
+        // The rules of the new password are checked by the account along with the current password
+        let result = match new_password.expose() == confirm_password.expose() {
+            true => self.account.change_password(password, new_password),
+            false => Err(AuthError::PolicyViolation(vec!["New passwords do not match".to_string()])),
+        };
+
+        match &result {
+            Ok(()) => {
+                self.clear_new_secure_password_error();
+                self.password_changed = true;
+            },
+            Err(error) => self.set_new_secure_password_error(error.clone()),
+        }
+        result
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::security::database::{DatabaseManager, DatabasePath, MigrationManager};
+    use super::super::security::session::{Clock, ManualClock};
+
+    const PASSWORD: &str = "Correct-horse-battery-staple-42";
+
+    /// User signed in to a session timed by a clock the test moves forward
+    fn signed_in<'a>() -> (Database, ManualClock, User<'a>) {
+        let mut database = Database::open(&DatabasePath::Memory).unwrap();
+        database.migrate(false).unwrap();
+
+        let mut user = User::new(database.clone());
+        user.account.create_account("alice".to_string(), &PASSWORD.into()).unwrap();
+        user.account.login("alice".to_string(), &PASSWORD.into()).unwrap();
+
+        let clock = ManualClock::new(1_700_000_000);
+        let session = Session::start_with_clock(
+            database.clone(), user.account.get_account_id().as_str(), Box::new(clock.clone())).unwrap();
+        user.session = Some(session);
+        user.tab = TabsState::new(MEMBER.to_vec());
+
+        (database, clock, user)
+    }
+
+    #[test]
+    fn locks_idle_session() {
+        let (_, clock, mut user) = signed_in();
+
+        clock.advance(4 * 60);
+        user.check_session();
+        assert!(!user.get_session_locked());
+        user.touch_session();
+
+        clock.advance(4 * 60);
+        user.check_session();
+        assert!(!user.get_session_locked());
+
+        clock.advance(60);
+        user.check_session();
+        assert!(user.get_session_locked());
+        assert!(user.get_signed_in());
+        assert!(user.account.get_vault_key().is_none());
+    }
+
+    #[test]
+    fn signs_out_of_expired_session() {
+        let (database, clock, mut user) = signed_in();
+
+        // Input all along does not extend the session
+        for _ in 0..12 * 60 {
+            clock.advance(60);
+            user.touch_session();
+            user.check_session();
+        }
+
+        assert!(!user.get_signed_in());
+        assert!(!user.get_session_locked());
+        assert_eq!(user.get_login_error(), Some(AuthError::SessionExpired));
+        assert_eq!(user.tab.titles, VISITOR.to_vec());
+        assert_eq!(user.tab.titles[user.tab.index], "Login");
+
+        let ended_at: Option<i64> = database.connection()
+            .query_row("SELECT `ended_at` FROM `sessions`", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(ended_at, Some(clock.now()));
+    }
+}
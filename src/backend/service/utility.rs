@@ -1,2 +1,5 @@
+pub mod arguments;
 pub mod constants;
-pub mod response;
+pub mod error;
+pub mod logger;
+pub mod paths;
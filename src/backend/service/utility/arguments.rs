@@ -0,0 +1,135 @@
+use std::fmt;
+
+use super::logger::Level;
+
+/// Environment variable holding the location of the database
+const DATABASE_ENV: &str = "TERMAUTH_DATABASE";
+
+/// Usage shown for invalid arguments
+pub const USAGE: &str = "usage: termauth-rs [--database <path>] [--log-level <level>] [--migrate-only] [--dry-run]
+       termauth-rs breach import <file> [--output <path>] [--bloom]
+  --database      database file, `:memory:` for a database discarded on exit
+                  (default: $TERMAUTH_DATABASE, else $XDG_DATA_HOME/termauth/database.db)
+  --log-level     error, warn, info, debug or trace, written to $XDG_STATE_HOME/termauth/termauth.log
+                  (default: info)
+  --migrate-only  apply pending database migrations and exit
+  --dry-run       with --migrate-only, list pending migrations without applying them
+  breach import   build the breached password index from a file of SHA-1 or NTLM hashes sorted by
+                  hash, in the Have I Been Pwned downloadable format, then exit
+  --output        with breach import, index file (default: breach_index of the password policy)
+  --bloom         with breach import, also build a Bloom filter next to the index";
+
+/// Command line arguments
+/// - database : location of the database, None for the default location
+/// - log_level : most verbose level written to the log
+/// - migrate_only : apply pending migrations then exit without starting the interface
+/// - dry_run : check the pending migrations, rolled back once applied
+/// - command : command run instead of the interface
+pub struct Arguments {
+    pub database: Option<String>,
+    pub log_level: Level,
+    pub migrate_only: bool,
+    pub dry_run: bool,
+    pub command: Option<Command>,
+}
+
+/// Command run instead of the interface
+/// - BreachImport : build the breached password index from a hash file, see breach::import
+pub enum Command {
+    BreachImport { source: String, output: Option<String>, bloom: bool },
+}
+
+impl Default for Arguments {
+    fn default() -> Arguments {
+        Arguments {
+            database: None,
+            log_level: Level::Info,
+            migrate_only: false,
+            dry_run: false,
+            command: None,
+        }
+    }
+}
+
+/// Invalid command line argument
+/// - Unknown : argument not supported
+/// - MissingValue : option given without its value
+/// - InvalidValue : option given with a value it does not accept
+#[derive(Debug)]
+pub enum ArgumentError {
+    Unknown(String),
+    MissingValue(String),
+    InvalidValue(String, String),
+}
+
+impl fmt::Display for ArgumentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgumentError::Unknown(arg) => write!(f, "unknown argument `{}`\n{}", arg, USAGE),
+            ArgumentError::MissingValue(arg) => write!(f, "missing value for `{}`\n{}", arg, USAGE),
+            ArgumentError::InvalidValue(arg, value) => write!(f, "invalid value `{}` for `{}`\n{}", value, arg, USAGE),
+        }
+    }
+}
+
+impl std::error::Error for ArgumentError {}
+
+impl Arguments {
+
+    /// Parse the arguments the application was started with
+    pub fn parse() -> Result<Arguments, ArgumentError> {
+        let mut arguments = Arguments::parse_from(std::env::args().skip(1))?;
+
+        if arguments.database.is_none() {
+            arguments.database = std::env::var(DATABASE_ENV).ok().filter(|path| !path.is_empty());
+        }
+
+        Ok(arguments)
+    }
+
+    /// Parse arguments, a dry run is always migrate only
+    pub fn parse_from<I: Iterator<Item = String>>(mut args: I) -> Result<Arguments, ArgumentError> {
+        let mut arguments = Arguments::default();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--database" => match args.next() {
+                    Some(path) => arguments.database = Some(path),
+                    None => return Err(ArgumentError::MissingValue(arg)),
+                },
+                "--log-level" => match args.next() {
+                    Some(level) => match level.parse() {
+                        Ok(level) => arguments.log_level = level,
+                        Err(level) => return Err(ArgumentError::InvalidValue(arg, level)),
+                    },
+                    None => return Err(ArgumentError::MissingValue(arg)),
+                },
+                "--migrate-only" => arguments.migrate_only = true,
+                "--dry-run" => {
+                    arguments.migrate_only = true;
+                    arguments.dry_run = true;
+                }
+                "breach" => match args.next().as_deref() {
+                    Some("import") => match args.next() {
+                        Some(source) => arguments.command = Some(Command::BreachImport { source, output: None, bloom: false }),
+                        None => return Err(ArgumentError::MissingValue("breach import".to_string())),
+                    },
+                    Some(command) => return Err(ArgumentError::Unknown(format!("breach {}", command))),
+                    None => return Err(ArgumentError::MissingValue(arg)),
+                },
+                "--output" => match (&mut arguments.command, args.next()) {
+                    (Some(Command::BreachImport { output, .. }), Some(path)) => *output = Some(path),
+                    (Some(Command::BreachImport { .. }), None) => return Err(ArgumentError::MissingValue(arg)),
+                    (None, _) => return Err(ArgumentError::Unknown(arg)),
+                },
+                "--bloom" => match &mut arguments.command {
+                    Some(Command::BreachImport { bloom, .. }) => *bloom = true,
+                    None => return Err(ArgumentError::Unknown(arg)),
+                },
+                _ => return Err(ArgumentError::Unknown(arg)),
+            }
+        }
+
+        Ok(arguments)
+    }
+}
@@ -7,12 +7,14 @@ pub const SYSTEM: [&str; 5] =
     "Credential Manager"
 ];
 
-pub const MEMBER: [&str; 4] = 
+pub const MEMBER: [&str; 6] = 
 [
     "Welcome",
     "Profile",
     "Notepad", 
-    "Credential Manager"
+    "Credential Manager",
+    "Activity",
+    "Diagnostics"
 ];
 
 pub const VISITOR: [&str; 3] = 
@@ -42,18 +44,18 @@ pub const ENROLMENT_INSTRUCTIONS: &str = r#"
                 2. Usernames should only use characters from this set [a-zA-Z0-9_]
                 3. Usernames should not use swear words
                 4. Users shouldn't be able to bypass rule 3 by substituting numbers for letters.
+                5. Usernames that look like an existing username, such as b0b for Bob, are refused.
 
             B. The application follows the NIST password guidelines for rule setting and are to be 
                enforced within this application. You can find more information about these guidelines
                at the following link: 
 
                - https://pages.nist.gov/800-63-3/sp800-63b.html#sec5
-            
-            C. Prohibiting common passwords and weak passwords from a specified file can be found
-               in the following text document within this repository.
 
-               - weak.txt (~4KB)
-               - breachedpasswords.txt (~6KB)
+            C. Passwords are checked against the password policy of this installation, every rule
+               that is not met is reported at once:
+
+                {password_rules}
 
             D. The password, username, and other appropritate information in relation to this 
                application are to be stored using secure methods.
@@ -84,7 +86,7 @@ pub const ENROLMENT_INSTRUCTIONS: &str = r#"
                     [restriction]                | https://github.com/finnbear/rustrict/tree/master
                 
                 - Rate Limiting:
-                    [rate limiting]              | https://docs.rs/ratelimit/latest/ratelimit/struct.Ratelimiter.html
+                    [nist throttling]            | https://pages.nist.gov/800-63-3/sp800-63b.html#throttle
 
 
 "#;
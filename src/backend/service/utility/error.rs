@@ -0,0 +1,45 @@
+use std::{fmt, time::Duration};
+
+/// Errors of the authentication services
+/// - UnknownUser : no account exists with the given username
+/// - InvalidCredentials : the password does not match the account
+/// - LockedOut : too many failed attempts, logins are refused for the given time
+/// - CodeRequired : the password was accepted, a one time code is needed to finish the login
+/// - SessionExpired : the session outlived its lifetime, the account must log in again
+/// - PolicyViolation : the username or password breaks the policy, with every reason
+/// - UsernameTaken : an account exists with the username, or with one looking like it
+/// - Storage : the database or a key could not be read or written
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    UnknownUser,
+    InvalidCredentials,
+    LockedOut(Duration),
+    CodeRequired,
+    SessionExpired,
+    PolicyViolation(Vec<String>),
+    UsernameTaken,
+    Storage(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthError::UnknownUser => write!(f, "unknown user"),
+            AuthError::InvalidCredentials => write!(f, "invalid credentials"),
+            AuthError::LockedOut(wait) => write!(f, "locked out for {}s", wait.as_secs()),
+            AuthError::CodeRequired => write!(f, "one time code required"),
+            AuthError::SessionExpired => write!(f, "session expired"),
+            AuthError::PolicyViolation(reasons) => write!(f, "policy violation: {}", reasons.join(", ")),
+            AuthError::UsernameTaken => write!(f, "username taken"),
+            AuthError::Storage(cause) => write!(f, "storage error: {}", cause),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl From<rusqlite::Error> for AuthError {
+    fn from(error: rusqlite::Error) -> AuthError {
+        AuthError::Storage(error.to_string())
+    }
+}
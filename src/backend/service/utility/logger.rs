@@ -0,0 +1,228 @@
+use std::{fmt, fs::{self, File, OpenOptions}, io::{self, Write}, os::unix::fs::OpenOptionsExt, path::{Path, PathBuf}, str::FromStr, sync::{Mutex, OnceLock}};
+
+use chrono::Utc;
+
+use super::super::security::secret::SecretString;
+
+/// Size a log file may reach before it is rotated
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+/// Rotated log files kept next to the current one, `termauth.log.1` being the newest
+const MAX_LOG_FILES: usize = 3;
+
+/// Text written in place of a secret
+const REDACTED: &str = "[redacted]";
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Verbosity of a log line, each level includes the ones above it
+/// - Error : an operation failed
+/// - Warn : an operation was refused, or something is about to fail
+/// - Info : accounts, sessions and migrations changing state
+/// - Debug : steps of an operation
+/// - Trace : everything else
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Level::Error => write!(f, "ERROR"),
+            Level::Warn => write!(f, "WARN"),
+            Level::Info => write!(f, "INFO"),
+            Level::Debug => write!(f, "DEBUG"),
+            Level::Trace => write!(f, "TRACE"),
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<Level, String> {
+        match level.to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(level.to_string()),
+        }
+    }
+}
+
+/// A value that can be written as a field of a log line.
+///
+/// Secrets are `SecretString`s and log as `[redacted]`, byte buffers such as salts and keys
+/// have no implementation at all, so a password, hash or salt cannot reach the log file
+/// unless it is first turned into plain text on purpose.
+pub trait Loggable {
+    fn log_value(&self) -> String;
+}
+
+impl Loggable for str {
+    fn log_value(&self) -> String {
+        // Quoted and escaped, a value cannot start a new line or fake another field
+        format!("{:?}", self)
+    }
+}
+
+impl Loggable for String {
+    fn log_value(&self) -> String {
+        self.as_str().log_value()
+    }
+}
+
+impl<T: Loggable + ?Sized> Loggable for &T {
+    fn log_value(&self) -> String {
+        (**self).log_value()
+    }
+}
+
+impl<T: Loggable> Loggable for Option<T> {
+    fn log_value(&self) -> String {
+        match self {
+            Some(value) => value.log_value(),
+            None => "none".to_string(),
+        }
+    }
+}
+
+impl Loggable for SecretString {
+    fn log_value(&self) -> String {
+        REDACTED.to_string()
+    }
+}
+
+macro_rules! loggable_display {
+    ($($kind:ty),*) => {
+        $(impl Loggable for $kind {
+            fn log_value(&self) -> String {
+                self.to_string()
+            }
+        })*
+    };
+}
+
+loggable_display!(bool, i32, i64, u32, u64, usize, Level);
+
+/// Leveled logger writing to a file only readable by the current user, the file is
+/// rotated once it grows past MAX_LOG_SIZE
+/// - level : most verbose level written
+/// - path : log file
+/// - file : open log file, None if it could not be opened
+pub struct Logger {
+    level: Level,
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl Logger {
+
+    /// Start logging for the whole process, lines logged before are discarded
+    pub fn init(level: Level, path: PathBuf) -> io::Result<&'static Logger> {
+        let file = open_log(&path)?;
+
+        Ok(LOGGER.get_or_init(|| Logger {
+            level,
+            path,
+            file: Mutex::new(Some(file)),
+        }))
+    }
+
+    /// Get the logger of the process, None until it is started
+    pub fn global() -> Option<&'static Logger> {
+        LOGGER.get()
+    }
+
+    /// Check if a level is written
+    pub fn enabled(&self, level: Level) -> bool {
+        level <= self.level
+    }
+
+    /// Write a line, failures to log are ignored rather than interrupting the application
+    /// - target : part of the application the line comes from
+    /// - message : what happened
+    /// - fields : named values, see Loggable
+    pub fn log(&self, level: Level, target: &str, message: &str, fields: &[(&str, &dyn Loggable)]) {
+        if !self.enabled(level) {
+            return;
+        }
+
+        let mut line = format!("{} {:<5} {}: {}",
+            Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ"), level, target, message);
+        for (name, value) in fields {
+            line.push_str(&format!(" {}={}", name, value.log_value()));
+        }
+        line.push('\n');
+
+        let mut file = match self.file.lock() {
+            Ok(file) => file,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let size = file.as_ref().and_then(|file| file.metadata().ok()).map(|metadata| metadata.len()).unwrap_or(0);
+        if size + line.len() as u64 > MAX_LOG_SIZE {
+            *file = None;
+            rotate(&self.path);
+            *file = open_log(&self.path).ok();
+        }
+
+        if let Some(file) = file.as_mut() {
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+}
+
+/// Log a line with the logger of the process, if it was started
+pub fn log(level: Level, target: &str, message: &str, fields: &[(&str, &dyn Loggable)]) {
+    if let Some(logger) = Logger::global() {
+        logger.log(level, target, message, fields);
+    }
+}
+
+/// Log an error
+pub fn error(target: &str, message: &str, fields: &[(&str, &dyn Loggable)]) {
+    log(Level::Error, target, message, fields);
+}
+
+/// Log a warning
+pub fn warn(target: &str, message: &str, fields: &[(&str, &dyn Loggable)]) {
+    log(Level::Warn, target, message, fields);
+}
+
+/// Log an information
+pub fn info(target: &str, message: &str, fields: &[(&str, &dyn Loggable)]) {
+    log(Level::Info, target, message, fields);
+}
+
+/// Log a step of an operation
+pub fn debug(target: &str, message: &str, fields: &[(&str, &dyn Loggable)]) {
+    log(Level::Debug, target, message, fields);
+}
+
+/// Log anything else
+pub fn trace(target: &str, message: &str, fields: &[(&str, &dyn Loggable)]) {
+    log(Level::Trace, target, message, fields);
+}
+
+/// Open a log file for appending, created only readable by the current user
+fn open_log(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).mode(0o600).open(path)
+}
+
+/// Shift the rotated files up by one, the oldest is overwritten
+fn rotate(path: &Path) {
+    let rotated = |index: usize| PathBuf::from(format!("{}.{}", path.display(), index));
+
+    for index in (1..MAX_LOG_FILES).rev() {
+        let _ = fs::rename(rotated(index), rotated(index + 1));
+    }
+    let _ = fs::rename(path, rotated(1));
+}
@@ -0,0 +1,73 @@
+use std::{env, fs, io, os::unix::fs::DirBuilderExt, path::{Path, PathBuf}};
+
+/// Directory of the application within the XDG base directories
+const APP_DIR: &str = "termauth";
+
+/// File name of the database within the data directory
+const DATABASE_FILE: &str = "database.db";
+
+/// File name of the breached password index within the data directory
+const BREACH_INDEX_FILE: &str = "breach.idx";
+
+/// File name of the log within the state directory
+const LOG_FILE: &str = "termauth.log";
+
+/// File name of the password policy within the config directory
+const POLICY_FILE: &str = "policy.conf";
+
+/// Data directory of the application
+/// - `$XDG_DATA_HOME/termauth`, if set to an absolute path
+/// - `$HOME/.local/share/termauth` otherwise
+pub fn data_dir() -> PathBuf {
+    base_dir("XDG_DATA_HOME", ".local/share").join(APP_DIR)
+}
+
+/// Default location of the database file
+pub fn database_file() -> PathBuf {
+    data_dir().join(DATABASE_FILE)
+}
+
+/// Default location of the breached password index, see BreachIndex
+pub fn breach_index() -> PathBuf {
+    data_dir().join(BREACH_INDEX_FILE)
+}
+
+/// State directory of the application, holding the logs
+/// - `$XDG_STATE_HOME/termauth`, if set to an absolute path
+/// - `$HOME/.local/state/termauth` otherwise
+pub fn state_dir() -> PathBuf {
+    base_dir("XDG_STATE_HOME", ".local/state").join(APP_DIR)
+}
+
+/// Location of the current log file, rotated files are kept next to it
+pub fn log_file() -> PathBuf {
+    state_dir().join(LOG_FILE)
+}
+
+/// Config directory of the application
+/// - `$XDG_CONFIG_HOME/termauth`, if set to an absolute path
+/// - `$HOME/.config/termauth` otherwise
+pub fn config_dir() -> PathBuf {
+    base_dir("XDG_CONFIG_HOME", ".config").join(APP_DIR)
+}
+
+/// Location of the password policy, see Policy
+pub fn policy_file() -> PathBuf {
+    config_dir().join(POLICY_FILE)
+}
+
+/// Create a directory and its parents, only accessible by the current user
+pub fn create_private_dir(path: &Path) -> io::Result<()> {
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(path)
+}
+
+/// XDG base directory from its variable, relative paths are ignored as the specification requires
+fn base_dir(variable: &str, fallback: &str) -> PathBuf {
+    match env::var_os(variable).map(PathBuf::from) {
+        Some(path) if path.is_absolute() => path,
+        _ => env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(fallback),
+    }
+}
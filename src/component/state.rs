@@ -14,6 +14,10 @@ impl <T>StateList<T> {
     }
 
     pub fn next(&mut self) {
+        if self.items.is_empty() {
+            self.state.select(None);
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= self.items.len() - 1 {
@@ -28,6 +32,10 @@ impl <T>StateList<T> {
     }
 
     pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            self.state.select(None);
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -40,6 +48,10 @@ impl <T>StateList<T> {
         };
         self.state.select(Some(i));
     }
+
+    pub fn selected(&self) -> Option<&T> {
+        self.state.selected().and_then(|i| self.items.get(i))
+    }
 }
 
 pub struct TabsState<'a> {
@@ -2,7 +2,7 @@ mod ui;
 mod component;
 mod backend;
 
-use std::{error::Error, io, time::{Duration, Instant}};
+use std::{error::Error, io, path::{Path, PathBuf}, time::{Duration, Instant}};
 use component::state:: StateList;
 use crossterm::{
     terminal::{enable_raw_mode, EnterAlternateScreen, disable_raw_mode, LeaveAlternateScreen }, 
@@ -12,7 +12,8 @@ use tui::{backend::{CrosstermBackend, Backend}, Terminal};
 use backend::
 service::{
     user::{User, UserMode, SignUp, Login, CredentialManager, Profile},
-    utility::constants::SYSTEM,
+    security::{blocklists::Blocklists, breach, pepper::Pepper, policy::{Policy, DEFAULT_ROLE}, database::{AccountManager, Database, DatabasePath, DatabaseManager, MigrationManager}},
+    utility::{arguments::{Arguments, Command}, constants::SYSTEM, logger::{self, Logger}, paths},
 };
 
 
@@ -26,10 +27,10 @@ pub struct App<'a> {
 
 impl <'a>App<'a> {
 
-    fn new() -> App<'a> {
+    fn new(database: Database) -> App<'a> {
 
         App {
-            user: User::new(),
+            user: User::new(database),
             scroll: 2,
             // info: StateList::all_items(SYSTEM.to_vec()),
             // state: true,
@@ -62,6 +63,7 @@ impl <'a>App<'a> {
         match self.user.get_signed_in() {
             true => {
                 self.user.logged_in_tab.next();
+                self.on_member_tab();
             }
             false => {
                 self.user.tab.next();
@@ -74,6 +76,7 @@ impl <'a>App<'a> {
         match self.user.get_signed_in() {
             true => {
                 self.user.logged_in_tab.previous();
+                self.on_member_tab();
             }
             false => {
                 self.user.tab.previous();
@@ -82,6 +85,20 @@ impl <'a>App<'a> {
         // self.user.tab.previous();
     }
 
+    /// Load what a member tab shows when it is opened
+    fn on_member_tab(&mut self) {
+        if self.user.logged_in_tab.titles[self.user.logged_in_tab.index] == "Activity" {
+            self.user.refresh_activity();
+        }
+    }
+
+    /// Lock idle sessions and sign out of expired ones, and check the signup username
+    /// once typing paused
+    fn on_tick(&mut self) {
+        self.user.check_session();
+        self.user.check_signup_username();
+    }
+
     fn panic_hook(&mut self) {
         let hook = std::panic::take_hook();
 
@@ -91,8 +108,77 @@ impl <'a>App<'a> {
     }
 }
 
+/// Report a startup error and exit before the terminal is taken over
+fn or_exit<T, E: std::fmt::Display>(result: Result<T, E>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
 
+    let arguments = or_exit(Arguments::parse());
+
+    // Log to the state directory, never to the terminal the interface is drawn on
+    or_exit(paths::create_private_dir(&paths::state_dir()));
+    or_exit(Logger::init(arguments.log_level, paths::log_file()));
+    logger::info("main", "starting", &[("version", &env!("CARGO_PKG_VERSION")), ("log_level", &arguments.log_level)]);
+
+    // Build the breached password index offline, where the password policy reads it from
+    if let Some(Command::BreachImport { source, output, bloom }) = &arguments.command {
+        let destination = output.clone()
+            .or_else(|| or_exit(Policy::init()).rules(DEFAULT_ROLE).breach_index.clone())
+            .map(PathBuf::from)
+            .unwrap_or_else(paths::breach_index);
+        if let Some(parent) = destination.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            or_exit(paths::create_private_dir(parent));
+        }
+
+        println!("Importing {} into {}", source, destination.display());
+        let summary = or_exit(breach::import(Path::new(source), &destination, *bloom));
+        println!("Imported {} {} hashes", summary.count, summary.kind.as_str());
+        if let Some(bloom) = summary.bloom {
+            println!("Bloom filter written to {}", bloom.display());
+        }
+        return Ok(());
+    }
+
+    // Open the database shared by every service, in the data directory unless configured
+    let database_path = match &arguments.database {
+        Some(setting) => DatabasePath::from_setting(setting),
+        None => {
+            or_exit(paths::create_private_dir(&paths::data_dir()));
+            DatabasePath::File(paths::database_file())
+        }
+    };
+    let mut database = or_exit(Database::open(&database_path));
+
+    // Bring the database schema up to date before anything reads it
+    let migrations = or_exit(database.migrate(arguments.dry_run));
+    if arguments.migrate_only {
+        let verb = if arguments.dry_run { "Pending" } else { "Applied" };
+        for migration in &migrations {
+            println!("{} migration {} - {}", verb, migration.version, migration.description);
+        }
+        for (username, existing) in or_exit(database.get_username_conflicts()) {
+            println!("Username conflict - {} only differs by case from {}, it logs in with its exact username until renamed",
+                username, existing);
+        }
+        println!("Schema version {} ({} migration(s) {})", database.get_schema_version()?, migrations.len(),
+            if arguments.dry_run { "checked, nothing applied" } else { "applied" });
+        return Ok(());
+    }
+
+    // Load the pepper, the blocklists and the password policy before taking over the terminal, so an
+    // unsafe or invalid key file, blocklist or policy file is reported
+    or_exit(Pepper::init());
+    or_exit(Blocklists::init());
+    or_exit(Policy::init());
+
     // Setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -105,7 +191,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let tick_rate = Duration::from_millis(250);
 
     // Create app 
-    let app = App::new();
+    let app = App::new(database);
     let res = ui_app(&mut terminal, app, tick_rate);
 
     // Restore terminal on panic
@@ -127,9 +213,12 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // Handle errors
     if let Err(e) = res {
+        logger::error("main", "interface stopped", &[("error", &e.to_string())]);
         println!("Error: {}", e);
     }
 
+    logger::info("main", "exiting", &[]);
+
     // Return result
     Ok(())
 }
@@ -137,11 +226,17 @@ fn main() -> Result<(), Box<dyn Error>> {
 fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Duration) -> io::Result<()> {
 
     let mut last_tick = Instant::now();
+    let mut last_session_tick = Instant::now();
 
     loop {
 
         terminal.draw(|f| ui::app::draw(f, &mut app))?;
 
+        if last_session_tick.elapsed() >= tick_rate {
+            app.on_tick();
+            last_session_tick = Instant::now();
+        }
+
         let timeout = tick_rate.checked_sub(
             last_tick.elapsed()).unwrap_or_else(|| Duration::from_secs(0));
 
@@ -150,6 +245,54 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
 
             if let Event::Key(key) = event::read()? {
 
+                // A session idle until this key press is locked before the key is handled
+                app.on_tick();
+
+                // Locked session, only the lock screen takes input
+                if app.user.get_signed_in() && app.user.get_session_locked() {
+                    match key.code {
+                        KeyCode::Enter => {
+                            let _ = app.user.unlock_session();
+                        }
+
+                        KeyCode::Char(c) => {
+                            app.user.set_session_password(c);
+                        }
+
+                        KeyCode::Backspace => {
+                            app.user.pop_session_password();
+                        }
+
+                        KeyCode::Esc => {
+                            app.user.logout();
+                        }
+
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                app.user.touch_session();
+
+                // Logout and switch user, from the normal mode of every member tab
+                if app.user.get_signed_in() {
+                    if let UserMode::Normal = app.user.user_mode {
+                        match key.code {
+                            KeyCode::Char('o') => {
+                                app.user.logout();
+                                continue;
+                            }
+
+                            KeyCode::Char('s') => {
+                                app.user.switch_user();
+                                continue;
+                            }
+
+                            _ => {}
+                        }
+                    }
+                }
+
                 // ##################################################################
                 // ##################################################################
                 // ##################################################################
@@ -211,30 +354,50 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
 
                             KeyCode::Char('w') => {
 
-                                let old_password = app.user.get_old_secure_password();
-                                let new_password = app.user.get_new_secure_password();
+                                // Confirm a two factor enrollment
+                                if let Profile::Code = app.user.get_profile_mode() {
+                                    let _ = app.user.confirm_totp_enrollment();
+                                    continue;
+                                }
 
-                                match app.user.change_account_password( 
-                                    old_password,
-                                    new_password
-                                ) {
-                                        true => {
-                                            app.user.clear_old_password();
-                                            app.user.clear_old_secure_password();
-                                            app.user.clear_new_password();
-                                            app.user.clear_new_secure_password();
-                                        }
-                                        _ => {}
-                                    };
+                                let old_password = app.user.take_old_secure_password();
+                                let new_password = app.user.take_new_secure_password();
+                                let confirm_password = app.user.take_confirm_secure_password();
+
+                                let _ = app.user.change_account_password(
+                                    &old_password,
+                                    &new_password,
+                                    &confirm_password
+                                );
 
                             }
 
+                            KeyCode::Char('t') => {
+                                let _ = app.user.start_totp_enrollment();
+                            }
+
+                            KeyCode::Char('r') => {
+                                let _ = app.user.regenerate_recovery_codes();
+                            }
+
+                            KeyCode::Char('x') => {
+                                app.user.cancel_totp_enrollment();
+                                app.user.dismiss_recovery_codes();
+                            }
+
                             KeyCode::Char('j') => {
-                                app.user.set_profile_mode(Profile::NewPassword)
+                                match (app.user.get_totp_enrollment().is_some(), app.user.get_profile_mode()) {
+                                    (true, _) => app.user.set_profile_mode(Profile::Code),
+                                    (false, Profile::OldPassword) => app.user.set_profile_mode(Profile::NewPassword),
+                                    (false, _) => app.user.set_profile_mode(Profile::ConfirmPassword),
+                                }
                             }
 
                             KeyCode::Char('k') => {
-                                app.user.set_profile_mode(Profile::OldPassword)
+                                match app.user.get_profile_mode() {
+                                    Profile::ConfirmPassword => app.user.set_profile_mode(Profile::NewPassword),
+                                    _ => app.user.set_profile_mode(Profile::OldPassword),
+                                }
                             }
 
                             KeyCode::Char('h') => app.on_left(),
@@ -247,22 +410,34 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
 
                             KeyCode::Char('i') => {
 
+                                app.user.clear_password_changed();
+
                                 match app.user.get_profile_mode() {
 
                                     // Login::Username => {
                                     Profile::OldPassword => {
-                                        app.user.clear_new_secure_password_error_message();
+                                        app.user.clear_new_secure_password_error();
                                         app.user.user_mode = UserMode::OldPassword;
                                         app.user.set_profile_mode(Profile::OldPassword);
                                         // app.user.login = Login::Username;
                                     }
 
                                     Profile::NewPassword => {
-                                        app.user.clear_new_secure_password_error_message();
+                                        app.user.clear_new_secure_password_error();
                                         app.user.user_mode = UserMode::NewPassword;
                                         app.user.set_profile_mode(Profile::NewPassword);
                                         // app.user.login = Login::Password;
                                     }
+
+                                    Profile::ConfirmPassword => {
+                                        app.user.clear_new_secure_password_error();
+                                        app.user.user_mode = UserMode::ConfirmPassword;
+                                    }
+
+                                    Profile::Code => {
+                                        app.user.clear_new_secure_password_error();
+                                        app.user.user_mode = UserMode::Code;
+                                    }
                                 }
                             }
 
@@ -306,7 +481,8 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
                         UserMode::NewPassword => match key.code {
 
                             KeyCode::Enter => {
-                                app.user.user_mode = UserMode::Normal;
+                                app.user.user_mode = UserMode::ConfirmPassword;
+                                app.user.set_profile_mode(Profile::ConfirmPassword);
                             }
 
                             KeyCode::Char(c) => {
@@ -324,6 +500,60 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
                                 app.user.user_mode = UserMode::Normal;
                             }
 
+                            _ => {}
+                        }
+
+                        // ##################################################################
+                        // ##################################################################
+
+                        // Confirm Password
+                        UserMode::ConfirmPassword => match key.code {
+
+                            KeyCode::Enter => {
+                                app.user.user_mode = UserMode::Normal;
+                            }
+
+                            KeyCode::Char(c) => {
+                                let ast: char = '*';
+                                app.user.set_confirm_password(ast);
+                                app.user.set_confirm_secure_password(c);
+                            }
+
+                            KeyCode::Backspace => {
+                                app.user.pop_confirm_password();
+                                app.user.pop_confirm_secure_password();
+                            }
+
+                            KeyCode::Esc => {
+                                app.user.user_mode = UserMode::Normal;
+                            }
+
+                            _ => {}
+                        }
+
+                        // ##################################################################
+                        // ##################################################################
+
+                        // Two factor enrollment code
+                        UserMode::Code => match key.code {
+
+                            KeyCode::Enter => {
+                                app.user.user_mode = UserMode::Normal;
+                                let _ = app.user.confirm_totp_enrollment();
+                            }
+
+                            KeyCode::Char(c) => {
+                                app.user.set_profile_code(c);
+                            }
+
+                            KeyCode::Backspace => {
+                                app.user.pop_profile_code();
+                            }
+
+                            KeyCode::Esc => {
+                                app.user.user_mode = UserMode::Normal;
+                            }
+
                             _ => {}
                         } // User mode parenthesis
 
@@ -397,16 +627,29 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
                 // Credential Manager
                 else if app.user.logged_in_tab.index == 3 && app.user.get_signed_in() {
 
+                    // A delete is only confirmed by pressing d twice in a row
+                    if key.code != KeyCode::Char('d') {
+                        app.user.cancel_delete_credential();
+                    }
+
                     match app.user.user_mode {
 
                         // ##################################################################
                         // ##################################################################
                         UserMode::Normal => match key.code {
 
+                            KeyCode::Char('w') if app.user.save_credential() => {
+                                app.user.clear_credential();
+                            }
+
                             KeyCode::Char('j') => {
 
                                 match app.user.app {
 
+                                    CredentialManager::List => {
+                                        app.user.next_credential();
+                                    }
+
                                     CredentialManager::App => {
                                         app.user.app = CredentialManager::Username;
                                     }
@@ -424,6 +667,10 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
                             KeyCode::Char('k') => {
                                 match app.user.app {
 
+                                    CredentialManager::List => {
+                                        app.user.previous_credential();
+                                    }
+
                                     CredentialManager::App => {
                                         app.user.app = CredentialManager::Password;
                                     }
@@ -451,6 +698,11 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
                                 // app.user.user_mode = UserMode::App;
                                 match app.user.app {
 
+                                    CredentialManager::List => {
+                                        app.user.user_mode = UserMode::App;
+                                        app.user.app = CredentialManager::App;
+                                    }
+
                                     CredentialManager::App => {
                                         app.user.user_mode = UserMode::App;
                                         app.user.app = CredentialManager::App;
@@ -467,6 +719,60 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
                                     }
                                 }
                             }
+
+                            KeyCode::Char('/') => {
+                                app.user.app = CredentialManager::List;
+                                app.user.user_mode = UserMode::Search;
+                            }
+
+                            KeyCode::Char('v') => {
+                                app.user.toggle_reveal_credential();
+                            }
+
+                            KeyCode::Char('e') => {
+                                app.user.edit_selected_credential();
+                            }
+
+                            KeyCode::Char('d') => {
+                                if let CredentialManager::List = app.user.app {
+                                    app.user.delete_selected_credential();
+                                }
+                            }
+
+                            KeyCode::Tab => {
+                                match app.user.app {
+                                    CredentialManager::List => {
+                                        app.user.app = CredentialManager::App;
+                                    }
+                                    _ => {
+                                        app.user.app = CredentialManager::List;
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+
+                        // ##################################################################
+                        // ##################################################################
+                        UserMode::Search => match key.code {
+
+                            KeyCode::Enter => {
+                                app.user.user_mode = UserMode::Normal;
+                            }
+
+                            KeyCode::Char(c) => {
+                                app.user.push_app_search_query(c);
+                            }
+
+                            KeyCode::Backspace => {
+                                app.user.pop_app_search_query();
+                            }
+
+                            KeyCode::Esc => {
+                                app.user.clear_app_search_query();
+                                app.user.user_mode = UserMode::Normal;
+                            }
+
                             _ => {}
                         }
 
@@ -525,19 +831,15 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
                         UserMode::Password => match key.code {
 
                             KeyCode::Enter => {
-                                app.user.set_app_password(app.user.app_secure_password.to_owned());
                                 app.user.user_mode = UserMode::Normal;
                             }
 
                             KeyCode::Char(c) => {
-                                // let ast: char = '*';
-                                app.user.app_password.push(c);
-                                app.user.app_secure_password.push(c);
+                                app.user.set_app_secure_password(c);
                             }
 
                             KeyCode::Backspace => {
-                                app.user.app_password.pop();
-                                app.user.app_secure_password.pop();
+                                app.user.pop_app_secure_password();
                             }
 
                             KeyCode::Esc => {
@@ -551,6 +853,74 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
                     } // final match for app.user.user_mode
                 } // final if else for tabs = credential manager
 
+                // ##################################################################
+                // ##################################################################
+                // ##################################################################
+
+                // Activity
+                else if app.user.logged_in_tab.index == 4 && app.user.get_signed_in() {
+
+                    // ##################################################################
+                    // ##################################################################
+                    if let UserMode::Normal = app.user.user_mode {
+                        match key.code {
+
+                            KeyCode::Char('j') => {
+                                app.user.activity.next();
+                            }
+
+                            KeyCode::Char('k') => {
+                                app.user.activity.previous();
+                            }
+
+                            KeyCode::Char('r') => {
+                                app.user.refresh_activity();
+                            }
+
+                            KeyCode::Char('h') => app.on_left(),
+                            KeyCode::Char('l') => app.on_right(),
+                            KeyCode::Char('q') | KeyCode::Esc => {
+                                disable_raw_mode()?;
+                                terminal.show_cursor()?;
+                                return Ok(())
+                            }
+
+                            _ => {}
+                        }
+                    }
+                }
+
+                // ##################################################################
+                // ##################################################################
+                // ##################################################################
+                // ##################################################################
+                // ##################################################################
+
+                // Diagnostics
+                else if app.user.logged_in_tab.index == 5 && app.user.get_signed_in() {
+
+                    // ##################################################################
+                    // ##################################################################
+                    if let UserMode::Normal = app.user.user_mode {
+                        match key.code {
+
+                            KeyCode::Char('r') => {
+                                app.user.reload_blocklists();
+                            }
+
+                            KeyCode::Char('h') => app.on_left(),
+                            KeyCode::Char('l') => app.on_right(),
+                            KeyCode::Char('q') | KeyCode::Esc => {
+                                disable_raw_mode()?;
+                                terminal.show_cursor()?;
+                                return Ok(())
+                            }
+
+                            _ => {}
+                        }
+                    }
+                }
+
 
                 // ##################################################################
                 // ##################################################################
@@ -613,15 +983,13 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
 
                             KeyCode::Char('w') => {
                                 let username = app.user.get_signup_username();
-                                let password = app.user.get_signup_secure_password();
+                                let password = app.user.take_signup_secure_password();
                                 match app.user.create_account( 
                                     username,
-                                    password
+                                    &password
                                 ) {
-                                        true => {
+                                        Ok(()) => {
                                             app.user.clear_signup_username();
-                                            app.user.clear_signup_password();
-                                            app.user.clear_signup_secure_password();
                                         }
                                         _ => {}
                                     };
@@ -649,16 +1017,16 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
                                 match app.user.signup {
 
                                     SignUp::Username => {
-                                        if app.user.get_signup_username_error_message().len() > 0 {
-                                            app.user.clear_signup_username_error_message();
+                                        if app.user.get_signup_username_error().is_some() {
+                                            app.user.clear_signup_username_error();
                                         }
                                         app.user.user_mode = UserMode::Username;
                                         app.user.signup = SignUp::Username;
                                     }
 
                                     SignUp::Password => {
-                                        if app.user.get_signup_password_error_message().len() > 0 {
-                                            app.user.clear_signup_password_error_message();
+                                        if app.user.get_signup_password_error().is_some() {
+                                            app.user.clear_signup_password_error();
                                         }
                                         app.user.user_mode = UserMode::Password;
                                         app.user.signup = SignUp::Password;
@@ -744,30 +1112,39 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
 
                             KeyCode::Char('w') => {
 
+                                // Second step of a login with two factor authentication
+                                if app.user.get_login_code_required() {
+                                    if app.user.verify_login_code().is_ok() {
+                                        app.user.clear_login_username();
+                                    }
+                                    continue;
+                                }
+
                                 let username = app.user.get_login_username();
-                                let password = app.user.get_login_secure_password();
+                                let password = app.user.take_login_secure_password();
 
                                 match app.user.login( 
                                     username,
-                                    password
+                                    &password
                                 ) {
-                                        true => {
+                                        Ok(()) => {
                                             app.user.clear_login_username();
-                                            app.user.clear_login_password();
-                                            app.user.clear_login_secure_password();
-                                            app.user.set_signed_in(true);
                                         }
                                         _ => {}
                                     };
 
                             }
 
-                            KeyCode::Char('j') => {
+                            KeyCode::Char('x') => {
+                                app.user.cancel_login_code();
+                            }
+
+                            KeyCode::Char('j') if !app.user.get_login_code_required() => {
                                 app.user.set_login_mode(Login::Password);
                                 // app.user.login = Login::Password;
                             }
 
-                            KeyCode::Char('k') => {
+                            KeyCode::Char('k') if !app.user.get_login_code_required() => {
                                 app.user.set_login_mode(Login::Username);
                                 // app.user.login = Login::Username;
                             }
@@ -785,18 +1162,23 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
                                 match app.user.get_login_mode() {
 
                                     Login::Username => {
-                                        app.user.clear_login_error_message();
+                                        app.user.clear_login_error();
                                         app.user.user_mode = UserMode::Username;
                                         app.user.set_login_mode(Login::Username);
                                         // app.user.login = Login::Username;
                                     }
 
                                     Login::Password => {
-                                        app.user.clear_login_error_message();
+                                        app.user.clear_login_error();
                                         app.user.user_mode = UserMode::Password;
                                         app.user.set_login_mode(Login::Password);
                                         // app.user.login = Login::Password;
                                     }
+
+                                    Login::Code => {
+                                        app.user.clear_login_error();
+                                        app.user.user_mode = UserMode::Code;
+                                    }
                                 }
                             }
 
@@ -855,6 +1237,34 @@ fn ui_app<B: Backend>(terminal: &mut Terminal<B>, mut app: App, tick_rate: Durat
                                 app.user.user_mode = UserMode::Normal;
                             }
 
+                            _ => {}
+                        }
+
+                        // ##################################################################
+                        // ##################################################################
+
+                        // Login Code
+                        UserMode::Code => match key.code {
+
+                            KeyCode::Enter => {
+                                app.user.user_mode = UserMode::Normal;
+                                if app.user.verify_login_code().is_ok() {
+                                    app.user.clear_login_username();
+                                }
+                            }
+
+                            KeyCode::Char(c) => {
+                                app.user.set_login_code(c);
+                            }
+
+                            KeyCode::Backspace => {
+                                app.user.pop_login_code();
+                            }
+
+                            KeyCode::Esc => {
+                                app.user.user_mode = UserMode::Normal;
+                            }
+
                             _ => {}
                         } // User mode parenthesis
 
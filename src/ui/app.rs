@@ -11,7 +11,7 @@ use tui::{
     text::{Span, Spans}};
 
 use crate::App;
-use super::tabs::{welcome, signup, login, notepad, profile, credential_manager};
+use super::tabs::{welcome, signup, login, lock, notepad, profile, credential_manager, activity, diagnostics};
 
 pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     let chunks = Layout::default()
@@ -48,13 +48,18 @@ pub fn draw<B: Backend>(f: &mut Frame<B>, app: &mut App) {
     // Render tabs with respective UI
     // f.render_widget(tabs, chunks[0]);
 
-    if app.user.get_signed_in() {
+    if app.user.get_signed_in() && app.user.get_session_locked() {
+        f.render_widget(logged_in_tab, chunks[0]);
+        lock::draw_lock(f, app, chunks[1]);
+    } else if app.user.get_signed_in() {
         f.render_widget(logged_in_tab, chunks[0]);
         match app.user.logged_in_tab.index {
             0 => welcome::draw_welcome(f, app, chunks[1]),
             1 => profile::draw_profile(f, app, chunks[1]),
             2 => notepad::draw_notepad(f, app, chunks[1]),
             3 => credential_manager::draw_credential_manager(f, app, chunks[1]),
+            4 => activity::draw_activity(f, app, chunks[1]),
+            5 => diagnostics::draw_diagnostics(f, app, chunks[1]),
             _ => {}
         }
     } else {
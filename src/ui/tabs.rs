@@ -1,7 +1,10 @@
 pub mod welcome;
 pub mod signup;
 pub mod login;
+pub mod lock;
 pub mod notepad;
 pub mod profile;
 pub mod credential_manager;
+pub mod activity;
+pub mod diagnostics;
 pub mod utility;
@@ -0,0 +1,88 @@
+use chrono::{Local, TimeZone};
+use tui::{backend::Backend, Frame,
+    layout::{Rect, Constraint, Layout},
+    widgets::{Paragraph, Block, Borders, BorderType, List, ListItem},
+    style::{Style, Color, Modifier},
+    text::{Span, Spans},
+};
+use crate::{App, backend::service::security::audit::ChainStatus};
+use super::utility::helper::draw_help_activity;
+
+pub fn draw_activity<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Length(2),
+                Constraint::Min(1),
+                Constraint::Length(10),
+            ].as_ref(),
+        ).split(area);
+
+    draw_activity_block(f, app, chunks[1]);
+    draw_help_activity(f, app, chunks[2]);
+
+}
+
+fn draw_activity_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Min(1),
+            ].as_ref(),
+        ).split(area);
+
+    // State of the hash chain, checked over the events of every account
+    let status = match app.user.get_activity_status() {
+        Some(ChainStatus::Intact) => Span::styled(
+            "Audit log intact, every event is chained to the one before it", Style::default().fg(Color::Green)),
+        Some(ChainStatus::Broken(id)) => Span::styled(
+            format!("Audit log tampered with, event #{} was edited or events before it were deleted", id),
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+        Some(ChainStatus::Truncated) => Span::styled(
+            "Audit log tampered with, the latest events were deleted",
+            Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+        None => Span::styled("Audit log could not be read", Style::default().fg(Color::Red)),
+    };
+
+    let status = Paragraph::new(Spans::from(status))
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Integrity"));
+
+    f.render_widget(status, chunks[0]);
+
+    let events: Vec<ListItem> = app
+        .user
+        .activity
+        .items
+        .iter()
+        .map(|record| {
+            let created_at = Local.timestamp_opt(record.created_at, 0)
+                .single()
+                .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default();
+
+            let outcome = match record.outcome.as_str() {
+                "success" => Style::default().fg(Color::Green),
+                "pending" => Style::default().fg(Color::Yellow),
+                _ => Style::default().fg(Color::Red),
+            };
+
+            ListItem::new(Spans::from(vec![
+                Span::raw(format!("{}  ", created_at)),
+                Span::styled(format!("{:<16}", record.event), Style::default().add_modifier(Modifier::BOLD)),
+                Span::styled(format!("{:<9}", record.outcome), outcome),
+                Span::raw(record.detail.clone()),
+            ]))
+        }).collect();
+
+    let title = format!("Activity ({})", app.user.activity.items.len());
+
+    let events = List::new(events)
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title(title))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+
+    f.render_stateful_widget(events, chunks[1], &mut app.user.activity.state);
+}
@@ -1,12 +1,13 @@
-use tui::{backend::Backend, Frame, 
-    layout::{Rect, Constraint, Layout}, 
-    widgets::{Paragraph, Block, Borders, BorderType}, 
-    style::{Style, Color}
+use tui::{backend::Backend, Frame,
+    layout::{Rect, Constraint, Layout, Direction},
+    widgets::{Paragraph, Block, Borders, BorderType, List, ListItem},
+    style::{Style, Color, Modifier},
+    text::{Span, Spans},
 };
-use crate::{App, component::block::centered_rect_a, 
-    backend::service::user::CredentialManager
+use crate::{App,
+    backend::service::user::{CredentialManager, UserMode}
 };
-use super::utility::helper::draw_help_welcome;
+use super::utility::helper::draw_help_credential_manager;
 
 pub fn draw_credential_manager<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 
@@ -15,7 +16,7 @@ pub fn draw_credential_manager<B: Backend>(f: &mut Frame<B>, app: &mut App, area
             [
                 Constraint::Length(2),
                 Constraint::Min(1),
-                Constraint::Length(11),
+                Constraint::Length(17),
             ].as_ref(),
         ).split(area);
 
@@ -30,24 +31,148 @@ pub fn draw_credential_manager<B: Backend>(f: &mut Frame<B>, app: &mut App, area
 
     // draw_help_popup(f, app, chunks[2]);
     draw_credential_manager_block(f, app, chunks[1]);
-    draw_help_welcome(f, app, chunks[2]);
+    draw_help_credential_manager(f, app, chunks[2]);
 
 }
 
 fn draw_credential_manager_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 
-    let app_pos = centered_rect_a(40, 8, f.size());
-    let username_pos = Rect::new(app_pos.left(), app_pos.bottom(), app_pos.width, 4);
-    let password_pos = Rect::new(username_pos.left(), username_pos.bottom(), username_pos.width, 4);
+    let chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage(40),
+                Constraint::Percentage(60),
+            ].as_ref(),
+        ).split(area);
+
+    let detail_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Min(7),
+                Constraint::Length(9),
+            ].as_ref(),
+        ).split(chunks[1]);
+
+    draw_credential_list(f, app, chunks[0]);
+    draw_credential_detail(f, app, detail_chunks[0]);
+    draw_credential_form(f, app, detail_chunks[1]);
+}
+
+fn draw_credential_list<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Min(1),
+            ].as_ref(),
+        ).split(area);
+
+    // Search prompt, filters the list as you type
+    let search_input = Paragraph::new(format!("/{}", app.user.app_search_query))
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Search"))
+        .style(match app.user.user_mode {
+            UserMode::Search => Style::default().fg(Color::Yellow),
+            _ => Style::default(),
+        });
+
+    f.render_widget(search_input, chunks[0]);
+
+    if let UserMode::Search = app.user.user_mode {
+        f.set_cursor(
+            chunks[0].x + app.user.app_search_query.len() as u16 + 2,
+            chunks[0].y + 1,
+        )
+    }
+
+    let credentials: Vec<ListItem> = app
+        .user
+        .app_search_list
+        .items
+        .iter()
+        .map(|record| {
+            ListItem::new(Spans::from(vec![
+                Span::styled(record.app.clone(), Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(" - "),
+                Span::raw(record.username.clone()),
+            ]))
+        }).collect();
+
+    let title = format!("Credentials ({}/{})",
+        app.user.app_search_list.items.len(), app.user.app_credentials_count());
+
+    let credentials = List::new(credentials)
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title(title))
+        .style(match app.user.app {
+            CredentialManager::List => Style::default().fg(Color::Magenta),
+            _ => Style::default(),
+        })
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+        .highlight_symbol("> ");
+
+    f.render_stateful_widget(credentials, chunks[1], &mut app.user.app_search_list.state);
+}
+
+fn draw_credential_detail<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+
+    let label = Style::default().fg(Color::LightBlue);
+
+    let text = match app.user.get_selected_credential() {
+        Some(record) => {
+            let password = app.user.get_revealed_password()
+                .unwrap_or("********");
+
+            let mut text = vec![
+                Spans::from(vec![Span::styled("App      : ", label), Span::raw(record.app.clone())]),
+                Spans::from(vec![Span::styled("Username : ", label), Span::raw(record.username.clone())]),
+                Spans::from(vec![Span::styled("Password : ", label), Span::raw(password)]),
+                Spans::from(vec![Span::styled("Created  : ", label), Span::raw(record.created_at.clone())]),
+                Spans::from(vec![Span::styled("Updated  : ", label), Span::raw(record.updated_at.clone())]),
+            ];
+
+            if app.user.get_delete_pending() {
+                text.push(Spans::from(Span::raw("")));
+                text.push(Spans::from(Span::styled("Press d again to delete this credential", Style::default().fg(Color::Red))));
+            }
+            text
+        }
+        None => vec![Spans::from(Span::raw("No credential selected"))],
+    };
+
+    let detail = Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Details"));
+
+    f.render_widget(detail, area);
+}
+
+fn draw_credential_form<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(3),
+            ].as_ref(),
+        ).split(area);
+
+    let title = match app.user.get_editing_credential() {
+        Some(editing) => format!("App (editing {})", editing),
+        None => String::from("App"),
+    };
 
     let app_input = Paragraph::new(app.user.app_name.as_ref())
-        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("App"))
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title(title))
         .style( match app.user.app {
                 CredentialManager::App => Style::default().fg(Color::Magenta),
                 _ => Style::default(),
         });
 
-    f.render_widget(app_input, app_pos);
+    f.render_widget(app_input, chunks[0]);
 
     let username_input = Paragraph::new(app.user.app_username.as_ref())
         .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Username"))
@@ -56,7 +181,7 @@ fn draw_credential_manager_block<B: Backend>(f: &mut Frame<B>, app: &mut App, ar
             _ => Style::default(),
         });
 
-    f.render_widget(username_input, username_pos);
+    f.render_widget(username_input, chunks[1]);
 
     let password_input = Paragraph::new(app.user.app_password.as_ref())
         .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Password"))
@@ -65,6 +190,6 @@ fn draw_credential_manager_block<B: Backend>(f: &mut Frame<B>, app: &mut App, ar
             _ => Style::default(),
         });
 
-    f.render_widget(password_input, password_pos);
+    f.render_widget(password_input, chunks[2]);
 
 }
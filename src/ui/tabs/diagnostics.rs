@@ -0,0 +1,101 @@
+use tui::{backend::Backend, Frame,
+    layout::{Rect, Constraint, Layout},
+    widgets::{Paragraph, Block, Borders, BorderType, List, ListItem},
+    style::{Style, Color, Modifier},
+    text::{Span, Spans},
+};
+use crate::{App, backend::service::security::{blocklists::Blocklists, policy::{Policy, DEFAULT_ROLE}}};
+use super::utility::helper::draw_help_diagnostics;
+
+pub fn draw_diagnostics<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Length(2),
+                Constraint::Min(1),
+                Constraint::Length(8),
+            ].as_ref(),
+        ).split(area);
+
+    draw_diagnostics_block(f, app, chunks[1]);
+    draw_help_diagnostics(f, app, chunks[2]);
+
+}
+
+fn draw_diagnostics_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(4),
+                Constraint::Min(1),
+            ].as_ref(),
+        ).split(area);
+
+    let blocklists = Blocklists::global();
+
+    // Breach index of the default role, opened once with the policy
+    let breach = match Policy::global().rules(DEFAULT_ROLE).breached_count() {
+        Some(count) => Span::styled(format!("{} hashes indexed", count), Style::default().fg(Color::Green)),
+        None => Span::styled("No index, only the blocklists are checked", Style::default().fg(Color::Yellow)),
+    };
+
+    let breach = Paragraph::new(Spans::from(breach))
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Breach index"));
+
+    f.render_widget(breach, chunks[0]);
+
+    // Settings of the username filter and the outcome of the last reload
+    let settings = match blocklists.settings().is_empty() {
+        true => "defaults".to_string(),
+        false => blocklists.settings().iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(", "),
+    };
+    let status = match app.user.get_blocklists_status() {
+        Some(Ok(message)) => Span::styled(message.as_str(), Style::default().fg(Color::Green)),
+        Some(Err(message)) => Span::styled(message.as_str(), Style::default().fg(Color::Red)),
+        None => Span::styled("Loaded at startup", Style::default().fg(Color::DarkGray)),
+    };
+
+    let filter = Paragraph::new(vec![
+        Spans::from(vec![
+            Span::styled("Strictness: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(format!("{} ", blocklists.strictness().as_str())),
+            Span::styled(format!("({})", settings), Style::default().fg(Color::DarkGray)),
+        ]),
+        Spans::from(status),
+    ]).block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Username filter"));
+
+    f.render_widget(filter, chunks[1]);
+
+    // Every list with its merged size, followed by the sources it was merged from
+    let mut items: Vec<ListItem> = Vec::new();
+    for list in blocklists.lists() {
+        let size = match list.is_empty() {
+            true => Span::styled("empty", Style::default().fg(Color::Yellow)),
+            false => Span::styled(format!("{} entries", list.len()), Style::default().fg(Color::Green)),
+        };
+
+        items.push(ListItem::new(Spans::from(vec![
+            Span::styled(format!("{:<20}", list.kind.title()), Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled(format!("{:<24}", list.kind.file_name()), Style::default().fg(Color::DarkGray)),
+            size,
+        ])));
+
+        for source in list.sources() {
+            let path = source.path.as_ref().map(|path| path.display().to_string()).unwrap_or_else(|| "compiled in".to_string());
+            items.push(ListItem::new(Spans::from(vec![
+                Span::raw(format!("    {:<10}", source.origin.as_str())),
+                Span::raw(format!("{:<8}", source.entries)),
+                Span::styled(path, Style::default().fg(Color::DarkGray)),
+            ])));
+        }
+        items.push(ListItem::new(""));
+    }
+
+    let lists = List::new(items)
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Blocklists"));
+
+    f.render_widget(lists, chunks[2]);
+}
@@ -0,0 +1,58 @@
+use tui::{backend::Backend, Frame, 
+    layout::{Rect, Constraint, Layout, Alignment}, 
+    widgets::{Block, Borders, BorderType, Paragraph}, 
+    style::{Style, Color, Modifier}, text::{Span, Spans}
+};
+
+use crate::{App, component::block::centered_rect_a};
+use super::utility::{helper::draw_help_lock, message::auth_error_message};
+
+pub fn draw_lock<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Length(2),
+                Constraint::Min(1),
+                Constraint::Length(6),
+            ].as_ref(),
+        ).split(area);
+
+    draw_lock_block(f, app);
+    draw_help_lock(f, app, chunks[2]);
+
+}
+
+fn draw_lock_block<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+
+    let message_pos = centered_rect_a(30, 8, f.size());
+    let password_pos = Rect::new(message_pos.left(), message_pos.bottom(), message_pos.width, 3);
+    let error_message_pos = Rect::new(
+        password_pos.left(), 
+        password_pos.bottom(), 
+        password_pos.width, 3);
+
+    // locked message
+    let message = Paragraph::new(vec![
+        Spans::from(Span::styled("Session locked", Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow))),
+        Spans::from(Span::raw(format!("Enter the password of {} to continue", app.user.get_account_username()))),
+    ]).alignment(Alignment::Center);
+
+    f.render_widget(message, Rect::new(message_pos.left(), message_pos.bottom().saturating_sub(2), message_pos.width, 2));
+
+    // password block, always in insert mode
+    let password_input = Paragraph::new(app.user.get_session_password())
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Password"))
+        .style(Style::default().fg(Color::Yellow));
+
+    f.render_widget(password_input, password_pos);
+
+    // Error of the last attempt
+    if let Some(error) = app.user.get_session_error() {
+        let error_message = Paragraph::new(Spans::from(vec![
+            Span::styled("System Error: ", Style::default().add_modifier(Modifier::BOLD).fg(Color::Red)),
+            Span::styled(auth_error_message(&error), Style::default().fg(Color::Red)),
+        ])).alignment(Alignment::Center);
+        f.render_widget(error_message, error_message_pos);
+    }
+}
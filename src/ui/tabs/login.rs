@@ -6,9 +6,9 @@ use tui::{backend::Backend, Frame,
 
 use crate::{
     App,
-    backend::service::user::{Login, UserMode},
+    backend::service::{user::{Login, UserMode}, security::rate_limiter::format_wait},
     component::block::centered_rect_a};
-use super::utility::helper::draw_help_login;
+use super::utility::{helper::draw_help_login, message::auth_error_message};
 
 pub fn draw_login<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 
@@ -17,7 +17,7 @@ pub fn draw_login<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
             [
                 Constraint::Length(2),
                 Constraint::Min(1),
-                Constraint::Length(11),
+                Constraint::Length(12),
             ].as_ref(),
         ).split(area);
 
@@ -40,16 +40,29 @@ fn draw_login_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 
     let username_pos = centered_rect_a(30, 8, f.size());
     let password_pos = Rect::new(username_pos.left(), username_pos.bottom(), username_pos.width, 3);
-    let user_mode_pos = Rect::new(
+
+    // The code input only takes room while a login waits for it
+    let code_pos = Rect::new(
         password_pos.left(), 
         password_pos.bottom(), 
-        password_pos.width, 3);
+        password_pos.width, 
+        if app.user.get_login_code_required() { 3 } else { 0 });
+
+    let user_mode_pos = Rect::new(
+        code_pos.left(), 
+        code_pos.bottom(), 
+        code_pos.width, 3);
 
     let error_message_pos = Rect::new(
         user_mode_pos.left(), 
         user_mode_pos.bottom(), 
         user_mode_pos.width, 3);
 
+    let wait_message_pos = Rect::new(
+        error_message_pos.left(), 
+        error_message_pos.bottom(), 
+        error_message_pos.width, 3);
+
     let color_mode_error = Style::default().fg(Color::Red);
 
     // username block
@@ -85,6 +98,17 @@ fn draw_login_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 
     f.render_widget(password_input, password_pos);
 
+    // one time code block
+    if app.user.get_login_code_required() {
+        let code_input = Paragraph::new(app.user.get_login_code())
+            .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Code or recovery code"))
+            .style(match app.user.user_mode {
+                UserMode::Code => color_mode_insert,
+                _ => color_mode_normal,
+            });
+
+        f.render_widget(code_input, code_pos);
+    }
 
     let (msg, style) = match app.user.user_mode {
         UserMode::Normal => (
@@ -110,6 +134,14 @@ fn draw_login_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
             ],
             Style::default()
         ),
+
+        UserMode::Code => (
+            vec![
+                Span::raw("You are now in "),
+                Span::styled("Insert Mode", Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow)),
+            ],
+            Style::default()
+        ),
         _ => (vec![], Style::default()),
     };
 
@@ -119,7 +151,7 @@ fn draw_login_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     f.render_widget(help_message, user_mode_pos);
 
     // Error blinkers
-    let error_response = app.user.get_login_error_message();
+    let error_response = app.user.get_login_error().map(|error| auth_error_message(&error)).unwrap_or_default();
     let (error_message, error_message_style) = match app.user.user_mode {
         UserMode::Normal => (
             vec![
@@ -135,7 +167,7 @@ fn draw_login_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
             ],
             Style::default().add_modifier(Modifier::RAPID_BLINK),
         ),
-        UserMode::Password => (
+        UserMode::Password | UserMode::Code => (
             vec![
                 Span::styled("System Error: ", Style::default().add_modifier(Modifier::BOLD).fg(Color::Red)),
                 Span::styled(error_response, Style::default().fg(Color::Red)),
@@ -149,7 +181,16 @@ fn draw_login_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     user_error.patch_style(error_message_style);
 
     let user_error_help_message = Paragraph::new(user_error).alignment(Alignment::Center);
-    if app.user.get_login_error_message().len() > 0 {
+    if app.user.get_login_error().is_some() {
         f.render_widget(user_error_help_message, error_message_pos);
     }
+
+    // Remaining wait while login attempts are throttled
+    if let Some(wait) = app.user.get_login_wait() {
+        let wait_message = Paragraph::new(Spans::from(vec![
+            Span::styled("Locked: ", Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow)),
+            Span::styled(format!("try again in {}", format_wait(wait)), Style::default().fg(Color::Yellow)),
+        ])).alignment(Alignment::Center);
+        f.render_widget(wait_message, wait_message_pos);
+    }
 }
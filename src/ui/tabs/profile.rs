@@ -1,14 +1,15 @@
+use chrono::{Local, TimeZone, Utc};
 use tui::{backend::Backend, Frame, 
     layout::{Rect, Constraint, Layout, Alignment}, 
-    widgets::{Block, Borders, BorderType, Paragraph}, 
+    widgets::{Block, Borders, BorderType, Clear, Paragraph}, 
     style::{Style, Color, Modifier}, text::{Span, Spans, Text}
 };
 
 use crate::{
     App,
-    backend::service::user::{UserMode, Profile},
+    backend::service::{user::{UserMode, Profile}, utility::error::AuthError},
     component::block::centered_rect_a};
-use super::utility::helper::draw_help_profile;
+use super::utility::{helper::draw_help_profile, message::auth_error_message, strength::{draw_strength_meter, STRENGTH_HEIGHT}};
 
 pub fn draw_profile<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 
@@ -17,7 +18,7 @@ pub fn draw_profile<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
             [
                 Constraint::Length(2),
                 Constraint::Min(1),
-                Constraint::Length(11),
+                Constraint::Length(15),
             ].as_ref(),
         ).split(area);
 
@@ -30,15 +31,22 @@ fn draw_profile_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 
     let old_password_pos = centered_rect_a(30, 8, f.size());
     let new_password_pos = Rect::new(old_password_pos.left(), old_password_pos.bottom(), old_password_pos.width, 3);
+    let strength_pos = Rect::new(new_password_pos.left(), new_password_pos.bottom(), new_password_pos.width, STRENGTH_HEIGHT);
+    let confirm_password_pos = Rect::new(strength_pos.left(), strength_pos.bottom(), strength_pos.width, 3);
     let user_mode_pos = Rect::new(
-        new_password_pos.left(), 
-        new_password_pos.bottom(), 
-        new_password_pos.width, 3);
+        confirm_password_pos.left(), 
+        confirm_password_pos.bottom(), 
+        confirm_password_pos.width, 3);
 
-    let error_message_pos = Rect::new(
+    let two_factor_pos = Rect::new(
         user_mode_pos.left(), 
         user_mode_pos.bottom(), 
-        user_mode_pos.width, 3);
+        user_mode_pos.width, 1);
+
+    let password_changed_pos = Rect::new(
+        two_factor_pos.left(), 
+        two_factor_pos.bottom(), 
+        two_factor_pos.width, 1);
 
     let color_mode_error = Style::default().fg(Color::Red);
 
@@ -74,6 +82,22 @@ fn draw_profile_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
         });
 
     f.render_widget(new_password_input, new_password_pos);
+    draw_strength_meter(f, app.user.get_new_password_strength().as_ref(), strength_pos);
+
+    // confirm password block
+    let confirm_password_input = Paragraph::new(app.user.get_confirm_password())
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Confirm New Password"))
+        .style(match app.user.get_profile_mode() {
+            Profile::ConfirmPassword => {
+                match app.user.user_mode {
+                    UserMode::ConfirmPassword => color_mode_insert,
+                    _ => color_mode_error,
+                }
+            }
+            _ => Style::default(),
+        });
+
+    f.render_widget(confirm_password_input, confirm_password_pos);
 
 
     let (msg, style) = match app.user.user_mode {
@@ -93,7 +117,7 @@ fn draw_profile_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
             Style::default()
         ),
 
-        UserMode::NewPassword => (
+        UserMode::NewPassword | UserMode::ConfirmPassword | UserMode::Code => (
             vec![
                 Span::raw("You are now in "),
                 Span::styled("Insert Mode", Style::default().add_modifier(Modifier::BOLD).fg(Color::Yellow)),
@@ -108,38 +132,179 @@ fn draw_profile_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     let help_message = Paragraph::new(user_mode).alignment(Alignment::Center);
     f.render_widget(help_message, user_mode_pos);
 
-    // Error blinkers
-    let error_response = app.user.get_new_secure_password_error_message();
-    let (error_message, error_message_style) = match app.user.user_mode {
-        UserMode::Normal => (
-            vec![
-                Span::styled("System Error: ", Style::default().add_modifier(Modifier::BOLD).fg(Color::Red)),
-                Span::styled(error_response, Style::default().fg(Color::Red)),
-            ],
+    // Two factor status, with the recovery codes left
+    let two_factor = Paragraph::new(Spans::from(match app.user.get_totp_enabled() {
+        true => vec![
+            Span::styled("Two-factor: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("enabled", Style::default().fg(Color::Green)),
+            Span::raw(format!(", {} recovery codes left", app.user.get_recovery_codes_remaining())),
+        ],
+        false => vec![
+            Span::styled("Two-factor: ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::styled("disabled", Style::default().fg(Color::DarkGray)),
+        ],
+    })).alignment(Alignment::Center);
+    f.render_widget(two_factor, two_factor_pos);
+
+    // When the password was last changed, with how long ago
+    let password_changed = app.user.get_password_changed_at()
+        .and_then(|changed_at| Local.timestamp_opt(changed_at, 0).single().map(|time| (changed_at, time)))
+        .map(|(changed_at, time)| format!("{} ({})", time.format("%Y-%m-%d %H:%M"), format_age(Utc::now().timestamp() - changed_at)))
+        .unwrap_or_else(|| "unknown".to_string());
+    let password_changed = Paragraph::new(Spans::from(vec![
+        Span::styled("Password last changed: ", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(password_changed),
+    ])).alignment(Alignment::Center);
+    f.render_widget(password_changed, password_changed_pos);
+
+    // Outcome of the last change, each reason a new password is refused for on its own line
+    let (outcome, outcome_style) = match app.user.get_new_secure_password_error() {
+        Some(AuthError::PolicyViolation(reasons)) => (
+            std::iter::once(Spans::from(Span::styled("Password not changed:", Style::default().add_modifier(Modifier::BOLD).fg(Color::Red))))
+                .chain(reasons.iter().map(|reason| Spans::from(Span::styled(format!("- {}", reason), Style::default().fg(Color::Red)))))
+                .collect(),
             Style::default().add_modifier(Modifier::RAPID_BLINK),
         ),
-        UserMode::OldPassword => (
-            vec![
+        Some(error) => (
+            vec![Spans::from(vec![
                 Span::styled("System Error: ", Style::default().add_modifier(Modifier::BOLD).fg(Color::Red)),
-                Span::styled(error_response, Style::default().fg(Color::Red)),
-            ],
+                Span::styled(auth_error_message(&error), Style::default().fg(Color::Red)),
+            ])],
             Style::default().add_modifier(Modifier::RAPID_BLINK),
         ),
-        UserMode::NewPassword => (
-            vec![
-                Span::styled("System Error: ", Style::default().add_modifier(Modifier::BOLD).fg(Color::Red)),
-                Span::styled(error_response, Style::default().fg(Color::Red)),
-            ],
-            Style::default().add_modifier(Modifier::RAPID_BLINK),
+        None if app.user.get_password_changed() => (
+            vec![Spans::from(Span::styled("Password changed", Style::default().add_modifier(Modifier::BOLD).fg(Color::Green)))],
+            Style::default(),
         ),
-        _ => (vec![], Style::default()),
+        None => (Vec::new(), Style::default()),
+    };
+
+    // Below the status lines, clamped to the terminal
+    let outcome_pos = Rect::new(
+        password_changed_pos.left(), 
+        password_changed_pos.bottom() + 1, 
+        password_changed_pos.width, 
+        (outcome.len() as u16).min(f.size().bottom().saturating_sub(password_changed_pos.bottom() + 1)));
+
+    let mut outcome = Text::from(outcome);
+    outcome.patch_style(outcome_style);
+    f.render_widget(Paragraph::new(outcome).alignment(Alignment::Center), outcome_pos);
+
+    if app.user.get_totp_enrollment().is_some() {
+        draw_totp_enrollment(f, app, area);
+    } else if app.user.get_recovery_codes().is_some() {
+        draw_recovery_codes(f, app, area);
+    }
+}
+
+/// Age of a change in the largest whole unit, such as `3 days ago`
+fn format_age(seconds: i64) -> String {
+    let (count, unit) = match seconds.max(0) {
+        seconds if seconds < 60 => return "just now".to_string(),
+        seconds if seconds < 3600 => (seconds / 60, "minute"),
+        seconds if seconds < 86400 => (seconds / 3600, "hour"),
+        seconds => (seconds / 86400, "day"),
+    };
+
+    match count {
+        1 => format!("1 {} ago", unit),
+        count => format!("{} {}s ago", count, unit),
+    }
+}
+
+/// Recovery codes just generated, shown until dismissed
+fn draw_recovery_codes<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+
+    let codes = match app.user.get_recovery_codes() {
+        Some(codes) => codes,
+        None => return,
     };
 
-    let mut user_error = Text::from(Spans::from(error_message.to_owned()));
-    user_error.patch_style(error_message_style);
+    let mut text = vec![
+        Spans::from(Span::raw("Each code can be used once instead of a code")),
+        Spans::from(Span::raw("from your authenticator app. Store them safely,")),
+        Spans::from(Span::raw("they will not be shown again.")),
+        Spans::from(Span::raw("")),
+    ];
+    text.extend(codes.iter().map(|code| Spans::from(
+        Span::styled(code.expose(), Style::default().add_modifier(Modifier::BOLD)))));
+    text.push(Spans::from(Span::raw("")));
+    text.push(Spans::from(Span::styled("Press x to close", Style::default().fg(Color::LightBlue))));
+
+    // Clamped to the tab
+    let width = 52.min(area.width);
+    let height = (text.len() as u16 + 2).min(area.height);
+    let panel_pos = Rect::new(
+        area.left() + (area.width - width) / 2, 
+        area.top() + (area.height - height) / 2, 
+        width, height);
+
+    let panel = Paragraph::new(text)
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Recovery codes"))
+        .alignment(Alignment::Center);
+
+    f.render_widget(Clear, panel_pos);
+    f.render_widget(panel, panel_pos);
+}
+
+/// Secret of a pending enrollment with the input for its first code
+fn draw_totp_enrollment<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
+
+    let enrollment = match app.user.get_totp_enrollment() {
+        Some(enrollment) => enrollment,
+        None => return,
+    };
+
+    let qr_height = enrollment.qr_code.lines().count() as u16;
+    let qr_width = enrollment.qr_code.lines().map(|line| line.chars().count()).max().unwrap_or(0) as u16;
+
+    // QR code, secret, code input and error, clamped to the tab
+    let width = (qr_width.max(enrollment.secret.expose().len() as u16) + 4).min(area.width);
+    let height = (qr_height + 9).min(area.height);
+    let panel_pos = Rect::new(
+        area.left() + (area.width - width) / 2, 
+        area.top() + (area.height - height) / 2, 
+        width, height);
+
+    let chunks = Layout::default()
+        .margin(1)
+        .constraints(
+            [
+                Constraint::Min(1),
+                Constraint::Length(2),
+                Constraint::Length(3),
+                Constraint::Length(1),
+            ].as_ref(),
+        ).split(panel_pos);
+
+    let panel = Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title("Scan with your authenticator app");
+
+    let qr_code = Paragraph::new(enrollment.qr_code.as_str()).alignment(Alignment::Center);
+    let secret = Paragraph::new(vec![
+        Spans::from(Span::raw("or enter the secret")),
+        Spans::from(Span::styled(enrollment.secret.expose(), Style::default().add_modifier(Modifier::BOLD))),
+    ]).alignment(Alignment::Center);
+
+    let code_input = Paragraph::new(app.user.get_profile_code())
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Code"))
+        .style(match app.user.user_mode {
+            UserMode::Code => Style::default().fg(Color::Yellow),
+            _ => Style::default().fg(Color::LightBlue),
+        });
+
+    f.render_widget(Clear, panel_pos);
+    f.render_widget(panel, panel_pos);
+    f.render_widget(qr_code, chunks[0]);
+    f.render_widget(secret, chunks[1]);
+    f.render_widget(code_input, chunks[2]);
 
-    let user_error_help_message = Paragraph::new(user_error).alignment(Alignment::Center);
-    if app.user.get_new_secure_password_error_message().len() > 0 {
-        f.render_widget(user_error_help_message, error_message_pos);
+    // The panel covers the error blinker of the tab
+    if let Some(error) = app.user.get_new_secure_password_error() {
+        let error_message = Paragraph::new(Span::styled(auth_error_message(&error), Style::default().fg(Color::Red)))
+            .alignment(Alignment::Center);
+        f.render_widget(error_message, chunks[3]);
     }
 }
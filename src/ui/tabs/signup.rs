@@ -5,18 +5,24 @@ use tui::{backend::Backend, Frame,
 };
 
 use crate::{ App,
-    backend::service::user::{SignUp, UserMode},
+    backend::service::{security::policy::{Policy, DEFAULT_ROLE}, user::{SignUp, UserMode, UsernameStatus}},
     component::block::centered_rect_a, ui::app};
-use super::utility::helper::draw_help_signup;
+use super::utility::{helper::draw_help_signup, message::auth_error_message, strength::{draw_strength_meter, STRENGTH_HEIGHT}};
+
+/// Lines taken by the username status, kept the same while typing so the fields below do not move
+const USERNAME_STATUS_HEIGHT: u16 = 2;
 
 pub fn draw_signup<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 
+    // Help holds the key bindings followed by the password rules of the policy
+    let help_height = 13 + Policy::global().rules(DEFAULT_ROLE).describe().len() as u16;
+
     let chunks = Layout::default()
         .constraints(
             [
                 Constraint::Length(2),
                 Constraint::Min(1),
-                Constraint::Length(11),
+                Constraint::Length(help_height),
             ].as_ref(),
         ).split(area);
 
@@ -38,12 +44,14 @@ pub fn draw_signup<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 fn draw_signup_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
 
     let username_pos = centered_rect_a(30, 8, f.size());
-    let password_pos = Rect::new(username_pos.left(), username_pos.bottom(), username_pos.width, 3);
+    let username_status_pos = Rect::new(username_pos.left(), username_pos.bottom(), username_pos.width, USERNAME_STATUS_HEIGHT);
+    let password_pos = Rect::new(username_status_pos.left(), username_status_pos.bottom(), username_status_pos.width, 3);
+    let strength_pos = Rect::new(password_pos.left(), password_pos.bottom(), password_pos.width, STRENGTH_HEIGHT);
 
     let user_mode_pos = Rect::new(
-        password_pos.left(), 
-        password_pos.bottom(), 
-        password_pos.width, 3);
+        strength_pos.left(), 
+        strength_pos.bottom(), 
+        strength_pos.width, 3);
 
     let username_error_message_pos = Rect::new(
         user_mode_pos.left(), 
@@ -73,6 +81,7 @@ fn draw_signup_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
         });
 
     f.render_widget(username_input, username_pos);
+    draw_username_status(f, app.user.get_signup_username_status(), username_status_pos);
 
     // password block
     let password_input = Paragraph::new(app.user.get_signup_password())
@@ -88,9 +97,10 @@ fn draw_signup_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
         });
 
     f.render_widget(password_input, password_pos);
+    draw_strength_meter(f, app.user.get_signup_strength().as_ref(), strength_pos);
 
     // Username error blocks - optional
-    let _username_error_message = Paragraph::new(app.user.get_signup_username_error_message())
+    let _username_error_message = Paragraph::new(app.user.get_signup_username_error().map(|error| auth_error_message(&error)).unwrap_or_default())
         .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Error Response")
             .title_alignment(tui::layout::Alignment::Left))
         .style(match app.user.signup {
@@ -110,7 +120,7 @@ fn draw_signup_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     });
 
     // Password error blocks - optional
-    let _password_error_message = Paragraph::new(app.user.get_signup_password_error_message())
+    let _password_error_message = Paragraph::new(app.user.get_signup_password_error().map(|error| auth_error_message(&error)).unwrap_or_default())
         .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded).title("Error Response")
             .title_alignment(tui::layout::Alignment::Left))
         .style(match app.user.signup {
@@ -161,8 +171,8 @@ fn draw_signup_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     let help_message = Paragraph::new(user_mode).alignment(Alignment::Center);
     f.render_widget(help_message, user_mode_pos);
 
-    let username_response = app.user.get_signup_username_error_message();
-    let password_response = app.user.get_signup_password_error_message();
+    let username_response = app.user.get_signup_username_error().map(|error| auth_error_message(&error)).unwrap_or_default();
+    let password_response = app.user.get_signup_password_error().map(|error| auth_error_message(&error)).unwrap_or_default();
 
     let (error_username, error_username_style) = match app.user.user_mode {
         UserMode::Normal => (
@@ -223,12 +233,43 @@ fn draw_signup_block<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     let user_error_help_message = Paragraph::new(user_error).alignment(Alignment::Center);
     let pass_error_help_message = Paragraph::new(pass_error).alignment(Alignment::Center);
 
-    if app.user.get_signup_username_error_message().len() > 0 {
+    if app.user.get_signup_username_error().is_some() {
         f.render_widget(user_error_help_message, username_error_message_pos);
     }
 
-    if app.user.get_signup_password_error_message().len() > 0 {
+    if app.user.get_signup_password_error().is_some() {
         f.render_widget(pass_error_help_message, password_error_message_pos);
     }
 
 }
+
+/// Status of the username being typed, checked once typing pauses, followed by free
+/// usernames when it is taken
+/// - status : None until a character is typed, nothing is drawn
+fn draw_username_status<B: Backend>(f: &mut Frame<B>, status: Option<&UsernameStatus>, area: Rect) {
+
+    let text = match status {
+        Some(UsernameStatus::Checking) => vec![
+            Spans::from(Span::styled("Checking username...", Style::default().fg(Color::DarkGray))),
+        ],
+        Some(UsernameStatus::Available) => vec![
+            Spans::from(Span::styled("Username is available", Style::default().fg(Color::Green))),
+        ],
+        Some(UsernameStatus::Invalid(error)) => vec![
+            Spans::from(Span::styled(auth_error_message(error), Style::default().fg(Color::Red))),
+        ],
+        Some(UsernameStatus::Taken(suggestions)) if suggestions.is_empty() => vec![
+            Spans::from(Span::styled("Username is taken", Style::default().fg(Color::Yellow))),
+        ],
+        Some(UsernameStatus::Taken(suggestions)) => vec![
+            Spans::from(Span::styled("Username is taken", Style::default().fg(Color::Yellow))),
+            Spans::from(vec![
+                Span::styled("Try: ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(suggestions.join(", ")),
+            ]),
+        ],
+        None => return,
+    };
+
+    f.render_widget(Paragraph::new(text).alignment(Alignment::Center), area);
+}
@@ -1,2 +1,4 @@
 pub mod helper;
+pub mod message;
 pub mod popup;
+pub mod strength;
@@ -7,11 +7,14 @@ use tui::{
     widgets::{Block, Borders, Wrap, Paragraph}
 };
 
-use crate::App;
+use crate::{
+    App,
+    backend::service::security::policy::{Policy, DEFAULT_ROLE},
+};
 
 
 pub fn draw_help_welcome<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
-    let text = vec![
+    let mut text = vec![
         Spans::from(vec![
             Span::styled("mode: ", Style::default().fg(Color::White)),
             Span::from("There are two modes, [insert] mode and [normal] mode."),
@@ -46,6 +49,14 @@ pub fn draw_help_welcome<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect
         ]),
     ];
 
+    // Member tabs can log out or hand over to another user
+    if app.user.get_signed_in() {
+        text.insert(text.len() - 1, Spans::from(vec![
+            Span::styled("[normal] o / s : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Log out, or log out and switch to another user"),
+        ]));
+    }
+
     let block = Block::default().borders(Borders::ALL).title(Span::styled(
         "Help",
         Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
@@ -57,7 +68,7 @@ pub fn draw_help_welcome<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect
 
 
 pub fn draw_help_signup<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
-    let text = vec![
+    let mut text = vec![
         Spans::from(vec![
             Span::from("There are two modes, "),
             Span::styled("[insert] mode", Style::default().fg(Color::Yellow)),
@@ -70,6 +81,11 @@ pub fn draw_help_signup<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect)
             Span::from("To exit insert mode and return to normal mode"),
         ]),
 
+        Spans::from(vec![
+            Span::styled("[insert] Username : ", Style::default().fg(Color::Yellow)),
+            Span::from("Checked once typing pauses, free usernames are suggested if it is taken"),
+        ]),
+
         Spans::from(vec![
             Span::styled("[normal] w : ", Style::default().fg(Color::LightBlue)),
             Span::from("Submit your input and create an account"),
@@ -104,9 +120,14 @@ pub fn draw_help_signup<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect)
             Span::styled("[normal] q : ", Style::default().fg(Color::LightBlue)),
             Span::from("Quit"),
         ]),
-
     ];
 
+    // Generated from the active policy so the help never drifts from what is enforced
+    text.push(Spans::from(Span::styled("Password rules :", Style::default().fg(Color::Green))));
+    for rule in Policy::global().rules(DEFAULT_ROLE).describe() {
+        text.push(Spans::from(Span::from(format!("  - {}", rule))));
+    }
+
     let block = Block::default().borders(Borders::ALL).title(Span::styled(
         "Help",
         Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
@@ -140,6 +161,11 @@ pub fn draw_help_login<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect)
             Span::from("Enter [insert] mode where input can be entered"),
         ]),
 
+        Spans::from(vec![
+            Span::styled("[normal] x : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Cancel the one time code and start the login again"),
+        ]),
+
         Spans::from(vec![
             Span::styled("[normal] j : ", Style::default().fg(Color::LightBlue)),
             Span::from("Move cursor down, if an option is presented"),
@@ -176,6 +202,70 @@ pub fn draw_help_login<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect)
 }
 
 
+pub fn draw_help_lock<B: Backend>(f: &mut Frame<B>, _app: &mut App, area: Rect) {
+    let text = vec![
+        Spans::from(vec![
+            Span::styled("[insert] Enter : ", Style::default().fg(Color::Yellow)),
+            Span::from("Unlock the session with your password"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[insert] Escape : ", Style::default().fg(Color::Yellow)),
+            Span::from("Log out, the next login needs your password again"),
+        ]),
+    ];
+
+    let block = Block::default().borders(Borders::ALL).title(Span::styled(
+        "Help",
+        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+    ));
+
+    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+pub fn draw_help_activity<B: Backend>(f: &mut Frame<B>, _app: &mut App, area: Rect) {
+    let text = vec![
+        Spans::from(vec![
+            Span::styled("[normal] j / k : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Move down or up the list of events, latest first"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] r : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Reload the events and check the audit log again"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] o / s : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Log out, or log out and switch to another user"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] l : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Move tab right"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] h : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Move tab left"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] q : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Quit"),
+        ]),
+    ];
+
+    let block = Block::default().borders(Borders::ALL).title(Span::styled(
+        "Help",
+        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+    ));
+
+    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
 pub fn draw_help_profile<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect) {
     let text = vec![
         Spans::from(vec![
@@ -192,7 +282,7 @@ pub fn draw_help_profile<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect
 
         Spans::from(vec![
             Span::styled("[normal] w : ", Style::default().fg(Color::LightBlue)),
-            Span::from("Submit your input and create an account"),
+            Span::from("Change your password, the new password is typed twice"),
         ]),
 
         Spans::from(vec![
@@ -200,6 +290,21 @@ pub fn draw_help_profile<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect
             Span::from("Enter [insert] mode where input can be entered"),
         ]),
 
+        Spans::from(vec![
+            Span::styled("[normal] t : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Set up two-factor authentication with an authenticator app"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] x : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Cancel the two-factor setup, or close the recovery codes"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] r : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Generate a new set of recovery codes, the previous set stops working"),
+        ]),
+
         Spans::from(vec![
             Span::styled("[normal] j : ", Style::default().fg(Color::LightBlue)),
             Span::from("Move cursor down, if an option is presented"),
@@ -220,6 +325,137 @@ pub fn draw_help_profile<B: Backend>(f: &mut Frame<B>, app: &mut App, area: Rect
             Span::from("Move tab left"),
         ]),
 
+        Spans::from(vec![
+            Span::styled("[normal] o / s : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Log out, or log out and switch to another user"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] q : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Quit"),
+        ]),
+    ];
+
+    let block = Block::default().borders(Borders::ALL).title(Span::styled(
+        "Help",
+        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+    ));
+
+    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+pub fn draw_help_credential_manager<B: Backend>(f: &mut Frame<B>, _app: &mut App, area: Rect) {
+    let text = vec![
+        Spans::from(vec![
+            Span::from("There are two modes, "),
+            Span::styled("[insert] mode", Style::default().fg(Color::Yellow)),
+            Span::from(" and "),
+            Span::styled("[normal] mode", Style::default().fg(Color::LightBlue)),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[insert] Escape : ", Style::default().fg(Color::Yellow)),
+            Span::from("To exit insert mode and return to normal mode"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] w : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Encrypt and save the credential to your vault"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] i : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Enter [insert] mode where input can be entered"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] j : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Select the next credential, or move to the next field"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] k : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Select the previous credential, or move to the previous field"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] / : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Search credentials by app or username, Escape clears the search"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] v : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Show or hide the password of the selected credential"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] e : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Edit the selected credential, w saves it over the existing entry"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] d : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Delete the selected credential, press d again to confirm"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] Tab : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Switch between the credential list and the new credential form"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] l : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Move tab right"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] h : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Move tab left"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] o / s : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Log out, or log out and switch to another user"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] q : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Quit"),
+        ]),
+    ];
+
+    let block = Block::default().borders(Borders::ALL).title(Span::styled(
+        "Help",
+        Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+    ));
+
+    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
+    f.render_widget(paragraph, area);
+}
+
+pub fn draw_help_diagnostics<B: Backend>(f: &mut Frame<B>, _app: &mut App, area: Rect) {
+    let text = vec![
+        Spans::from(vec![
+            Span::styled("[normal] r : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Reload the username lists and settings, administrators only"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] o / s : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Log out, or log out and switch to another user"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] l : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Move tab right"),
+        ]),
+
+        Spans::from(vec![
+            Span::styled("[normal] h : ", Style::default().fg(Color::LightBlue)),
+            Span::from("Move tab left"),
+        ]),
+
         Spans::from(vec![
             Span::styled("[normal] q : ", Style::default().fg(Color::LightBlue)),
             Span::from("Quit"),
@@ -0,0 +1,15 @@
+use crate::backend::service::{security::rate_limiter::format_wait, utility::error::AuthError};
+
+/// Message shown to the user for an authentication error
+/// - unknown usernames and wrong passwords share a message, so usernames cannot be guessed
+pub fn auth_error_message(error: &AuthError) -> String {
+    match error {
+        AuthError::UnknownUser | AuthError::InvalidCredentials => "Invalid credentials, try again".to_string(),
+        AuthError::LockedOut(wait) => format!("Too many failed attempts, try again in {}", format_wait(*wait)),
+        AuthError::CodeRequired => "Enter the 6-digit code from your authenticator app".to_string(),
+        AuthError::SessionExpired => "Session expired, log in again".to_string(),
+        AuthError::PolicyViolation(reasons) => reasons.join(", "),
+        AuthError::UsernameTaken => "Username is taken or looks like an existing username".to_string(),
+        AuthError::Storage(cause) => format!("Storage error, {}", cause),
+    }
+}
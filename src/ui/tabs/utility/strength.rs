@@ -0,0 +1,58 @@
+use tui::{
+    backend::Backend,
+    Frame,
+    layout::{Rect, Constraint, Layout},
+    widgets::{LineGauge, Paragraph, Wrap},
+    text::{Span, Spans},
+    style::{Style, Modifier, Color},
+    symbols,
+};
+
+use crate::backend::service::security::strength::Estimate;
+
+/// Lines taken by the strength meter, kept the same while typing so the fields below do not move
+pub const STRENGTH_HEIGHT: u16 = 5;
+
+/// Color of each score, from very weak to very strong
+const SCORE_COLORS: [Color; 5] = [Color::Red, Color::LightRed, Color::Yellow, Color::LightGreen, Color::Green];
+
+/// Live strength meter of a password being typed: a gauge of the score, the time to crack
+/// the password offline, then the main weakness and how to improve it
+/// - estimate : None until a character is typed, nothing is drawn
+pub fn draw_strength_meter<B: Backend>(f: &mut Frame<B>, estimate: Option<&Estimate>, area: Rect) {
+
+    let estimate = match estimate {
+        Some(estimate) => estimate,
+        None => return,
+    };
+
+    let chunks = Layout::default()
+        .constraints(
+            [
+                Constraint::Length(1),
+                Constraint::Min(0),
+            ].as_ref(),
+        ).split(area);
+
+    let color = SCORE_COLORS[estimate.score];
+    let gauge = LineGauge::default()
+        .gauge_style(Style::default().fg(color))
+        .line_set(symbols::line::THICK)
+        .label(Span::styled(format!("{:<12}", estimate.label()), Style::default().fg(color).add_modifier(Modifier::BOLD)))
+        .ratio(estimate.ratio());
+    f.render_widget(gauge, chunks[0]);
+
+    let mut text = vec![Spans::from(vec![
+        Span::styled("Crack time: ", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(estimate.crack_time.as_str()),
+    ])];
+    if let Some(warning) = &estimate.warning {
+        text.push(Spans::from(Span::styled(warning.as_str(), Style::default().fg(Color::Yellow))));
+    }
+    text.extend(estimate.suggestions.iter().map(|suggestion| {
+        Spans::from(Span::styled(format!("- {}", suggestion), Style::default().fg(Color::DarkGray)))
+    }));
+
+    let hints = Paragraph::new(text).wrap(Wrap { trim: true });
+    f.render_widget(hints, chunks[1]);
+}
@@ -6,7 +6,7 @@ use tui::{backend::Backend, Frame,
 
 use crate::{
     App,
-    backend::service::utility::constants::ENROLMENT_INSTRUCTIONS,
+    backend::service::security::policy::Policy,
 };
 use super::utility::helper::draw_help_welcome;
 
@@ -51,7 +51,7 @@ fn draw_welcome_block<B: Backend>(f: &mut Frame<B>,app: &mut App, area: Rect) {
                 ].as_ref(),
             ).direction(Direction::Horizontal) .split(chunks[0]);
 
-        let instruction = Paragraph::new(ENROLMENT_INSTRUCTIONS)
+        let instruction = Paragraph::new(Policy::global().enrolment_instructions())
             .style(Style::default().fg(Color::Green))
             .alignment(Alignment::Left)
             // .wrap(Wrap { trim: true })